@@ -0,0 +1,526 @@
+/*
+Command line prover and verifier for the experiment circuits.
+
+    halo2-exp prove merkle-v3 --params kzg.params --input witness.json --out proof.json
+    halo2-exp verify --params kzg.params --proof proof.json --leaf 99 --root <root printed by prove>
+    halo2-exp cost merkle-tree-2 merkle-tree-3 --out cost.json
+    halo2-exp layout --out-dir prints
+    halo2-exp dot-graph --out-dir prints
+
+Proofs are generated with the KZG backend over BN254 and written as proof bundles (JSON if the output file ends
+with .json, binary otherwise). The verifier rebuilds the verifying key from the circuit and the parameters
+stored in the bundle, checks its fingerprint and verifies the proof. The public inputs stored in the bundle are only
+accepted if they match the values expected by the verifier, given on the command line (--leaf and --root for a Merkle
+proof, --digest for a Poseidon proof).
+
+The KZG parameters are loaded with --params from the file of a trusted setup ceremony (in the format of
+kzg::write_params), which must hold parameters for at least 2^k rows and is the same for the prover and the verifier.
+--insecure-dev-setup <SEED> generates them from a seed instead, which is only suitable for tests: whoever knows the seed
+can forge proofs. The public inputs computed by the prover (the root of the tree, the digest of the hash) are stored in
+the instances of the bundle and printed by prove as decimals, the encoding accepted by --leaf, --root and --digest.
+
+The cost command measures the experiments registered in halo2_experiments::experiments (all of them if no name
+is given) and outputs the report as JSON. The layout command, available with the dev-graph feature, renders the
+layout of every experiment as a PNG. The dot-graph command, available with the dot-graph feature, writes the DOT
+graph of every experiment.
+
+Exit codes: 0 if the command succeeded, 1 if the proof is invalid, 2 for any other error.
+*/
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use halo2_experiments::{
+    chips::poseidon_spec::P128Pow5T3Bn256,
+    circuits::{merkle_v3::MerkleTreeV3Circuit, poseidon::PoseidonCircuit},
+    cost::CostVisitor,
+    experiments::{experiment_names, visit_experiments},
+    merkle_tree::MerkleProof,
+    proving::{kzg, min_k},
+    serialization::{
+        field_from_str, field_to_decimal, vk_fingerprint, CircuitParams, ProofBundle,
+    },
+};
+use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength};
+use halo2_proofs::{
+    circuit::Value,
+    halo2curves::bn256::{Bn256, Fr},
+    plonk::Circuit,
+    poly::kzg::commitment::ParamsKZG,
+};
+use serde::Deserialize;
+use std::{error::Error, fs, path::PathBuf, process::ExitCode};
+
+const POSEIDON_SPEC: &str = "P128Pow5T3Bn256";
+
+#[derive(Parser)]
+#[command(
+    name = "halo2-exp",
+    about = "Prover and verifier for the halo2 experiment circuits"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generates a proof bundle from a JSON witness file
+    Prove {
+        circuit: CircuitId,
+        /// JSON witness file
+        #[arg(long)]
+        input: PathBuf,
+        /// Output proof bundle, encoded as JSON if the file name ends with .json
+        #[arg(long)]
+        out: PathBuf,
+        #[command(flatten)]
+        setup: Setup,
+    },
+    /// Verifies a proof bundle
+    Verify {
+        /// Proof bundle, either JSON or binary
+        #[arg(long)]
+        proof: PathBuf,
+        #[command(flatten)]
+        setup: Setup,
+        #[command(flatten)]
+        expected: ExpectedPublicInputs,
+    },
+    /// Measures the cost of the experiment circuits and outputs it as JSON
+    Cost {
+        /// Names of the experiments to measure, all of them if none is given
+        names: Vec<String>,
+        /// Output file, the report is printed if missing
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Renders the layout of every experiment circuit as a PNG
+    #[cfg(feature = "dev-graph")]
+    Layout {
+        #[arg(long, default_value = "prints")]
+        out_dir: PathBuf,
+    },
+    /// Writes the Graphviz DOT graph of every experiment circuit
+    #[cfg(feature = "dot-graph")]
+    DotGraph {
+        #[arg(long, default_value = "prints")]
+        out_dir: PathBuf,
+    },
+}
+
+// Source of the KZG parameters, exactly one of them is required. The prover and the verifier must use the same
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+struct Setup {
+    /// Parameters of a trusted setup ceremony, for at least 2^k rows
+    #[arg(long)]
+    params: Option<PathBuf>,
+    /// Generates the parameters from a seed instead. Whoever knows the seed can forge proofs, only use it for tests
+    #[arg(long, value_name = "SEED")]
+    insecure_dev_setup: Option<u64>,
+}
+
+impl Setup {
+    fn kzg_params(&self, k: u32) -> Result<ParamsKZG<Bn256>, Box<dyn Error>> {
+        match (&self.params, self.insecure_dev_setup) {
+            (Some(path), _) => Ok(kzg::load_params(path, k)?),
+            (None, Some(seed)) => {
+                eprintln!(
+                    "warning: the KZG parameters are generated from a seed, proofs can be forged"
+                );
+                Ok(kzg::setup(k, seed))
+            }
+            (None, None) => Err("either --params or --insecure-dev-setup is required".into()),
+        }
+    }
+}
+
+// Public inputs the verifier expects, the ones of the circuit of the bundle are required
+#[derive(Args)]
+struct ExpectedPublicInputs {
+    /// Expected leaf of a merkle-v3 proof
+    #[arg(long)]
+    leaf: Option<String>,
+    /// Expected root of a merkle-v3 proof
+    #[arg(long)]
+    root: Option<String>,
+    /// Expected digest of a poseidon proof
+    #[arg(long)]
+    digest: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CircuitId {
+    /// Poseidon Merkle tree inclusion proof, depth 1 to 20
+    MerkleV3,
+    /// Poseidon hash of 1 to 4 field elements
+    Poseidon,
+}
+
+impl CircuitId {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::MerkleV3 => "merkle-v3",
+            Self::Poseidon => "poseidon",
+        }
+    }
+
+    // Names of the public inputs of the circuit, in the order of the instance column
+    fn public_input_names(&self) -> &'static [&'static str] {
+        match self {
+            Self::MerkleV3 => &["leaf", "root"],
+            Self::Poseidon => &["digest"],
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name {
+            "merkle-v3" => Ok(Self::MerkleV3),
+            "poseidon" => Ok(Self::Poseidon),
+            _ => Err(format!("unknown circuit {}", name).into()),
+        }
+    }
+}
+
+// Field elements are decimal strings or big-endian hex strings starting with 0x
+#[derive(Deserialize)]
+struct MerkleV3Witness {
+    leaf: String,
+    path_elements: Vec<String>,
+    path_indices: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct PoseidonWitness {
+    inputs: Vec<String>,
+}
+
+// Expands to a match over the supported values of a const generic parameter
+macro_rules! dispatch {
+    ($value:expr, $name:literal, [$($n:literal),*], $f:ident ( $($arg:expr),* )) => {
+        match $value {
+            $($n => $f::<$n>($($arg),*),)*
+            other => Err(format!("unsupported {} {}", $name, other).into()),
+        }
+    };
+}
+
+fn prove_circuit<C: Circuit<Fr>>(
+    circuit: C,
+    public_inputs: Vec<Vec<Fr>>,
+    circuit_id: CircuitId,
+    params: CircuitParams,
+    setup: &Setup,
+) -> Result<ProofBundle, Box<dyn Error>> {
+    let kzg_params = setup.kzg_params(params.k)?;
+    let pk = kzg::keys(&kzg_params, &circuit)?;
+    let proof = kzg::full_prover(&kzg_params, &pk, &circuit, &public_inputs)?;
+    Ok(ProofBundle::new(
+        circuit_id.name(),
+        params,
+        &public_inputs,
+        proof,
+        vk_fingerprint(pk.get_vk()),
+    ))
+}
+
+// Returns Ok(false) if the proof is invalid, Err if the bundle can't be checked
+fn verify_circuit<C: Circuit<Fr>>(
+    circuit: C,
+    bundle: &ProofBundle,
+    setup: &Setup,
+) -> Result<bool, Box<dyn Error>> {
+    let kzg_params = setup.kzg_params(bundle.params.k)?;
+    let vk = kzg::verifying_key(&kzg_params, &circuit)?;
+    bundle.check_vk(&vk)?;
+    let instances = bundle.instances::<Fr>()?;
+    Ok(kzg::full_verifier(&kzg_params, &vk, &bundle.proof.0, &instances).is_ok())
+}
+
+fn prove_merkle_v3<const DEPTH: usize>(
+    witness: &MerkleV3Witness,
+    setup: &Setup,
+) -> Result<ProofBundle, Box<dyn Error>> {
+    if witness.path_indices.len() != DEPTH || witness.path_indices.iter().any(|i| *i > 1) {
+        return Err("path_indices must contain one 0 or 1 for each path element".into());
+    }
+    let proof = MerkleProof {
+        leaf: field_from_str(&witness.leaf)?,
+        path_elements: witness
+            .path_elements
+            .iter()
+            .map(|x| field_from_str(x))
+            .collect::<Result<Vec<Fr>, _>>()?,
+        path_indices: witness
+            .path_indices
+            .iter()
+            .map(|i| Fr::from(*i as u64))
+            .collect(),
+        root: Fr::from(0),
+    };
+    let root = proof.compute_root::<P128Pow5T3Bn256>();
+
+    let circuit = MerkleTreeV3Circuit::<Fr, P128Pow5T3Bn256, DEPTH>::from_proof(&proof);
+    let public_inputs = vec![vec![proof.leaf, root]];
+    let params = CircuitParams {
+        k: min_k(&circuit, &public_inputs)?,
+        poseidon_spec: Some(POSEIDON_SPEC.to_string()),
+        depth: Some(DEPTH),
+        num_inputs: None,
+    };
+    prove_circuit(circuit, public_inputs, CircuitId::MerkleV3, params, setup)
+}
+
+fn verify_merkle_v3<const DEPTH: usize>(
+    bundle: &ProofBundle,
+    setup: &Setup,
+) -> Result<bool, Box<dyn Error>> {
+    let circuit = MerkleTreeV3Circuit::<Fr, P128Pow5T3Bn256, DEPTH>::new(
+        Value::unknown(),
+        [Value::unknown(); DEPTH],
+        [Value::unknown(); DEPTH],
+    );
+    verify_circuit(circuit, bundle, setup)
+}
+
+fn prove_poseidon<const L: usize>(
+    witness: &PoseidonWitness,
+    setup: &Setup,
+) -> Result<ProofBundle, Box<dyn Error>> {
+    let inputs = witness
+        .inputs
+        .iter()
+        .map(|x| field_from_str(x))
+        .collect::<Result<Vec<Fr>, _>>()?;
+    let inputs: [Fr; L] = inputs.try_into().unwrap();
+    let digest = poseidon::Hash::<_, P128Pow5T3Bn256, ConstantLength<L>, 3, 2>::init().hash(inputs);
+
+    let circuit = PoseidonCircuit::<Fr, P128Pow5T3Bn256, 3, 2, L>::new(
+        inputs.map(Value::known),
+        Value::known(digest),
+    );
+    let public_inputs = vec![vec![digest]];
+    let params = CircuitParams {
+        k: min_k(&circuit, &public_inputs)?,
+        poseidon_spec: Some(POSEIDON_SPEC.to_string()),
+        depth: None,
+        num_inputs: Some(L),
+    };
+    prove_circuit(circuit, public_inputs, CircuitId::Poseidon, params, setup)
+}
+
+fn verify_poseidon<const L: usize>(
+    bundle: &ProofBundle,
+    setup: &Setup,
+) -> Result<bool, Box<dyn Error>> {
+    let circuit = PoseidonCircuit::<Fr, P128Pow5T3Bn256, 3, 2, L>::new(
+        [Value::unknown(); L],
+        Value::unknown(),
+    );
+    verify_circuit(circuit, bundle, setup)
+}
+
+fn prove(
+    circuit: CircuitId,
+    input: &PathBuf,
+    out: &PathBuf,
+    setup: &Setup,
+) -> Result<(), Box<dyn Error>> {
+    let input = fs::read_to_string(input)?;
+    let bundle = match circuit {
+        CircuitId::MerkleV3 => {
+            let witness: MerkleV3Witness = serde_json::from_str(&input)?;
+            dispatch!(
+                witness.path_elements.len(),
+                "depth",
+                [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20],
+                prove_merkle_v3(&witness, setup)
+            )
+        }
+        CircuitId::Poseidon => {
+            let witness: PoseidonWitness = serde_json::from_str(&input)?;
+            dispatch!(
+                witness.inputs.len(),
+                "number of inputs",
+                [1, 2, 3, 4],
+                prove_poseidon(&witness, setup)
+            )
+        }
+    }?;
+
+    if out.extension().map_or(false, |ext| ext == "json") {
+        fs::write(out, bundle.to_json()?)?;
+    } else {
+        fs::write(out, bundle.to_bytes()?)?;
+    }
+    println!("proof written to {}", out.display());
+
+    // the verifier passes the public inputs with --leaf, --root and --digest, they are printed in the same encoding
+    for (name, value) in circuit
+        .public_input_names()
+        .iter()
+        .zip(bundle.instances::<Fr>()?.iter().flatten())
+    {
+        println!("{}: {}", name, field_to_decimal(value));
+    }
+    Ok(())
+}
+
+// Parses an expected public input, which is required for the circuit of the bundle
+fn expected_value(
+    value: Option<&str>,
+    arg: &str,
+    circuit: CircuitId,
+) -> Result<Fr, Box<dyn Error>> {
+    let value =
+        value.ok_or_else(|| format!("{} is required to verify a {} proof", arg, circuit.name()))?;
+    Ok(field_from_str(value)?)
+}
+
+fn verify(
+    proof: &PathBuf,
+    setup: &Setup,
+    expected: &ExpectedPublicInputs,
+) -> Result<bool, Box<dyn Error>> {
+    let bytes = fs::read(proof)?;
+    // JSON bundles are objects, binary bundles start with the version number
+    let bundle = if bytes.first() == Some(&b'{') {
+        ProofBundle::from_json(std::str::from_utf8(&bytes)?)?
+    } else {
+        ProofBundle::from_bytes(&bytes)?
+    };
+
+    match bundle.params.poseidon_spec.as_deref() {
+        Some(POSEIDON_SPEC) => {}
+        other => return Err(format!("unsupported Poseidon spec {:?}", other).into()),
+    }
+
+    // the proof only shows that the public inputs of the bundle are valid, they must be the ones the verifier expects
+    let circuit = CircuitId::from_name(&bundle.circuit)?;
+    let expected_values = match circuit {
+        CircuitId::MerkleV3 => vec![
+            expected_value(expected.leaf.as_deref(), "--leaf", circuit)?,
+            expected_value(expected.root.as_deref(), "--root", circuit)?,
+        ],
+        CircuitId::Poseidon => vec![expected_value(
+            expected.digest.as_deref(),
+            "--digest",
+            circuit,
+        )?],
+    };
+    let instances = bundle.instances::<Fr>()?;
+    if instances != vec![expected_values] {
+        eprintln!("the public inputs of the bundle are not the expected ones");
+        return Ok(false);
+    }
+
+    match circuit {
+        CircuitId::MerkleV3 => dispatch!(
+            bundle.params.depth.ok_or("missing depth")?,
+            "depth",
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20],
+            verify_merkle_v3(&bundle, setup)
+        ),
+        CircuitId::Poseidon => dispatch!(
+            bundle.params.num_inputs.ok_or("missing number of inputs")?,
+            "number of inputs",
+            [1, 2, 3, 4],
+            verify_poseidon(&bundle, setup)
+        ),
+    }
+}
+
+fn cost(names: Vec<String>, out: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let known_names = experiment_names();
+    if let Some(name) = names.iter().find(|name| !known_names.contains(name)) {
+        return Err(format!(
+            "unknown experiment {}, expected one of {}",
+            name,
+            known_names.join(", ")
+        )
+        .into());
+    }
+
+    let mut visitor = CostVisitor::new(names);
+    visit_experiments(&mut visitor);
+    if let Some((name, e)) = visitor.errors.first() {
+        return Err(format!("failed to measure {}: {}", name, e).into());
+    }
+
+    let report = serde_json::to_string_pretty(&visitor.costs)?;
+    match out {
+        Some(out) => fs::write(out, report)?,
+        None => println!("{}", report),
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Prove {
+            circuit,
+            input,
+            out,
+            setup,
+        } => match prove(circuit, &input, &out, &setup) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                ExitCode::from(2)
+            }
+        },
+        Command::Cost { names, out } => match cost(names, out) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                ExitCode::from(2)
+            }
+        },
+        #[cfg(feature = "dev-graph")]
+        Command::Layout { out_dir } => match halo2_experiments::graphs::render_layouts(&out_dir) {
+            Ok(paths) => {
+                for path in paths {
+                    println!("{}", path.display());
+                }
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                ExitCode::from(2)
+            }
+        },
+        #[cfg(feature = "dot-graph")]
+        Command::DotGraph { out_dir } => {
+            match halo2_experiments::graphs::write_dot_graphs(&out_dir) {
+                Ok(paths) => {
+                    for path in paths {
+                        println!("{}", path.display());
+                    }
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    ExitCode::from(2)
+                }
+            }
+        }
+        Command::Verify {
+            proof,
+            setup,
+            expected,
+        } => match verify(&proof, &setup, &expected) {
+            Ok(true) => {
+                println!("proof is valid");
+                ExitCode::SUCCESS
+            }
+            Ok(false) => {
+                eprintln!("proof is invalid");
+                ExitCode::from(1)
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                ExitCode::from(2)
+            }
+        },
+    }
+}
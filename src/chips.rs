@@ -1,8 +1,20 @@
+pub mod bit_decomposition;
 pub mod hash_v1;
 pub mod hash_v2;
+pub mod hash_instructions;
 pub mod inclusion_check;
 pub mod inclusion_check_v2;
-pub mod merkle_v1;
+pub mod merkle_index;
+pub mod merkle_public_positions;
+pub mod merkle_quaternary;
+pub mod merkle_tree;
+pub mod merkle_update;
 pub mod merkle_v2;
 pub mod poseidon;
+pub mod poseidon_permutation;
+pub mod poseidon_spec;
 pub mod merkle_v3;
+pub mod range_check;
+pub mod merkle_sum_tree;
+pub mod merkle_sum_tree_multi_asset;
+pub mod sparse_merkle_tree;
@@ -0,0 +1,133 @@
+/*
+Decomposition of a cell into N_BITS bits, least significant bit first.
+
+The value is decomposed into a running sum z_0, z_1, ..., z_N_BITS, where z_0 is the value and
+z_{i+1} = (z_i - bit_i) / 2. Each bit_i = z_i - 2 * z_{i+1} is assigned in its own column, so that it can be copied
+into other regions, and is constrained to be boolean. The last element of the running sum must be 0, therefore the
+decomposition only exists if the value is smaller than 2^N_BITS, and it is unique.
+
+| z        | bit        | bit_selector | zero_selector |
+| -------- | ---------- | ------------ | ------------- |
+| value    | bit_0      | 1            | 0             |
+| z_1      | bit_1      | 1            | 0             |
+| ...      | ...        | 1            | 0             |
+| z_N      |            | 0            | 1             |
+*/
+
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+
+#[derive(Debug, Clone)]
+pub struct BitDecompositionConfig {
+    pub z: Column<Advice>,
+    pub bit: Column<Advice>,
+    pub bit_selector: Selector,
+    pub zero_selector: Selector,
+}
+#[derive(Debug, Clone)]
+pub struct BitDecompositionChip<F: FieldExt, const N_BITS: usize> {
+    config: BitDecompositionConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt, const N_BITS: usize> BitDecompositionChip<F, N_BITS> {
+    pub fn construct(config: BitDecompositionConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        z: Column<Advice>,
+        bit: Column<Advice>,
+    ) -> BitDecompositionConfig {
+        // with N_BITS >= F::NUM_BITS both a value and the value + p would fit in N_BITS bits, the decomposition
+        // wouldn't be unique
+        assert!(
+            N_BITS < F::NUM_BITS as usize,
+            "{} bits don't fit in a field element of {} bits",
+            N_BITS,
+            F::NUM_BITS
+        );
+
+        let bit_selector = meta.selector();
+        let zero_selector = meta.selector();
+
+        // the value is copied into the running sum column and the bits are copied out of the bit column
+        meta.enable_equality(z);
+        meta.enable_equality(bit);
+
+        // Enforces that the bit is 0 or 1 and that it is the difference between z_cur and 2 * z_next
+        // s * bit * (1 - bit) = 0
+        // s * (z_cur - 2 * z_next - bit) = 0
+        meta.create_gate("bit decomposition", |meta| {
+            let s = meta.query_selector(bit_selector);
+            let z_cur = meta.query_advice(z, Rotation::cur());
+            let z_next = meta.query_advice(z, Rotation::next());
+            let bit = meta.query_advice(bit, Rotation::cur());
+            vec![
+                s.clone() * bit.clone() * (Expression::Constant(F::from(1)) - bit.clone()),
+                s * (z_cur - z_next * Expression::Constant(F::from(2)) - bit),
+            ]
+        });
+
+        // Enforces that the running sum ends at 0
+        // s * z = 0
+        meta.create_gate("bit decomposition end", |meta| {
+            let s = meta.query_selector(zero_selector);
+            let z = meta.query_advice(z, Rotation::cur());
+            vec![s * z]
+        });
+
+        BitDecompositionConfig {
+            z,
+            bit,
+            bit_selector,
+            zero_selector,
+        }
+    }
+
+    // Returns the cells containing the N_BITS bits of the value of the cell, least significant bit first
+    // The constraints can only be satisfied if the value is smaller than 2^N_BITS
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+    ) -> Result<[AssignedCell<F, F>; N_BITS], Error> {
+        layouter.assign_region(
+            || "bit decomposition",
+            |mut region| {
+                cell.copy_advice(
+                    || "copy value to be decomposed",
+                    &mut region,
+                    self.config.z,
+                    0,
+                )?;
+
+                let mut z = cell.value().map(|x| x.to_owned());
+                let inv_2 = F::from(2).invert().unwrap();
+                let mut bits = Vec::with_capacity(N_BITS);
+                for row in 0..N_BITS {
+                    self.config.bit_selector.enable(&mut region, row)?;
+
+                    let bit = z.map(|z| F::from((z.get_lower_128() & 1) as u64));
+                    bits.push(region.assign_advice(
+                        || "assign bit",
+                        self.config.bit,
+                        row,
+                        || bit,
+                    )?);
+
+                    // z_{i+1} = (z_i - bit_i) / 2
+                    z = z.zip(bit).map(|(z, bit)| (z - bit) * inv_2);
+                    region.assign_advice(|| "assign running sum", self.config.z, row + 1, || z)?;
+                }
+
+                self.config.zero_selector.enable(&mut region, N_BITS)?;
+                Ok(bits.try_into().unwrap())
+            },
+        )
+    }
+}
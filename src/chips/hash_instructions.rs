@@ -0,0 +1,26 @@
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
+use std::fmt::Debug;
+
+// Instructions of a chip hashing two field elements into one, such as the hash of two nodes of a Merkle tree.
+// Implemented by Hash2Chip (dummy hash) and PoseidonChip, so that the Merkle tree chips don't depend on the hash function
+pub trait TwoToOneHashInstructions<F: FieldExt>: Sized {
+    type Config: Clone + Debug;
+
+    // The advice columns and the instance column are the ones of the parent chip. A hash chip can either share
+    // them or create its own columns
+    fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> Self::Config;
+
+    fn construct(config: Self::Config) -> Self;
+
+    // Takes the cells containing the left and the right inputs and returns the cell containing the digest
+    fn hash_two(
+        &self,
+        layouter: impl Layouter<F>,
+        left: AssignedCell<F, F>,
+        right: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error>;
+}
@@ -1,5 +1,6 @@
 use std::marker::PhantomData;
 
+use super::hash_instructions::TwoToOneHashInstructions;
 use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
 
 #[derive(Debug, Clone)]
@@ -110,3 +111,29 @@ impl<F: FieldExt> Hash2Chip<F> {
         layouter.constrain_instance(c_cell.cell(), self.config.instance, row)
     }
 }
+
+impl<F: FieldExt> TwoToOneHashInstructions<F> for Hash2Chip<F> {
+    type Config = Hash2Config;
+
+    // The dummy hash shares the columns of the parent chip
+    fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> Hash2Config {
+        Self::configure(meta, advice, instance)
+    }
+
+    fn construct(config: Hash2Config) -> Self {
+        Self::construct(config)
+    }
+
+    fn hash_two(
+        &self,
+        layouter: impl Layouter<F>,
+        left: AssignedCell<F, F>,
+        right: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.hash(layouter, left, right)
+    }
+}
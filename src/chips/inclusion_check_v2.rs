@@ -80,14 +80,19 @@ impl<F: FieldExt> InclusionCheckV2Chip<F> {
     }
 
     // Assign rows for instance column passing the entry of the users
+    // The table can have any number of users, one row each, and inclusion_index must be one of its rows
     pub fn assign_rows(
         &self,
         mut layouter: impl Layouter<F>,
-        usernames: [Value<F>; 10],
-        balances: [Value<F>; 10],
+        usernames: &[Value<F>],
+        balances: &[Value<F>],
         zero_val: Value<F>,
-        inclusion_index: u8,
+        inclusion_index: usize,
     ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        if usernames.len() != balances.len() || inclusion_index >= usernames.len() {
+            return Err(Error::Synthesis);
+        }
+
         layouter.assign_region(
             || "user and balance table",
             |mut region| {
@@ -111,7 +116,7 @@ impl<F: FieldExt> InclusionCheckV2Chip<F> {
 
                 // loop over the username and balance arrays and assign the values to the table
                 for _i in 0..usernames.len() {
-                    if (_i as u8) == inclusion_index {
+                    if _i == inclusion_index {
                         self.config.selector.enable(&mut region, _i + 1)?;
 
                         region.assign_advice(
@@ -0,0 +1,113 @@
+use super::bit_decomposition::{BitDecompositionChip, BitDecompositionConfig};
+use super::merkle_v3::{MerkleTreeV3Chip, MerkleTreeV3Config};
+use halo2_gadgets::poseidon::primitives::Spec;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
+use std::marker::PhantomData;
+
+// Merkle tree of depth DEPTH where the position of the leaf is a single index instead of a list of path indices.
+// The index is decomposed into DEPTH bits, which are copied into the index cells of the layers of the
+// MerkleTreeV3Chip, so that the proof is bound to the position of the leaf
+#[derive(Debug, Clone)]
+pub struct MerkleTreeIndexConfig<F: FieldExt> {
+    pub merkle_tree_v3_config: MerkleTreeV3Config<F>,
+    pub bit_decomposition_config: BitDecompositionConfig,
+}
+#[derive(Debug, Clone)]
+pub struct MerkleTreeIndexChip<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> {
+    config: MerkleTreeIndexConfig<F>,
+    _marker: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> MerkleTreeIndexChip<F, S, DEPTH> {
+    pub fn construct(config: MerkleTreeIndexConfig<F>) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> MerkleTreeIndexConfig<F> {
+        // The levels of the tree are proven by the MerkleTreeV3Chip, the index is decomposed in the same columns
+        let merkle_tree_v3_config = MerkleTreeV3Chip::<F, S>::configure(meta, advice, instance);
+        let bit_decomposition_config =
+            BitDecompositionChip::<F, DEPTH>::configure(meta, advice[0], advice[1]);
+
+        MerkleTreeIndexConfig {
+            merkle_tree_v3_config,
+            bit_decomposition_config,
+        }
+    }
+
+    pub fn assign_leaf(
+        &self,
+        layouter: impl Layouter<F>,
+        leaf: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        MerkleTreeV3Chip::<F, S>::construct(self.config.merkle_tree_v3_config.clone())
+            .assign_leaf(layouter, leaf)
+    }
+
+    // Assigns the index and returns its cell together with the cells of its DEPTH bits, least significant bit first
+    // The constraints can only be satisfied if the index is smaller than 2^DEPTH
+    pub fn assign_index(
+        &self,
+        mut layouter: impl Layouter<F>,
+        index: Value<F>,
+    ) -> Result<(AssignedCell<F, F>, [AssignedCell<F, F>; DEPTH]), Error> {
+        let index_cell = layouter.assign_region(
+            || "assign index",
+            |mut region| {
+                region.assign_advice(
+                    || "assign index",
+                    self.config.merkle_tree_v3_config.advice[0],
+                    0,
+                    || index,
+                )
+            },
+        )?;
+
+        let bits = BitDecompositionChip::<F, DEPTH>::construct(
+            self.config.bit_decomposition_config.clone(),
+        )
+        .assign(layouter.namespace(|| "decompose index"), &index_cell)?;
+
+        Ok((index_cell, bits))
+    }
+
+    // Returns the cell containing the root of the tree, the bit of level i of the index being the swap bit of level i
+    pub fn merkle_prove(
+        &self,
+        mut layouter: impl Layouter<F>,
+        leaf_cell: &AssignedCell<F, F>,
+        index_bits: &[AssignedCell<F, F>; DEPTH],
+        path_elements: &[Value<F>; DEPTH],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let merkle_chip =
+            MerkleTreeV3Chip::<F, S>::construct(self.config.merkle_tree_v3_config.clone());
+
+        let mut node_cell = leaf_cell.clone();
+        for (bit, path_element) in index_bits.iter().zip(path_elements.iter()) {
+            node_cell = merkle_chip.merkle_prove_layer_with_index_cell(
+                layouter.namespace(|| "merkle prove layer"),
+                &node_cell,
+                *path_element,
+                bit,
+            )?;
+        }
+        Ok(node_cell)
+    }
+
+    // Enforce permutation check between input cell and instance column at row passed as input
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.merkle_tree_v3_config.instance, row)
+    }
+}
@@ -0,0 +1,165 @@
+use super::hash_instructions::TwoToOneHashInstructions;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+// Proves the inclusion of a batch of leaves at public positions in the same tree, generic over the hash function H
+// like the MerkleTreeChip. The positions of the leaves are known when the circuit is built, so the left and right nodes are ordered at
+// synthesis time and no swap constraint is needed. A node that is an ancestor of many leaves is hashed only once and
+// a sibling that is computed from another leaf is reused instead of being a witness, therefore all the leaves lead to
+// the same root cell. The positions are therefore part of the circuit and revealed by its verifying key
+#[derive(Debug, Clone)]
+pub struct MerkleTreePublicPositionsConfig<C> {
+    pub advice: [Column<Advice>; 3],
+    pub instance: Column<Instance>,
+    pub hash_config: C,
+}
+#[derive(Debug, Clone)]
+pub struct MerkleTreePublicPositionsChip<F: FieldExt, H: TwoToOneHashInstructions<F>> {
+    config: MerkleTreePublicPositionsConfig<H::Config>,
+    _marker: PhantomData<(F, H)>,
+}
+
+impl<F: FieldExt, H: TwoToOneHashInstructions<F>> MerkleTreePublicPositionsChip<F, H> {
+    pub fn construct(config: MerkleTreePublicPositionsConfig<H::Config>) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> MerkleTreePublicPositionsConfig<H::Config> {
+        // the leaves and the siblings are copied into the hash chip, the root and the leaves are public
+        for column in advice {
+            meta.enable_equality(column);
+        }
+        meta.enable_equality(instance);
+
+        let hash_config = H::configure(meta, advice, instance);
+
+        MerkleTreePublicPositionsConfig {
+            advice,
+            instance,
+            hash_config,
+        }
+    }
+
+    // Assigns the leaves in the first column, one per row
+    pub fn assign_leaves(
+        &self,
+        mut layouter: impl Layouter<F>,
+        leaves: &[Value<F>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        layouter.assign_region(
+            || "assign leaves",
+            |mut region| {
+                leaves
+                    .iter()
+                    .enumerate()
+                    .map(|(row, leaf)| {
+                        region.assign_advice(|| "assign leaf", self.config.advice[0], row, || *leaf)
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            },
+        )
+    }
+
+    // Returns the cell containing the root of the tree, computed from all the leaves.
+    // public_leaf_indices are the positions of the leaves, which must be distinct and are part of the shape of the
+    // circuit, and path_elements[i] is the authentication path of the leaf i. The path element of a level is only used if the sibling is not computed from another leaf.
+    // Returns Error::Synthesis if there is no leaf, if the lengths differ, or if a position is out of the tree or used twice
+    pub fn merkle_prove_batch(
+        &self,
+        mut layouter: impl Layouter<F>,
+        public_leaf_indices: &[usize],
+        leaf_cells: &[AssignedCell<F, F>],
+        path_elements: &[&[Value<F>]],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        if leaf_cells.is_empty()
+            || public_leaf_indices.len() != leaf_cells.len()
+            || path_elements.len() != leaf_cells.len()
+        {
+            return Err(Error::Synthesis);
+        }
+        let depth = path_elements[0].len();
+        if path_elements.iter().any(|path| path.len() != depth) {
+            return Err(Error::Synthesis);
+        }
+
+        // nodes of the current level computed in the circuit, indexed by their position in the level, together with
+        // one of the leaves below them, whose path provides the siblings of the next levels
+        let mut nodes = BTreeMap::new();
+        for (i, (index, cell)) in public_leaf_indices
+            .iter()
+            .zip(leaf_cells.iter())
+            .enumerate()
+        {
+            if index.checked_shr(depth as u32).unwrap_or(0) != 0
+                || nodes.insert(*index, (cell.clone(), i)).is_some()
+            {
+                return Err(Error::Synthesis);
+            }
+        }
+
+        let hash_chip = H::construct(self.config.hash_config.clone());
+        for level in 0..depth {
+            let mut parents = BTreeMap::new();
+            // the positions are visited in increasing order: a left node is always visited before its right sibling,
+            // so a pair of computed siblings is hashed when visiting the left one
+            for (&position, (cell, leaf)) in nodes.iter() {
+                if parents.contains_key(&(position >> 1)) {
+                    continue;
+                }
+
+                let sibling = match nodes.get(&(position ^ 1)) {
+                    Some((sibling, _)) => sibling.clone(),
+                    None => self.assign_sibling(
+                        layouter.namespace(|| "assign sibling"),
+                        path_elements[*leaf][level],
+                    )?,
+                };
+                let (left, right) = if position & 1 == 0 {
+                    (cell.clone(), sibling)
+                } else {
+                    (sibling, cell.clone())
+                };
+
+                let parent =
+                    hash_chip.hash_two(layouter.namespace(|| "hash two nodes"), left, right)?;
+                parents.insert(position >> 1, (parent, *leaf));
+            }
+            nodes = parents;
+        }
+
+        // only the root is left
+        let (root, _) = nodes.remove(&0).unwrap();
+        Ok(root)
+    }
+
+    fn assign_sibling(
+        &self,
+        mut layouter: impl Layouter<F>,
+        sibling: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "assign sibling",
+            |mut region| {
+                region.assign_advice(|| "assign sibling", self.config.advice[1], 0, || sibling)
+            },
+        )
+    }
+
+    // Enforce permutation check between input cell and instance column at row passed as input
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.instance, row)
+    }
+}
@@ -0,0 +1,236 @@
+use super::poseidon::{PoseidonChip, PoseidonConfig};
+use halo2_gadgets::poseidon::primitives::Spec;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+use std::ops::{Add, Mul, Sub};
+
+// Merkle tree where every node has four children, hashed with the Poseidon hash (width 5, rate 4) of PoseidonChip.
+// A level takes three siblings and the position of the node among the four children, given by two bits, so that a
+// tree of depth DEPTH holds as many leaves as a binary tree of depth 2 * DEPTH with half the permutations
+#[derive(Debug, Clone)]
+pub struct QuaternaryMerkleTreeConfig<F: FieldExt> {
+    pub advice: [Column<Advice>; 3],
+    pub bool_selector: Selector,
+    pub reorder_selector: Selector,
+    pub instance: Column<Instance>,
+    pub poseidon_config: PoseidonConfig<F, 5, 4>,
+}
+#[derive(Debug, Clone)]
+pub struct QuaternaryMerkleTreeChip<F: FieldExt, S: Spec<F, 5, 4>> {
+    config: QuaternaryMerkleTreeConfig<F>,
+    _marker: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 5, 4>> QuaternaryMerkleTreeChip<F, S> {
+    pub fn construct(config: QuaternaryMerkleTreeConfig<F>) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> QuaternaryMerkleTreeConfig<F> {
+        let col_a = advice[0];
+        let col_b = advice[1];
+        let col_c = advice[2];
+
+        // create selectors
+        let bool_selector = meta.selector();
+        let reorder_selector = meta.selector();
+
+        // The node cell is copied from one level to the other, the children are copied into the poseidon chip
+        meta.enable_equality(col_a);
+        meta.enable_equality(col_b);
+        meta.enable_equality(col_c);
+        meta.enable_equality(instance);
+
+        // Enforces that the two bits of the position, in the third column of the current and the next row, are
+        // either 0 or 1 when the bool selector is enabled
+        // s * b_0 * (1 - b_0) = 0 and s * b_1 * (1 - b_1) = 0
+        meta.create_gate("bool constraint", |meta| {
+            let s = meta.query_selector(bool_selector);
+            let b_0 = meta.query_advice(col_c, Rotation::cur());
+            let b_1 = meta.query_advice(col_c, Rotation::next());
+            vec![
+                s.clone() * b_0.clone() * (Expression::Constant(F::from(1)) - b_0),
+                s * b_1.clone() * (Expression::Constant(F::from(1)) - b_1),
+            ]
+        });
+
+        // Generalizes the swap constraint of the MerkleTreeChip to four children. The layer is
+        // | n  | s0 | b_0 |
+        // | s1 | s2 | b_1 |
+        // | c0 | c1 |  -  |
+        // | c2 | c3 |  -  |
+        // The position p = b_0 + 2 * b_1 is selected by e_p, the product of b_i or (1 - b_i) for the two bits, which is
+        // 1 for the position of the node and 0 for the other three. Every child must be the one of the selected
+        // position: n at position p, and the siblings at the other positions from left to right
+        // s * (c_j - sum_p e_p * children_p[j]) = 0 for j in 0..4
+        // This applies only when the reorder selector is enabled
+        meta.create_gate("reorder constraint", |meta| {
+            let s = meta.query_selector(reorder_selector);
+            let n = meta.query_advice(col_a, Rotation::cur());
+            let s_0 = meta.query_advice(col_b, Rotation::cur());
+            let b_0 = meta.query_advice(col_c, Rotation::cur());
+            let s_1 = meta.query_advice(col_a, Rotation::next());
+            let s_2 = meta.query_advice(col_b, Rotation::next());
+            let b_1 = meta.query_advice(col_c, Rotation::next());
+            let children = [
+                meta.query_advice(col_a, Rotation(2)),
+                meta.query_advice(col_b, Rotation(2)),
+                meta.query_advice(col_a, Rotation(3)),
+                meta.query_advice(col_b, Rotation(3)),
+            ];
+
+            let selected_children = reorder(
+                Expression::Constant(F::from(1)),
+                n,
+                [s_0, s_1, s_2],
+                b_0,
+                b_1,
+            );
+            children
+                .into_iter()
+                .zip(selected_children)
+                .map(|(child, selected_child)| s.clone() * (child - selected_child))
+                .collect::<Vec<_>>()
+        });
+
+        // The permutation needs 5 advice columns of its own
+        let hash_inputs = (0..5).map(|_| meta.advice_column()).collect::<Vec<_>>();
+        let poseidon_config = PoseidonChip::<F, S, 5, 4, 4>::configure(meta, hash_inputs, instance);
+
+        QuaternaryMerkleTreeConfig {
+            advice: [col_a, col_b, col_c],
+            bool_selector,
+            reorder_selector,
+            instance,
+            poseidon_config,
+        }
+    }
+
+    pub fn assign_leaf(
+        &self,
+        mut layouter: impl Layouter<F>,
+        leaf: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "assign leaf",
+            |mut region| region.assign_advice(|| "assign leaf", self.config.advice[0], 0, || leaf),
+        )
+    }
+
+    // Returns the cell containing the digest of the node and its three siblings, ordered by the index
+    // The index is the position (0 to 3) of the node among the four children, its two bits are assigned as witnesses
+    pub fn merkle_prove_layer(
+        &self,
+        mut layouter: impl Layouter<F>,
+        node_cell: &AssignedCell<F, F>,
+        siblings: [Value<F>; 3],
+        index: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let advice = self.config.advice;
+
+        // The bits are such that b_0 + 2 * b_1 = index, so that an index larger than 3 fails the bool constraint
+        let b_0 = index.map(|x| F::from((x.get_lower_128() & 1) as u64));
+        let b_1 = index.zip(b_0).map(|(x, b_0)| (x - b_0) * F::TWO_INV);
+
+        let children = layouter.assign_region(
+            || "merkle prove layer",
+            |mut region| {
+                // Row 0: | node | sibling 0 | bit 0 |
+                self.config.bool_selector.enable(&mut region, 0)?;
+                self.config.reorder_selector.enable(&mut region, 0)?;
+                node_cell.copy_advice(
+                    || "copy node cell from previous prove layer",
+                    &mut region,
+                    advice[0],
+                    0,
+                )?;
+                region.assign_advice(|| "assign sibling 0", advice[1], 0, || siblings[0])?;
+                region.assign_advice(|| "assign bit 0", advice[2], 0, || b_0)?;
+
+                // Row 1: | sibling 1 | sibling 2 | bit 1 |
+                region.assign_advice(|| "assign sibling 1", advice[0], 1, || siblings[1])?;
+                region.assign_advice(|| "assign sibling 2", advice[1], 1, || siblings[2])?;
+                region.assign_advice(|| "assign bit 1", advice[2], 1, || b_1)?;
+
+                // Rows 2 and 3: | child 0 | child 1 | and | child 2 | child 3 |
+                // Here we just perform the assignment - no hashing is performed here!
+                let node = node_cell.value().map(|x| x.to_owned());
+                let ordered_children = node
+                    .zip(siblings[0])
+                    .zip(siblings[1])
+                    .zip(siblings[2])
+                    .zip(b_0.zip(b_1))
+                    .map(|((((node, s_0), s_1), s_2), (b_0, b_1))| {
+                        reorder(F::one(), node, [s_0, s_1, s_2], b_0, b_1)
+                    });
+
+                (0..4)
+                    .map(|j| {
+                        region.assign_advice(
+                            || format!("assign child {} to be hashed", j),
+                            advice[j % 2],
+                            2 + j / 2,
+                            || ordered_children.map(|children| children[j]),
+                        )
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            },
+        )?;
+
+        // The poseidon chip copies the four children and returns the cell containing the digest
+        let poseidon_chip =
+            PoseidonChip::<F, S, 5, 4, 4>::construct(self.config.poseidon_config.clone());
+        poseidon_chip.hash(
+            layouter.namespace(|| "hash four nodes"),
+            &children.try_into().unwrap(),
+        )
+    }
+
+    // Enforce permutation check between input cell and instance column at row passed as input
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.instance, row)
+    }
+}
+
+// Children of a node given the bits of its position, as constrained by the reorder constraint. The same expression is
+// used for the gate and for the witnesses: with b_0 and b_1 being bits it matches merkle_tree::quaternary_children
+fn reorder<T>(one: T, node: T, siblings: [T; 3], b_0: T, b_1: T) -> [T; 4]
+where
+    T: Clone + Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+{
+    let [s_0, s_1, s_2] = siblings;
+
+    // e[p] is 1 if the position is p, 0 otherwise
+    let e = [
+        (one.clone() - b_0.clone()) * (one.clone() - b_1.clone()),
+        b_0.clone() * (one.clone() - b_1.clone()),
+        (one - b_0.clone()) * b_1.clone(),
+        b_0 * b_1,
+    ];
+    let children_per_position = [
+        [node.clone(), s_0.clone(), s_1.clone(), s_2.clone()],
+        [s_0.clone(), node.clone(), s_1.clone(), s_2.clone()],
+        [s_0.clone(), s_1.clone(), node.clone(), s_2.clone()],
+        [s_0, s_1, s_2, node],
+    ];
+
+    std::array::from_fn(|j| {
+        e.iter()
+            .zip(children_per_position.iter())
+            .map(|(e_p, children_p)| e_p.clone() * children_p[j].clone())
+            .reduce(|acc, term| acc + term)
+            .unwrap()
+    })
+}
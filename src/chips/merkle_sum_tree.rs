@@ -0,0 +1,523 @@
+use super::hash_instructions::TwoToOneHashInstructions;
+use super::merkle_tree::{swap, MerkleTreeChip, MerkleTreeConfig};
+use super::poseidon::{PoseidonChip, PoseidonConfig};
+use super::range_check::{RangeCheckChip, RangeCheckConfig};
+use halo2_gadgets::poseidon::primitives::Spec;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+
+// Config shared by the Merkle sum tree chips, whatever the number of sums carried by a node. C is the config of the
+// hash chip of the MerkleTreeChip, which provides the bool and swap gates
+#[derive(Debug, Clone)]
+pub struct MerkleSumLayerConfig<C> {
+    pub merkle_tree_config: MerkleTreeConfig<C>,
+    pub sum_selector: Selector,
+    pub range_check_config: RangeCheckConfig,
+}
+
+pub type MerkleSumTreeConfig<F> = MerkleSumLayerConfig<PoseidonConfig<F, 3, 2>>;
+
+// Values of a level of a Merkle sum tree whose nodes carry N_SUMS sums. hashes and sums are the left and right values
+// to be hashed, already ordered: the chips order them with swap, the tests pass the orderings of a malicious prover to
+// check that the swap constraint rejects them
+pub(crate) struct SumLayer<'a, F: FieldExt, const N_SUMS: usize> {
+    pub node_hash: &'a AssignedCell<F, F>,
+    pub node_sums: &'a [AssignedCell<F, F>],
+    pub element_hash: Value<F>,
+    pub element_sums: [Value<F>; N_SUMS],
+    pub index: Value<F>,
+    pub hashes: (Value<F>, Value<F>),
+    pub sums: [(Value<F>, Value<F>); N_SUMS],
+}
+
+impl<'a, F: FieldExt, const N_SUMS: usize> SumLayer<'a, F, N_SUMS> {
+    // The hashes and the sums of every asset are swapped according to the same index
+    pub(crate) fn ordered(
+        node_hash: &'a AssignedCell<F, F>,
+        node_sums: &'a [AssignedCell<F, F>],
+        element_hash: Value<F>,
+        element_sums: [Value<F>; N_SUMS],
+        index: Value<F>,
+    ) -> Self {
+        assert_eq!(node_sums.len(), N_SUMS);
+        Self {
+            node_hash,
+            node_sums,
+            element_hash,
+            element_sums,
+            index,
+            hashes: swap(node_hash.value().copied(), element_hash, index),
+            sums: std::array::from_fn(|i| {
+                swap(node_sums[i].value().copied(), element_sums[i], index)
+            }),
+        }
+    }
+}
+
+// Cells of a level to be hashed into the parent node, and the sums of the parent node
+pub(crate) struct AssignedSumLayer<F: FieldExt> {
+    pub left_hash: AssignedCell<F, F>,
+    pub right_hash: AssignedCell<F, F>,
+    pub left_sums: Vec<AssignedCell<F, F>>,
+    pub right_sums: Vec<AssignedCell<F, F>>,
+    pub parent_sums: Vec<AssignedCell<F, F>>,
+}
+
+impl<C: Clone + std::fmt::Debug> MerkleSumLayerConfig<C> {
+    pub(crate) fn configure<F: FieldExt, H: TwoToOneHashInstructions<F, Config = C>>(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> Self {
+        // The bool and swap constraints, together with the hash config, come from the MerkleTreeChip
+        let merkle_tree_config = MerkleTreeChip::<F, H>::configure(meta, advice, instance);
+
+        let col_a = advice[0];
+        let col_b = advice[1];
+        let col_c = advice[2];
+
+        let sum_selector = meta.selector();
+
+        // Enforces that the sum of the parent node is the sum of the left and right nodes
+        // s * (a + b - c) = 0
+        meta.create_gate("sum constraint", |meta| {
+            let s = meta.query_selector(sum_selector);
+            let a = meta.query_advice(col_a, Rotation::cur());
+            let b = meta.query_advice(col_b, Rotation::cur());
+            let c = meta.query_advice(col_c, Rotation::cur());
+            vec![s * (a + b - c)]
+        });
+
+        // Balances and sums are range checked to 64 bits so that they cannot wrap around the field modulus
+        let range_check_column = meta.advice_column();
+        let range_check_config = RangeCheckChip::<F, 8>::configure(meta, range_check_column);
+
+        Self {
+            merkle_tree_config,
+            sum_selector,
+            range_check_config,
+        }
+    }
+
+    pub(crate) fn range_check_chip<F: FieldExt>(&self) -> RangeCheckChip<F, 8> {
+        RangeCheckChip::construct(self.range_check_config.clone())
+    }
+
+    // Assigns the region of a level and range checks the sums of the sibling and of the parent. The sums of the node
+    // have been range checked at the previous level (or with the balances of the leaf)
+    //
+    // | a          | b            | c          |
+    // | ---------- | ------------ | ---------- |
+    // | node_hash  | element_hash | index      | bool, swap
+    // | left_hash  | right_hash   |            |
+    // | node_sum   | element_sum  | index      | swap        (two rows per sum,
+    // | left_sum   | right_sum    | parent_sum | sum          the same index is copied)
+    pub(crate) fn assign_layer<F: FieldExt, const N_SUMS: usize>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        layer: &SumLayer<'_, F, N_SUMS>,
+    ) -> Result<AssignedSumLayer<F>, Error> {
+        let config = &self.merkle_tree_config;
+
+        let (assigned, element_sum_cells) = layouter.assign_region(
+            || "merkle sum prove layer",
+            |mut region| {
+                // Row 0: | node_hash | element_hash | index |
+                config.bool_selector.enable(&mut region, 0)?;
+                config.swap_selector.enable(&mut region, 0)?;
+                layer.node_hash.copy_advice(
+                    || "copy node hash from previous prove layer",
+                    &mut region,
+                    config.advice[0],
+                    0,
+                )?;
+                region.assign_advice(
+                    || "assign element hash",
+                    config.advice[1],
+                    0,
+                    || layer.element_hash,
+                )?;
+                let index_cell =
+                    region.assign_advice(|| "assign index", config.advice[2], 0, || layer.index)?;
+
+                // Row 1: | left_hash | right_hash | - |
+                let (l, r) = layer.hashes;
+                let left_hash = region.assign_advice(
+                    || "assign left hash to be hashed",
+                    config.advice[0],
+                    1,
+                    || l,
+                )?;
+                let right_hash = region.assign_advice(
+                    || "assign right hash to be hashed",
+                    config.advice[1],
+                    1,
+                    || r,
+                )?;
+
+                let mut element_sum_cells = vec![];
+                let mut left_sums = vec![];
+                let mut right_sums = vec![];
+                let mut parent_sums = vec![];
+
+                for (i, ((node_sum, element_sum), &(l, r))) in layer
+                    .node_sums
+                    .iter()
+                    .zip(layer.element_sums.iter())
+                    .zip(layer.sums.iter())
+                    .enumerate()
+                {
+                    let row = 2 + 2 * i;
+
+                    // Row 2 + 2i: | node_sum | element_sum | index |
+                    config.swap_selector.enable(&mut region, row)?;
+                    node_sum.copy_advice(
+                        || format!("copy node sum {} from previous prove layer", i),
+                        &mut region,
+                        config.advice[0],
+                        row,
+                    )?;
+                    element_sum_cells.push(region.assign_advice(
+                        || format!("assign element sum {}", i),
+                        config.advice[1],
+                        row,
+                        || *element_sum,
+                    )?);
+                    index_cell.copy_advice(|| "copy index", &mut region, config.advice[2], row)?;
+
+                    // Row 3 + 2i: | left_sum | right_sum | parent_sum |
+                    self.sum_selector.enable(&mut region, row + 1)?;
+                    left_sums.push(region.assign_advice(
+                        || format!("assign left sum {} to be hashed", i),
+                        config.advice[0],
+                        row + 1,
+                        || l,
+                    )?);
+                    right_sums.push(region.assign_advice(
+                        || format!("assign right sum {} to be hashed", i),
+                        config.advice[1],
+                        row + 1,
+                        || r,
+                    )?);
+                    parent_sums.push(region.assign_advice(
+                        || format!("assign parent sum {}", i),
+                        config.advice[2],
+                        row + 1,
+                        || l + r,
+                    )?);
+                }
+
+                let assigned = AssignedSumLayer {
+                    left_hash,
+                    right_hash,
+                    left_sums,
+                    right_sums,
+                    parent_sums,
+                };
+                Ok((assigned, element_sum_cells))
+            },
+        )?;
+
+        let range_check_chip = self.range_check_chip::<F>();
+        for (element_sum_cell, parent_sum) in
+            element_sum_cells.iter().zip(assigned.parent_sums.iter())
+        {
+            range_check_chip.assign(
+                layouter.namespace(|| "range check element sum"),
+                element_sum_cell,
+            )?;
+            range_check_chip.assign(layouter.namespace(|| "range check parent sum"), parent_sum)?;
+        }
+
+        Ok(assigned)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MerkleSumTreeChip<F: FieldExt, S: Spec<F, 3, 2>> {
+    config: MerkleSumTreeConfig<F>,
+    _marker: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>> MerkleSumTreeChip<F, S> {
+    pub fn construct(config: MerkleSumTreeConfig<F>) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> MerkleSumTreeConfig<F> {
+        MerkleSumLayerConfig::configure::<F, PoseidonChip<F, S, 3, 2, 2>>(meta, advice, instance)
+    }
+
+    // Loads the lookup table used by the range checks, it must be called once per circuit
+    pub fn load_range_check_table(&self, layouter: impl Layouter<F>) -> Result<(), Error> {
+        self.config.range_check_chip::<F>().load_table(layouter)
+    }
+
+    // Assigns the username and the balance of the user and computes the hash of the leaf
+    // Returns the cells containing the leaf hash and the balance, which is the sum of the leaf
+    // The balance is range checked to 64 bits
+    pub fn assign_leaf(
+        &self,
+        mut layouter: impl Layouter<F>,
+        username: Value<F>,
+        balance: Value<F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let advice = self.config.merkle_tree_config.advice;
+
+        let (username_cell, balance_cell) = layouter.assign_region(
+            || "assign leaf",
+            |mut region| {
+                let username_cell =
+                    region.assign_advice(|| "assign username", advice[0], 0, || username)?;
+                let balance_cell =
+                    region.assign_advice(|| "assign balance", advice[1], 0, || balance)?;
+                Ok((username_cell, balance_cell))
+            },
+        )?;
+
+        self.config
+            .range_check_chip::<F>()
+            .assign(layouter.namespace(|| "range check balance"), &balance_cell)?;
+
+        let poseidon_chip = PoseidonChip::<F, S, 3, 2, 2>::construct(
+            self.config.merkle_tree_config.hash_config.clone(),
+        );
+        let leaf_hash = poseidon_chip.hash(
+            layouter.namespace(|| "hash leaf"),
+            &[username_cell, balance_cell.clone()],
+        )?;
+
+        Ok((leaf_hash, balance_cell))
+    }
+
+    // Returns the cells containing the hash and the sum of the parent node
+    // The sum of the sibling and the sum of the parent are range checked to 64 bits
+    pub fn merkle_prove_layer(
+        &self,
+        mut layouter: impl Layouter<F>,
+        node_hash: &AssignedCell<F, F>,
+        node_sum: &AssignedCell<F, F>,
+        element_hash: Value<F>,
+        element_sum: Value<F>,
+        index: Value<F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let layer = SumLayer::ordered(
+            node_hash,
+            std::slice::from_ref(node_sum),
+            element_hash,
+            [element_sum],
+            index,
+        );
+        let AssignedSumLayer {
+            left_hash,
+            right_hash,
+            left_sums,
+            right_sums,
+            mut parent_sums,
+        } = self
+            .config
+            .assign_layer(layouter.namespace(|| "merkle sum prove layer"), &layer)?;
+
+        // The hash of the parent node commits to both the hashes and the sums of its children
+        let poseidon_chip = PoseidonChip::<F, S, 3, 2, 4>::construct(
+            self.config.merkle_tree_config.hash_config.clone(),
+        );
+        let parent_hash = poseidon_chip.hash(
+            layouter.namespace(|| "hash row constaint"),
+            &[
+                left_hash,
+                left_sums[0].clone(),
+                right_hash,
+                right_sums[0].clone(),
+            ],
+        )?;
+
+        Ok((parent_hash, parent_sums.remove(0)))
+    }
+
+    // Enforce permutation check between input cell and instance column at row passed as input
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.merkle_tree_config.instance, row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MerkleSumLayerConfig, SumLayer};
+    use crate::chips::poseidon::{PoseidonChip, PoseidonConfig};
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::pasta::Fp,
+        plonk::{Circuit, ConstraintSystem, Error},
+    };
+
+    // One level with 2 sums whose left and right hashes and sums are chosen by the prover instead of being ordered by
+    // the index. Both the MerkleSumTreeChip (1 sum) and the MerkleSumTreeMultiAssetChip assign their levels with
+    // assign_layer, so this covers the orderings a malicious prover could use against any of them
+    struct ForgedSumLayerCircuit {
+        node_hash: Value<Fp>,
+        node_sums: [Value<Fp>; 2],
+        element_hash: Value<Fp>,
+        element_sums: [Value<Fp>; 2],
+        index: Value<Fp>,
+        hashes: (Value<Fp>, Value<Fp>),
+        sums: [(Value<Fp>, Value<Fp>); 2],
+    }
+
+    impl Circuit<Fp> for ForgedSumLayerCircuit {
+        type Config = MerkleSumLayerConfig<PoseidonConfig<Fp, 3, 2>>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                node_hash: Value::unknown(),
+                node_sums: [Value::unknown(); 2],
+                element_hash: Value::unknown(),
+                element_sums: [Value::unknown(); 2],
+                index: Value::unknown(),
+                hashes: (Value::unknown(), Value::unknown()),
+                sums: [(Value::unknown(), Value::unknown()); 2],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let col_a = meta.advice_column();
+            let col_b = meta.advice_column();
+            let col_c = meta.advice_column();
+            let instance = meta.instance_column();
+            MerkleSumLayerConfig::configure::<Fp, PoseidonChip<Fp, P128Pow5T3, 3, 2, 2>>(
+                meta,
+                [col_a, col_b, col_c],
+                instance,
+            )
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            config
+                .range_check_chip::<Fp>()
+                .load_table(layouter.namespace(|| "load range check table"))?;
+
+            let advice = config.merkle_tree_config.advice;
+            let (node_hash, node_sums) = layouter.assign_region(
+                || "assign node",
+                |mut region| {
+                    let node_hash = region.assign_advice(
+                        || "assign node hash",
+                        advice[0],
+                        0,
+                        || self.node_hash,
+                    )?;
+                    let node_sums = self
+                        .node_sums
+                        .iter()
+                        .enumerate()
+                        .map(|(i, sum)| {
+                            region.assign_advice(|| "assign node sum", advice[1], i, || *sum)
+                        })
+                        .collect::<Result<Vec<_>, Error>>()?;
+                    Ok((node_hash, node_sums))
+                },
+            )?;
+
+            let layer = SumLayer {
+                node_hash: &node_hash,
+                node_sums: &node_sums,
+                element_hash: self.element_hash,
+                element_sums: self.element_sums,
+                index: self.index,
+                hashes: self.hashes,
+                sums: self.sums,
+            };
+            let assigned = config.assign_layer(layouter.namespace(|| "forged layer"), &layer)?;
+
+            // the sums of the parent are public, as the sums of the root are
+            for (i, parent_sum) in assigned.parent_sums.iter().enumerate() {
+                layouter.constrain_instance(
+                    parent_sum.cell(),
+                    config.merkle_tree_config.instance,
+                    i,
+                )?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_merkle_sum_layer_forged_ordering() {
+        // the node is the left child of its parent, the sibling is another user
+        let (node_hash, node_sums) = (Fp::from(11), [Fp::from(300), Fp::from(5000)]);
+        let (element_hash, element_sums) = (Fp::from(22), [Fp::from(400), Fp::from(7000)]);
+
+        // The public inputs are the sums of the forged parent, so that the sum constraint and the range checks hold
+        // and only the swap constraint can reject the witness
+        let run = |hashes: (Fp, Fp), sums: [(Fp, Fp); 2]| {
+            let circuit = ForgedSumLayerCircuit {
+                node_hash: Value::known(node_hash),
+                node_sums: node_sums.map(Value::known),
+                element_hash: Value::known(element_hash),
+                element_sums: element_sums.map(Value::known),
+                index: Value::known(Fp::zero()),
+                hashes: (Value::known(hashes.0), Value::known(hashes.1)),
+                sums: sums.map(|(left, right)| (Value::known(left), Value::known(right))),
+            };
+            let public_input = sums.map(|(left, right)| left + right).to_vec();
+            MockProver::run(10, &circuit, vec![public_input]).unwrap()
+        };
+        let assert_swap_rejected = |prover: MockProver<Fp>| {
+            let failures = prover.verify().unwrap_err();
+            assert!(
+                failures
+                    .iter()
+                    .all(|failure| format!("{:?}", failure).contains("swap constraint")),
+                "{:?}",
+                failures
+            );
+        };
+
+        let honest_hashes = (node_hash, element_hash);
+        let honest_sums = [
+            (node_sums[0], element_sums[0]),
+            (node_sums[1], element_sums[1]),
+        ];
+        run(honest_hashes, honest_sums).assert_satisfied();
+
+        // Both sums of an asset lowered by the balance of the user, which hides it from the total sum of the
+        // liabilities. A single equation of the swap constraint only fixed the difference of the sums, so it accepted
+        // this witness
+        let hidden = node_sums[1];
+        assert_swap_rejected(run(
+            honest_hashes,
+            [
+                honest_sums[0],
+                (node_sums[1] - hidden, element_sums[1] - hidden),
+            ],
+        ));
+
+        // the sums of an asset swapped while the hashes aren't, the total sum is preserved
+        assert_swap_rejected(run(
+            honest_hashes,
+            [honest_sums[0], (element_sums[1], node_sums[1])],
+        ));
+
+        // the hashes swapped while the sums aren't
+        assert_swap_rejected(run((element_hash, node_hash), honest_sums));
+    }
+}
@@ -0,0 +1,157 @@
+use super::merkle_sum_tree::{AssignedSumLayer, MerkleSumLayerConfig, SumLayer};
+use super::poseidon::{PoseidonChip, PoseidonConfig};
+use halo2_gadgets::poseidon::primitives::Spec;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
+use std::marker::PhantomData;
+
+// Merkle sum tree where every leaf holds N_ASSETS balances and every node carries N_ASSETS sums.
+// A leaf is H(username, balance_0, ..., balance_N-1), a parent node is H(left_hash, left_sums.., right_hash, right_sums..).
+// The number of inputs to be hashed grows with N_ASSETS, therefore the hashes use the sponge mode of the PoseidonChip
+// and the width of the permutation is left to the user (e.g. P128Pow5T3 or P128Pow5T5Bn256).
+// The bool and swap gates, together with the poseidon config, come from the MerkleTreeChip, and the layers
+// are assigned by the MerkleSumLayerConfig shared with the MerkleSumTreeChip.
+
+pub type MerkleSumTreeMultiAssetConfig<F, const WIDTH: usize, const RATE: usize> =
+    MerkleSumLayerConfig<PoseidonConfig<F, WIDTH, RATE>>;
+
+#[derive(Debug, Clone)]
+pub struct MerkleSumTreeMultiAssetChip<
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
+    const WIDTH: usize,
+    const RATE: usize,
+    const N_ASSETS: usize,
+> {
+    config: MerkleSumTreeMultiAssetConfig<F, WIDTH, RATE>,
+    _marker: PhantomData<S>,
+}
+
+impl<
+        F: FieldExt,
+        S: Spec<F, WIDTH, RATE>,
+        const WIDTH: usize,
+        const RATE: usize,
+        const N_ASSETS: usize,
+    > MerkleSumTreeMultiAssetChip<F, S, WIDTH, RATE, N_ASSETS>
+{
+    pub fn construct(config: MerkleSumTreeMultiAssetConfig<F, WIDTH, RATE>) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> MerkleSumTreeMultiAssetConfig<F, WIDTH, RATE> {
+        // The permutation of the hash of two nodes is the one used by the sponge, L is not relevant for the sponge mode
+        MerkleSumLayerConfig::configure::<F, PoseidonChip<F, S, WIDTH, RATE, 2>>(
+            meta, advice, instance,
+        )
+    }
+
+    // Loads the lookup table used by the range checks, it must be called once per circuit
+    pub fn load_range_check_table(&self, layouter: impl Layouter<F>) -> Result<(), Error> {
+        self.config.range_check_chip::<F>().load_table(layouter)
+    }
+
+    // Assigns the username and the balances of the user and computes the hash of the leaf
+    // Returns the cells containing the leaf hash and the balances, which are the sums of the leaf
+    // Every balance is range checked to 64 bits
+    pub fn assign_leaf(
+        &self,
+        mut layouter: impl Layouter<F>,
+        username: Value<F>,
+        balances: [Value<F>; N_ASSETS],
+    ) -> Result<(AssignedCell<F, F>, Vec<AssignedCell<F, F>>), Error> {
+        let advice = self.config.merkle_tree_config.advice;
+
+        let (username_cell, balance_cells) = layouter.assign_region(
+            || "assign leaf",
+            |mut region| {
+                let username_cell =
+                    region.assign_advice(|| "assign username", advice[0], 0, || username)?;
+                let balance_cells = balances
+                    .iter()
+                    .enumerate()
+                    .map(|(i, balance)| {
+                        region.assign_advice(
+                            || format!("assign balance {}", i),
+                            advice[1],
+                            i,
+                            || *balance,
+                        )
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok((username_cell, balance_cells))
+            },
+        )?;
+
+        let range_check_chip = self.config.range_check_chip::<F>();
+        for balance_cell in balance_cells.iter() {
+            range_check_chip.assign(layouter.namespace(|| "range check balance"), balance_cell)?;
+        }
+
+        let hash_input = std::iter::once(username_cell)
+            .chain(balance_cells.iter().cloned())
+            .collect::<Vec<_>>();
+
+        let poseidon_chip = PoseidonChip::<F, S, WIDTH, RATE, 0>::construct(
+            self.config.merkle_tree_config.hash_config.clone(),
+        );
+        let leaf_hash =
+            poseidon_chip.hash_sponge(layouter.namespace(|| "hash leaf"), &hash_input)?;
+
+        Ok((leaf_hash, balance_cells))
+    }
+
+    // Returns the cells containing the hash and the N_ASSETS sums of the parent node
+    // The sums of the sibling and the sums of the parent are range checked to 64 bits
+    pub fn merkle_prove_layer(
+        &self,
+        mut layouter: impl Layouter<F>,
+        node_hash: &AssignedCell<F, F>,
+        node_sums: &[AssignedCell<F, F>],
+        element_hash: Value<F>,
+        element_sums: [Value<F>; N_ASSETS],
+        index: Value<F>,
+    ) -> Result<(AssignedCell<F, F>, Vec<AssignedCell<F, F>>), Error> {
+        let layer = SumLayer::ordered(node_hash, node_sums, element_hash, element_sums, index);
+        let AssignedSumLayer {
+            left_hash,
+            right_hash,
+            left_sums,
+            right_sums,
+            parent_sums,
+        } = self
+            .config
+            .assign_layer(layouter.namespace(|| "merkle sum prove layer"), &layer)?;
+
+        // The hash of the parent node commits to both the hashes and the sums of its children
+        let hash_input = std::iter::once(left_hash)
+            .chain(left_sums)
+            .chain(std::iter::once(right_hash))
+            .chain(right_sums)
+            .collect::<Vec<_>>();
+
+        let poseidon_chip = PoseidonChip::<F, S, WIDTH, RATE, 0>::construct(
+            self.config.merkle_tree_config.hash_config.clone(),
+        );
+        let parent_hash =
+            poseidon_chip.hash_sponge(layouter.namespace(|| "hash parent node"), &hash_input)?;
+
+        Ok((parent_hash, parent_sums))
+    }
+
+    // Enforce permutation check between input cell and instance column at row passed as input
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.merkle_tree_config.instance, row)
+    }
+}
@@ -0,0 +1,404 @@
+use super::hash_instructions::TwoToOneHashInstructions;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+
+// Merkle tree chip generic over the hash function H used to hash two nodes into their parent.
+// The config only depends on the config of the hash chip, so that it is Clone and Debug whatever the hash chip is
+#[derive(Debug, Clone)]
+pub struct MerkleTreeConfig<C> {
+    pub advice: [Column<Advice>; 3],
+    pub bool_selector: Selector,
+    pub swap_selector: Selector,
+    pub instance: Column<Instance>,
+    pub hash_config: C,
+}
+#[derive(Debug, Clone)]
+pub struct MerkleTreeChip<F: FieldExt, H: TwoToOneHashInstructions<F>> {
+    config: MerkleTreeConfig<H::Config>,
+    _marker: PhantomData<(F, H)>,
+}
+
+impl<F: FieldExt, H: TwoToOneHashInstructions<F>> MerkleTreeChip<F, H> {
+    pub fn construct(config: MerkleTreeConfig<H::Config>) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> MerkleTreeConfig<H::Config> {
+        let col_a = advice[0];
+        let col_b = advice[1];
+        let col_c = advice[2];
+
+        // create selectors
+        let bool_selector = meta.selector();
+        let swap_selector = meta.selector();
+
+        // Enable equality on the advice columns and the instance column. The node cell is copied from one level to the
+        // other, the left and right cells are copied into the hash chip and the digest is checked against the instance
+        meta.enable_equality(col_a);
+        meta.enable_equality(col_b);
+        meta.enable_equality(col_c);
+        meta.enable_equality(instance);
+
+        // Enforces that c is either a 0 or 1 when the bool selector is enabled
+        // s * c * (1 - c) = 0
+        meta.create_gate("bool constraint", |meta| {
+            let s = meta.query_selector(bool_selector);
+            let c = meta.query_advice(col_c, Rotation::cur());
+            vec![s * c.clone() * (Expression::Constant(F::from(1)) - c)]
+        });
+
+        // Enforces that if the swap bit (c) is on, l=b and r=a. Otherwise, l=a and r=b.
+        // s * (c * (b - a) - (l - a)) = 0
+        // s * (c * (a - b) - (r - b)) = 0
+        // Both equations are needed: a single combination of them only fixes r - l, so that l = a + t and r = b + t
+        // would pass for any t. This applies only when the swap selector is enabled
+        meta.create_gate("swap constraint", |meta| {
+            let s = meta.query_selector(swap_selector);
+            let a = meta.query_advice(col_a, Rotation::cur());
+            let b = meta.query_advice(col_b, Rotation::cur());
+            let c = meta.query_advice(col_c, Rotation::cur());
+            let l = meta.query_advice(col_a, Rotation::next());
+            let r = meta.query_advice(col_b, Rotation::next());
+            vec![
+                s.clone() * (c.clone() * (b.clone() - a.clone()) - (l - a.clone())),
+                s * (c * (a - b.clone()) - (r - b)),
+            ]
+        });
+
+        let hash_config = H::configure(meta, advice, instance);
+
+        MerkleTreeConfig {
+            advice: [col_a, col_b, col_c],
+            bool_selector,
+            swap_selector,
+            instance,
+            hash_config,
+        }
+    }
+
+    pub fn assign_leaf(
+        &self,
+        mut layouter: impl Layouter<F>,
+        leaf: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "assign leaf",
+            |mut region| region.assign_advice(|| "assign leaf", self.config.advice[0], 0, || leaf),
+        )
+    }
+
+    // Returns the cell containing the digest of the node and the path element, ordered by the index
+    pub fn merkle_prove_layer(
+        &self,
+        layouter: impl Layouter<F>,
+        node_cell: &AssignedCell<F, F>,
+        path_element: Value<F>,
+        index: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let (left, right) = swap(node_cell.value().copied(), path_element, index);
+        self.assign_layer(
+            layouter,
+            node_cell,
+            |region| {
+                let element_cell = region.assign_advice(
+                    || "assign element",
+                    self.config.advice[1],
+                    0,
+                    || path_element,
+                )?;
+                let index_cell =
+                    region.assign_advice(|| "assign index", self.config.advice[2], 0, || index)?;
+                Ok((element_cell, index_cell))
+            },
+            left,
+            right,
+        )
+    }
+
+    // Same as merkle_prove_layer, but the index is copied from a cell assigned by another chip, such as a bit of the
+    // decomposition of a key, instead of being a free witness
+    pub fn merkle_prove_layer_with_index_cell(
+        &self,
+        layouter: impl Layouter<F>,
+        node_cell: &AssignedCell<F, F>,
+        path_element: Value<F>,
+        index_cell: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let (left, right) = swap(
+            node_cell.value().copied(),
+            path_element,
+            index_cell.value().copied(),
+        );
+        self.assign_layer(
+            layouter,
+            node_cell,
+            |region| {
+                let element_cell = region.assign_advice(
+                    || "assign element",
+                    self.config.advice[1],
+                    0,
+                    || path_element,
+                )?;
+                let index_cell =
+                    index_cell.copy_advice(|| "copy index", region, self.config.advice[2], 0)?;
+                Ok((element_cell, index_cell))
+            },
+            left,
+            right,
+        )
+    }
+
+    // Same as merkle_prove_layer, but both the path element and the index are copied from cells assigned beforehand,
+    // so that the same authentication path can be used for several leaves
+    pub fn merkle_prove_layer_with_cells(
+        &self,
+        layouter: impl Layouter<F>,
+        node_cell: &AssignedCell<F, F>,
+        path_element_cell: &AssignedCell<F, F>,
+        index_cell: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let (left, right) = swap(
+            node_cell.value().copied(),
+            path_element_cell.value().copied(),
+            index_cell.value().copied(),
+        );
+        self.assign_layer(
+            layouter,
+            node_cell,
+            |region| {
+                let element_cell = path_element_cell.copy_advice(
+                    || "copy element",
+                    region,
+                    self.config.advice[1],
+                    0,
+                )?;
+                let index_cell =
+                    index_cell.copy_advice(|| "copy index", region, self.config.advice[2], 0)?;
+                Ok((element_cell, index_cell))
+            },
+            left,
+            right,
+        )
+    }
+
+    // assign_element_and_index assigns the path element and the index in the second and third columns of the first
+    // row of the region. left and right are the nodes to be hashed, already ordered: the public methods order them
+    // with swap, the tests pass the orderings of a malicious prover to check that the swap constraint rejects them
+    fn assign_layer(
+        &self,
+        mut layouter: impl Layouter<F>,
+        node_cell: &AssignedCell<F, F>,
+        assign_element_and_index: impl Fn(
+            &mut Region<'_, F>,
+        ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error>,
+        left: Value<F>,
+        right: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let (left, right) = layouter.assign_region(
+            || "merkle prove layer",
+            |mut region| {
+                // Row 0
+                self.config.bool_selector.enable(&mut region, 0)?;
+                self.config.swap_selector.enable(&mut region, 0)?;
+                node_cell.copy_advice(
+                    || "copy node cell from previous prove layer",
+                    &mut region,
+                    self.config.advice[0],
+                    0,
+                )?;
+                assign_element_and_index(&mut region)?;
+
+                // Row 1
+                // Here we just perform the assignment - no hashing is performed here!
+                // We need to perform the assignment of the row below in order to perform the swap check
+                let left = region.assign_advice(
+                    || "assign left to be hashed",
+                    self.config.advice[0],
+                    1,
+                    || left,
+                )?;
+                let right = region.assign_advice(
+                    || "assign right to be hashed",
+                    self.config.advice[1],
+                    1,
+                    || right,
+                )?;
+
+                Ok((left, right))
+            },
+        )?;
+
+        // The hash chip copies the left and right cells and returns the cell containing the digest
+        let hash_chip = H::construct(self.config.hash_config.clone());
+        hash_chip.hash_two(layouter.namespace(|| "hash two nodes"), left, right)
+    }
+
+    // Enforce permutation check between input cell and instance column at row passed as input
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.instance, row)
+    }
+}
+
+// Orders a node and its sibling as the swap constraint requires: the node is on the left if the index is 0, on the
+// right otherwise
+pub(crate) fn swap<F: FieldExt>(
+    node: Value<F>,
+    sibling: Value<F>,
+    index: Value<F>,
+) -> (Value<F>, Value<F>) {
+    let (mut l, mut r) = (node, sibling);
+    index.map(|x| {
+        (l, r) = if x == F::zero() { (l, r) } else { (r, l) };
+    });
+    (l, r)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chips::merkle_v3::{MerkleTreeV3Chip, MerkleTreeV3Config};
+    use crate::merkle_tree::hash_nodes;
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::pasta::Fp,
+        plonk::{Circuit, ConstraintSystem, Error},
+    };
+
+    // One level whose left and right nodes are chosen by the prover instead of being ordered by the index. Every
+    // Merkle chip proves its levels with MerkleTreeChip, so this covers the orderings a malicious prover could use
+    // against any of them
+    struct ForgedLayerCircuit {
+        leaf: Value<Fp>,
+        path_element: Value<Fp>,
+        index: Value<Fp>,
+        left: Value<Fp>,
+        right: Value<Fp>,
+    }
+
+    impl Circuit<Fp> for ForgedLayerCircuit {
+        type Config = MerkleTreeV3Config<Fp>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                leaf: Value::unknown(),
+                path_element: Value::unknown(),
+                index: Value::unknown(),
+                left: Value::unknown(),
+                right: Value::unknown(),
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let col_a = meta.advice_column();
+            let col_b = meta.advice_column();
+            let col_c = meta.advice_column();
+            let instance = meta.instance_column();
+            MerkleTreeV3Chip::<Fp, P128Pow5T3>::configure(meta, [col_a, col_b, col_c], instance)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let chip = MerkleTreeV3Chip::<Fp, P128Pow5T3>::construct(config);
+            let leaf_cell = chip.assign_leaf(layouter.namespace(|| "assign leaf"), self.leaf)?;
+            chip.expose_public(layouter.namespace(|| "public leaf"), &leaf_cell, 0)?;
+            let root_cell = chip.assign_layer(
+                layouter.namespace(|| "forged layer"),
+                &leaf_cell,
+                |region| {
+                    let element_cell = region.assign_advice(
+                        || "assign element",
+                        chip.config.advice[1],
+                        0,
+                        || self.path_element,
+                    )?;
+                    let index_cell = region.assign_advice(
+                        || "assign index",
+                        chip.config.advice[2],
+                        0,
+                        || self.index,
+                    )?;
+                    Ok((element_cell, index_cell))
+                },
+                self.left,
+                self.right,
+            )?;
+            chip.expose_public(layouter.namespace(|| "public root"), &root_cell, 1)
+        }
+    }
+
+    #[test]
+    fn test_merkle_tree_forged_layer() {
+        // a tree of depth 1 whose leaves are x and s
+        let (x, s) = (Fp::from(99), Fp::from(102));
+        let root = hash_nodes::<Fp, P128Pow5T3>(x, s);
+
+        // The public inputs are the claimed leaf and the hash of the forged left and right nodes, so that the copy
+        // constraints and the hash hold and only the swap constraint can reject the witness
+        let run = |leaf: Fp, path_element: Fp, index: u64, left: Fp, right: Fp| {
+            let circuit = ForgedLayerCircuit {
+                leaf: Value::known(leaf),
+                path_element: Value::known(path_element),
+                index: Value::known(Fp::from(index)),
+                left: Value::known(left),
+                right: Value::known(right),
+            };
+            let public_input = vec![leaf, hash_nodes::<Fp, P128Pow5T3>(left, right)];
+            MockProver::run(8, &circuit, vec![public_input]).unwrap()
+        };
+        let assert_swap_rejected = |prover: MockProver<Fp>| {
+            let failures = prover.verify().unwrap_err();
+            assert!(
+                failures
+                    .iter()
+                    .all(|failure| format!("{:?}", failure).contains("swap constraint")),
+                "{:?}",
+                failures
+            );
+        };
+
+        // the honest orderings are accepted
+        run(x, s, 0, x, s).assert_satisfied();
+        run(s, x, 1, x, s).assert_satisfied();
+
+        // Membership of a leaf y that isn't in the tree: with t = x - y and the path element s - t, the nodes y + t and
+        // (s - t) + t hash to the real root. A single equation c * 2 * (b - a) - (l - a) - (b - r) only constrains
+        // r - l, so it would accept this witness
+        let y = Fp::from(12345);
+        let t = x - y;
+        assert_eq!(hash_nodes::<Fp, P128Pow5T3>(y + t, (s - t) + t), root);
+        assert_swap_rejected(run(y, s - t, 0, y + t, (s - t) + t));
+
+        // the same with y = 0: a key of a sparse tree proven to be absent, its empty leaf leading to the root of the
+        // real leaf x
+        assert_swap_rejected(run(Fp::zero(), s - x, 0, x, s));
+
+        // x claimed at position 1 with the path element 2x - s, which a single equation would accept as well
+        assert_swap_rejected(run(x, x + x - s, 1, x, s));
+
+        // the node and the path element swapped while the index is 0, the sum of the nodes is preserved
+        assert_swap_rejected(run(x, s, 0, s, x));
+
+        // not swapped while the index is 1: the same leaf and root claimed at another position
+        assert_swap_rejected(run(x, s, 1, x, s));
+
+        // hashed with another sibling than the path element, as a new root computed from a tampered path would be
+        assert_swap_rejected(run(x, s, 0, x, s + Fp::one()));
+    }
+}
@@ -0,0 +1,173 @@
+use super::merkle_v3::{MerkleTreeV3Chip, MerkleTreeV3Config};
+use halo2_gadgets::poseidon::primitives::Spec;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
+use std::marker::PhantomData;
+
+// Proves that replacing the old leaf with the new leaf at the same position turns the old root into the new root.
+// The path elements and the indices are assigned once and copied into the layers of both the old and the new root,
+// so that the two leaves are necessarily at the same position of the same tree
+#[derive(Debug, Clone)]
+pub struct MerkleUpdateConfig<F: FieldExt> {
+    pub merkle_tree_v3_config: MerkleTreeV3Config<F>,
+}
+#[derive(Debug, Clone)]
+pub struct MerkleUpdateChip<F: FieldExt, S: Spec<F, 3, 2>> {
+    config: MerkleUpdateConfig<F>,
+    _marker: PhantomData<S>,
+}
+
+// Cells of the leaves and of the roots before and after an update
+#[derive(Debug, Clone)]
+pub struct AssignedMerkleUpdate<F: FieldExt> {
+    pub old_leaf: AssignedCell<F, F>,
+    pub new_leaf: AssignedCell<F, F>,
+    pub old_root: AssignedCell<F, F>,
+    pub new_root: AssignedCell<F, F>,
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>> MerkleUpdateChip<F, S> {
+    pub fn construct(config: MerkleUpdateConfig<F>) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> MerkleUpdateConfig<F> {
+        // The bool and swap constraints, together with the poseidon config, come from the MerkleTreeV3Chip
+        let merkle_tree_v3_config = MerkleTreeV3Chip::<F, S>::configure(meta, advice, instance);
+
+        MerkleUpdateConfig {
+            merkle_tree_v3_config,
+        }
+    }
+
+    pub fn assign_leaves(
+        &self,
+        mut layouter: impl Layouter<F>,
+        old_leaf: Value<F>,
+        new_leaf: Value<F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let advice = self.config.merkle_tree_v3_config.advice;
+        layouter.assign_region(
+            || "assign leaves",
+            |mut region| {
+                let old_leaf_cell =
+                    region.assign_advice(|| "assign old leaf", advice[0], 0, || old_leaf)?;
+                let new_leaf_cell =
+                    region.assign_advice(|| "assign new leaf", advice[1], 0, || new_leaf)?;
+                Ok((old_leaf_cell, new_leaf_cell))
+            },
+        )
+    }
+
+    // Assigns the authentication path shared by the old and the new leaf, one level per row
+    // Returns the cells of the path element and of the index of every level. The indices are constrained to be
+    // boolean by the bool constraint of the layers they are copied into
+    pub fn assign_path(
+        &self,
+        mut layouter: impl Layouter<F>,
+        path_elements: &[Value<F>],
+        path_indices: &[Value<F>],
+    ) -> Result<Vec<(AssignedCell<F, F>, AssignedCell<F, F>)>, Error> {
+        let advice = self.config.merkle_tree_v3_config.advice;
+        layouter.assign_region(
+            || "assign path",
+            |mut region| {
+                path_elements
+                    .iter()
+                    .zip(path_indices.iter())
+                    .enumerate()
+                    .map(|(row, (element, index))| {
+                        let element_cell = region.assign_advice(
+                            || "assign element",
+                            advice[1],
+                            row,
+                            || *element,
+                        )?;
+                        let index_cell =
+                            region.assign_advice(|| "assign index", advice[2], row, || *index)?;
+                        Ok((element_cell, index_cell))
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            },
+        )
+    }
+
+    // Returns the cell containing the root computed from the leaf and the path
+    pub fn compute_root(
+        &self,
+        mut layouter: impl Layouter<F>,
+        leaf_cell: &AssignedCell<F, F>,
+        path: &[(AssignedCell<F, F>, AssignedCell<F, F>)],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let merkle_chip =
+            MerkleTreeV3Chip::<F, S>::construct(self.config.merkle_tree_v3_config.clone());
+
+        let mut node_cell = leaf_cell.clone();
+        for (element_cell, index_cell) in path {
+            node_cell = merkle_chip.merkle_prove_layer_with_cells(
+                layouter.namespace(|| "merkle update layer"),
+                &node_cell,
+                element_cell,
+                index_cell,
+            )?;
+        }
+        Ok(node_cell)
+    }
+
+    // Assigns the leaves and the path of an update and computes the old and the new root
+    pub fn update(
+        &self,
+        mut layouter: impl Layouter<F>,
+        old_leaf: Value<F>,
+        new_leaf: Value<F>,
+        path_elements: &[Value<F>],
+        path_indices: &[Value<F>],
+    ) -> Result<AssignedMerkleUpdate<F>, Error> {
+        let (old_leaf, new_leaf) =
+            self.assign_leaves(layouter.namespace(|| "assign leaves"), old_leaf, new_leaf)?;
+        let path = self.assign_path(
+            layouter.namespace(|| "assign path"),
+            path_elements,
+            path_indices,
+        )?;
+
+        let old_root = self.compute_root(layouter.namespace(|| "old root"), &old_leaf, &path)?;
+        let new_root = self.compute_root(layouter.namespace(|| "new root"), &new_leaf, &path)?;
+
+        Ok(AssignedMerkleUpdate {
+            old_leaf,
+            new_leaf,
+            old_root,
+            new_root,
+        })
+    }
+
+    // Enforces that the new root of an update is the old root of the next one
+    pub fn chain(
+        &self,
+        mut layouter: impl Layouter<F>,
+        previous: &AssignedMerkleUpdate<F>,
+        next: &AssignedMerkleUpdate<F>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "chain updates",
+            |mut region| region.constrain_equal(previous.new_root.cell(), next.old_root.cell()),
+        )
+    }
+
+    // Enforce permutation check between input cell and instance column at row passed as input
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.merkle_tree_v3_config.instance, row)
+    }
+}
@@ -1,42 +1,153 @@
 /*
 An easy-to-use implementation of the Poseidon Hash in the form of a Halo2 Chip. While the Poseidon Hash function
 is already implemented in halo2_gadgets, there is no wrapper chip that makes it easy to use in other circuits.
+The permutation is computed by the Pow5Chip of halo2_gadgets when the spec has an even number of partial rounds. The
+odd number of partial rounds of the circomlib specs for BN254 isn't supported by the Pow5Chip, so these specs use the
+PermutationChip, which has the same layout.
 */
 
 // This chip adds a set of advice columns to the gadget Chip to store the inputs of the hash
 // Furthermore it adds an instance column to store the public expected output of the hash
 
-use halo2_gadgets::poseidon::{primitives::*, Hash, Pow5Chip, Pow5Config};
-use halo2_proofs::{circuit::*, plonk::*, halo2curves::pasta::Fp};
-use std::marker::PhantomData;
+use super::hash_instructions::TwoToOneHashInstructions;
+use super::poseidon_permutation::{PermutationChip, PermutationConfig};
+use super::poseidon_spec::permute;
+use halo2_gadgets::poseidon::{
+    primitives::*, PaddedWord, PoseidonInstructions, Pow5Chip, Pow5Config, Sponge, StateWord,
+};
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
+use std::{iter, marker::PhantomData};
+
+// Domain used for messages whose length is only known at proving time.
+// The message is padded with a single 1 followed by as many 0s as needed to fill the last block (10* padding),
+// so that messages of different lengths never produce the same padded input.
+// The capacity is initialized to 1, while ConstantLength<L> always uses a multiple of 2^64, which separates the two domains.
+#[derive(Clone, Copy, Debug)]
+pub struct VariableLength;
+
+impl<F: FieldExt, const RATE: usize> Domain<F, RATE> for VariableLength {
+    type Padding = Vec<F>;
+
+    fn name() -> String {
+        "VariableLength".to_string()
+    }
+
+    fn initial_capacity_element() -> F {
+        F::one()
+    }
+
+    fn padding(input_len: usize) -> Self::Padding {
+        let padding_len = RATE - input_len % RATE;
+        iter::once(F::one())
+            .chain(iter::repeat(F::zero()))
+            .take(padding_len)
+            .collect()
+    }
+}
+
+// Computes outside of the circuit the same digest as PoseidonChip::hash_sponge, to be used for witness generation
+pub fn sponge_hash<F: FieldExt, S: Spec<F, WIDTH, RATE>, const WIDTH: usize, const RATE: usize>(
+    message: &[F],
+) -> F {
+    let mut state = [F::zero(); WIDTH];
+    state[RATE] = <VariableLength as Domain<F, RATE>>::initial_capacity_element();
+
+    let padded_message = message
+        .iter()
+        .cloned()
+        .chain(<VariableLength as Domain<F, RATE>>::padding(message.len()))
+        .collect::<Vec<F>>();
+
+    // absorb one block of RATE elements per permutation
+    for block in padded_message.chunks(RATE) {
+        for (word, value) in state.iter_mut().zip(block.iter()) {
+            *word += value;
+        }
+        permute::<F, S, WIDTH, RATE>(&mut state);
+    }
+
+    // squeeze a single element
+    state[0]
+}
+
+// Computes outside of the circuit the same digest as PoseidonChip::hash_circomlib, which is the digest of circomlib
+// poseidon for RATE inputs: the permutation of [0, message], squeezed from the first word
+pub fn circomlib_hash<
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
+    const WIDTH: usize,
+    const RATE: usize,
+>(
+    message: &[F; RATE],
+) -> F {
+    let mut state = [F::zero(); WIDTH];
+    state[1..].copy_from_slice(message);
+    permute::<F, S, WIDTH, RATE>(&mut state);
+    state[0]
+}
+
+// Rows of the layout of the Pow5Chip with P128Pow5T3 (8 full rounds, 56 partial rounds, width 3), used by the
+// cost tests. A permutation takes one row per full round, one row per pair of partial rounds and one row for its
+// output. A hash with ConstantLength<2> lays out its initial state in 1 row and absorbs the inputs in 3 rows before
+// the permutation, and assigns one constant per word of its initial state
+#[cfg(test)]
+pub(crate) const P128_POW5_T3_PERMUTATION_ROWS: usize = 8 + 56 / 2 + 1;
+#[cfg(test)]
+pub(crate) const P128_POW5_T3_HASH_ROWS: usize = 1 + 3 + P128_POW5_T3_PERMUTATION_ROWS;
+#[cfg(test)]
+pub(crate) const P128_POW5_T3_HASH_CONSTANTS: usize = 3;
+
+// The chip computing the permutation, chosen by PoseidonChip::configure from the number of partial rounds of the spec
+#[derive(Debug, Clone)]
+enum Permutation<F: FieldExt, const WIDTH: usize, const RATE: usize> {
+    Pow5(Pow5Config<F, WIDTH, RATE>),
+    OddPartialRounds(PermutationConfig<F, WIDTH, RATE>),
+}
 
 #[derive(Debug, Clone)]
 
-// WIDTH, RATE and L are const generics for the struct, which represent the width, rate, and number of inputs for the Poseidon hash function, respectively.
+// WIDTH and RATE are const generics for the struct, which represent the width and rate of the Poseidon permutation.
 // This means they are values that are known at compile time and can be used to specialize the implementation of the struct.
-// The actual chip provided by halo2_gadgets is added to the parent Chip.
-pub struct PoseidonConfig<const WIDTH: usize, const RATE: usize, const L: usize> {
+// The number of inputs L is not part of the configuration: the same config can be shared by chips hashing a different number of inputs.
+// The chip computing the permutation is added to the parent Chip.
+pub struct PoseidonConfig<F: FieldExt, const WIDTH: usize, const RATE: usize> {
     hash_inputs: Vec<Column<Advice>>,
     instance: Column<Instance>,
-    pow5_config: Pow5Config<Fp, WIDTH, RATE>,
+    permutation: Permutation<F, WIDTH, RATE>,
 }
 
 #[derive(Debug, Clone)]
 
 pub struct PoseidonChip<
-    S: Spec<Fp, WIDTH, RATE>,
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
     const WIDTH: usize,
     const RATE: usize,
     const L: usize,
 > {
-    config: PoseidonConfig<WIDTH, RATE, L>,
+    config: PoseidonConfig<F, WIDTH, RATE>,
     _marker: PhantomData<S>,
 }
 
-impl<S: Spec<Fp, WIDTH, RATE>, const WIDTH: usize, const RATE: usize, const L: usize> 
-    PoseidonChip<S, WIDTH, RATE, L>
+impl<
+        F: FieldExt,
+        S: Spec<F, WIDTH, RATE>,
+        const WIDTH: usize,
+        const RATE: usize,
+        const L: usize,
+    > PoseidonChip<F, S, WIDTH, RATE, L>
 {
-    pub fn construct(config: PoseidonConfig<WIDTH, RATE, L>) -> Self {
+    // hash_batch only supports tuples that fit in a single block, so it doesn't compile for L > RATE.
+    // The other methods don't reference it and keep working for any L
+    const FITS_IN_ONE_BLOCK: () = assert!(
+        L <= RATE,
+        "batched hashing requires the inputs to fit in a single block"
+    );
+
+    // circomlib hashes n inputs with a permutation of width n + 1, so hash_circomlib doesn't compile for L != RATE
+    const CIRCOMLIB_ARITY: () = assert!(L == RATE, "the circomlib hash takes exactly RATE inputs");
+
+    pub fn construct(config: PoseidonConfig<F, WIDTH, RATE>) -> Self {
         Self {
             config,
             _marker: PhantomData,
@@ -45,11 +156,10 @@ impl<S: Spec<Fp, WIDTH, RATE>, const WIDTH: usize, const RATE: usize, const L: u
 
     // Configuration of the PoseidonChip
     pub fn configure(
-        meta: &mut ConstraintSystem<Fp>,
+        meta: &mut ConstraintSystem<F>,
         hash_inputs: Vec<Column<Advice>>,
         instance: Column<Instance>,
-    ) -> PoseidonConfig<WIDTH, RATE, L> {
-
+    ) -> PoseidonConfig<F, WIDTH, RATE> {
         let partial_sbox = meta.advice_column();
         let rc_a = (0..WIDTH).map(|_| meta.fixed_column()).collect::<Vec<_>>();
         let rc_b = (0..WIDTH).map(|_| meta.fixed_column()).collect::<Vec<_>>();
@@ -60,29 +170,44 @@ impl<S: Spec<Fp, WIDTH, RATE>, const WIDTH: usize, const RATE: usize, const L: u
         meta.enable_equality(instance);
         meta.enable_constant(rc_b[0]);
 
-        let pow5_config = Pow5Chip::configure::<S>(
-            meta,
-            hash_inputs.clone().try_into().unwrap(),
-            partial_sbox,
-            rc_a.try_into().unwrap(),
-            rc_b.try_into().unwrap(),
-        );
+        let state: [Column<Advice>; WIDTH] = hash_inputs.clone().try_into().unwrap();
+        let rc_a = rc_a.try_into().unwrap();
+        let rc_b = rc_b.try_into().unwrap();
+
+        // Both chips have the same gates and layout when the number of partial rounds is even
+        let permutation = if S::partial_rounds() % 2 == 0 {
+            Permutation::Pow5(Pow5Chip::configure::<S>(
+                meta,
+                state,
+                partial_sbox,
+                rc_a,
+                rc_b,
+            ))
+        } else {
+            Permutation::OddPartialRounds(PermutationChip::<F, S, WIDTH, RATE>::configure(
+                meta,
+                state,
+                partial_sbox,
+                rc_a,
+                rc_b,
+            ))
+        };
 
         PoseidonConfig {
             hash_inputs,
             instance,
-            pow5_config,
+            permutation,
         }
     }
 
     pub fn load_private_inputs(
         &self,
-        mut layouter: impl Layouter<Fp>,
-        inputs: [Value<Fp>; L],
-    ) -> Result<[AssignedCell<Fp, Fp>; L], Error> {
+        mut layouter: impl Layouter<F>,
+        inputs: [Value<F>; L],
+    ) -> Result<[AssignedCell<F, F>; L], Error> {
         layouter.assign_region(
             || "load private inputs",
-            |mut region| -> Result<[AssignedCell<Fp, Fp>; L], Error> {
+            |mut region| -> Result<[AssignedCell<F, F>; L], Error> {
                 let result = inputs
                     .iter()
                     .enumerate()
@@ -94,7 +219,7 @@ impl<S: Spec<Fp, WIDTH, RATE>, const WIDTH: usize, const RATE: usize, const L: u
                             || x.to_owned(),
                         )
                     })
-                    .collect::<Result<Vec<AssignedCell<Fp, Fp>>, Error>>();
+                    .collect::<Result<Vec<AssignedCell<F, F>>, Error>>();
                 Ok(result?.try_into().unwrap())
             },
         )
@@ -102,49 +227,282 @@ impl<S: Spec<Fp, WIDTH, RATE>, const WIDTH: usize, const RATE: usize, const L: u
 
     // L is the number of inputs to the hash function
     // Takes the cells containing the input values of the hash function and return the cell containing the hash output
-    // It uses the pow5_chip to compute the hash
+    // The digest is the one of the ConstantLength<L> domain of halo2_gadgets, as computed by poseidon::primitives::Hash
+    // The input cells can live in any region of the circuit: they are copied straight into the state of the permutation,
+    // so no intermediate copy region is needed
     pub fn hash(
         &self,
-        mut layouter: impl Layouter<Fp>,
-        input_cells: &[AssignedCell<Fp, Fp>; L],
-    ) -> Result<AssignedCell<Fp, Fp>, Error> {
-        // Assign values to word_cells by copying it from the cells passed as input
-        let hash_input_cells = layouter.assign_region(
-            || "copy input cells to hash input cells",
-            |mut region| -> Result<[AssignedCell<Fp, Fp>; L], Error> {
-                let result = input_cells
+        layouter: impl Layouter<F>,
+        input_cells: &[AssignedCell<F, F>; L],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let message = input_cells
+            .iter()
+            .cloned()
+            .map(PaddedWord::Message)
+            .chain(
+                <ConstantLength<L> as Domain<F, RATE>>::padding(L)
+                    .into_iter()
+                    .map(PaddedWord::Padding),
+            )
+            .collect::<Vec<_>>();
+        self.absorb_and_squeeze::<ConstantLength<L>>(layouter, &message)
+    }
+
+    // Hashes L = RATE inputs like circomlib poseidon: the permutation starts from [0, inputs], with the zero
+    // capacity first, and the digest is the first word of its output. There is no padding and no block to absorb, the
+    // input cells are copied straight into the first row of the permutation
+    pub fn hash_circomlib(
+        &self,
+        mut layouter: impl Layouter<F>,
+        input_cells: &[AssignedCell<F, F>; L],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let () = Self::CIRCOMLIB_ARITY;
+
+        let capacity_cell = layouter.assign_region(
+            || "circomlib capacity",
+            |mut region| {
+                region.assign_advice_from_constant(
+                    || "capacity",
+                    self.config.hash_inputs[0],
+                    0,
+                    F::zero(),
+                )
+            },
+        )?;
+
+        let initial_state: [AssignedCell<F, F>; WIDTH] = iter::once(capacity_cell)
+            .chain(input_cells.iter().cloned())
+            .collect::<Vec<AssignedCell<F, F>>>()
+            .try_into()
+            .unwrap();
+
+        let state = self.permute(layouter.namespace(|| "permute"), &initial_state)?;
+        Ok(state[0].clone())
+    }
+
+    // Hashes many tuples of L inputs at once and returns one digest cell per tuple, in the same order
+    // The digests are the same as the ones computed by calling hash on every tuple, but only tuples that fit in
+    // a single block (L <= RATE) are supported. After absorbing a single block the state of the sponge is
+    // [inputs, padding, capacity] where the padding and the capacity are the same constants for every tuple:
+    // they are assigned once and shared, and each tuple only costs the rows of its permutation.
+    pub fn hash_batch(
+        &self,
+        mut layouter: impl Layouter<F>,
+        inputs: &[[AssignedCell<F, F>; L]],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let () = Self::FITS_IN_ONE_BLOCK;
+
+        let (padding_cell, capacity_cell) = layouter.assign_region(
+            || "shared padding and capacity",
+            |mut region| {
+                let padding_cell = region.assign_advice_from_constant(
+                    || "padding",
+                    self.config.hash_inputs[0],
+                    0,
+                    F::zero(),
+                )?;
+                let capacity_cell = region.assign_advice_from_constant(
+                    || "capacity",
+                    self.config.hash_inputs[1],
+                    0,
+                    <ConstantLength<L> as Domain<F, RATE>>::initial_capacity_element(),
+                )?;
+                Ok((padding_cell, capacity_cell))
+            },
+        )?;
+
+        let mut digests = Vec::with_capacity(inputs.len());
+        for (i, input_cells) in inputs.iter().enumerate() {
+            let initial_state: [AssignedCell<F, F>; WIDTH] = (0..WIDTH)
+                .map(|j| {
+                    if j < L {
+                        input_cells[j].clone()
+                    } else if j < RATE {
+                        padding_cell.clone()
+                    } else {
+                        capacity_cell.clone()
+                    }
+                })
+                .collect::<Vec<AssignedCell<F, F>>>()
+                .try_into()
+                .unwrap();
+
+            let state = self.permute(
+                layouter.namespace(|| format!("permute {}", i)),
+                &initial_state,
+            )?;
+
+            // squeeze the first element of the state
+            digests.push(state[0].clone());
+        }
+
+        Ok(digests)
+    }
+
+    // Loads an arbitrary number of inputs, filling the hash_inputs columns row by row
+    pub fn load_private_sponge_inputs(
+        &self,
+        mut layouter: impl Layouter<F>,
+        inputs: &[Value<F>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        layouter.assign_region(
+            || "load private sponge inputs",
+            |mut region| {
+                inputs
                     .iter()
                     .enumerate()
-                    .map(|(i, input_cell)| {
-                        input_cell.copy_advice(
-                            || format!("word {}", i),
-                            &mut region,
-                            self.config.hash_inputs[i],
-                            0,
+                    .map(|(i, x)| {
+                        region.assign_advice(
+                            || "private input",
+                            self.config.hash_inputs[i % WIDTH],
+                            i / WIDTH,
+                            || x.to_owned(),
                         )
                     })
-                    .collect::<Result<Vec<AssignedCell<Fp, Fp>>, Error>>();
-                Ok(result?.try_into().unwrap())
+                    .collect::<Result<Vec<AssignedCell<F, F>>, Error>>()
             },
-        )?;
+        )
+    }
 
+    // Hashes a number of input cells that is only known at proving time
+    // Each block of RATE cells is absorbed with one permutation, the last block is completed with the padding of the VariableLength domain
+    // L is not used here, the same chip can be used for both fixed and variable length hashing
+    pub fn hash_sponge(
+        &self,
+        layouter: impl Layouter<F>,
+        input_cells: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let message = input_cells
+            .iter()
+            .cloned()
+            .map(PaddedWord::Message)
+            .chain(
+                <VariableLength as Domain<F, RATE>>::padding(input_cells.len())
+                    .into_iter()
+                    .map(PaddedWord::Padding),
+            )
+            .collect::<Vec<_>>();
+        self.absorb_and_squeeze::<VariableLength>(layouter, &message)
+    }
 
-        let pow5_chip = Pow5Chip::construct(self.config.pow5_config.clone());
+    // Sponge of the domain D over a padded message: the initial state takes one row, each block of RATE words is
+    // added to the state in three rows and followed by a permutation, and the digest is the first word of the state
+    fn absorb_and_squeeze<D: Domain<F, RATE>>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        message: &[PaddedWord<F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let config = match &self.config.permutation {
+            Permutation::Pow5(config) => config,
+            Permutation::OddPartialRounds(config) => {
+                return Self::absorb_and_squeeze_odd_partial_rounds::<D>(config, layouter, message)
+            }
+        };
 
-        // initialize the hasher
-        let hasher = Hash::<_, _, S, ConstantLength<L>, WIDTH, RATE>::init(
-            pow5_chip,
-            layouter.namespace(|| "hasher"),
+        // the Sponge gadget absorbs a block every RATE words, the message is already padded
+        let mut sponge = Sponge::<_, _, S, Absorbing<PaddedWord<F>, RATE>, D, WIDTH, RATE>::new(
+            Pow5Chip::construct(config.clone()),
+            layouter.namespace(|| format!("initial state for domain {}", D::name())),
         )?;
-        hasher.hash(layouter.namespace(|| "hash"), hash_input_cells)
+        for (i, word) in message.iter().enumerate() {
+            sponge.absorb(
+                layouter.namespace(|| format!("absorb word {}", i)),
+                word.clone(),
+            )?;
+        }
+        sponge
+            .finish_absorbing(layouter.namespace(|| "finish absorbing"))?
+            .squeeze(layouter.namespace(|| "squeeze"))
+    }
+
+    // The Sponge gadget of halo2_gadgets only drives the Pow5Chip, so the steps of the sponge are the ones exposed by
+    // the PermutationChip
+    fn absorb_and_squeeze_odd_partial_rounds<D: Domain<F, RATE>>(
+        config: &PermutationConfig<F, WIDTH, RATE>,
+        mut layouter: impl Layouter<F>,
+        message: &[PaddedWord<F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let permutation_chip = PermutationChip::<F, S, WIDTH, RATE>::construct(config.clone());
+
+        let mut state = permutation_chip.initial_state(
+            layouter.namespace(|| format!("initial state for domain {}", D::name())),
+            D::initial_capacity_element(),
+        )?;
+
+        for (i, block) in message.chunks(RATE).enumerate() {
+            state = permutation_chip.add_input(
+                layouter.namespace(|| format!("absorb block {}", i)),
+                &state,
+                block,
+            )?;
+            state = permutation_chip.permute(
+                layouter.namespace(|| format!("permute block {}", i)),
+                &state,
+            )?;
+        }
+
+        Ok(state[0].clone())
+    }
+
+    // Applies the permutation to a state whose cells can live in any region, they are copied into its first row
+    fn permute(
+        &self,
+        mut layouter: impl Layouter<F>,
+        state: &[AssignedCell<F, F>; WIDTH],
+    ) -> Result<[AssignedCell<F, F>; WIDTH], Error> {
+        match &self.config.permutation {
+            Permutation::Pow5(config) => {
+                let chip = Pow5Chip::construct(config.clone());
+                let state = PoseidonInstructions::<F, S, WIDTH, RATE>::permute(
+                    &chip,
+                    &mut layouter,
+                    &state.clone().map(StateWord::from),
+                )?;
+                Ok(state.map(AssignedCell::from))
+            }
+            Permutation::OddPartialRounds(config) => {
+                PermutationChip::<F, S, WIDTH, RATE>::construct(config.clone())
+                    .permute(layouter, state)
+            }
+        }
     }
 
     pub fn expose_public(
         &self,
-        mut layouter: impl Layouter<Fp>,
-        cell: &AssignedCell<Fp, Fp>,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
         row: usize,
     ) -> Result<(), Error> {
         layouter.constrain_instance(cell.cell(), self.config.instance, row)
     }
 }
+
+// Hash of two inputs with ConstantLength<2>, as computed by merkle_tree::hash_nodes for width 3 and rate 2
+impl<F: FieldExt, S: Spec<F, WIDTH, RATE>, const WIDTH: usize, const RATE: usize>
+    TwoToOneHashInstructions<F> for PoseidonChip<F, S, WIDTH, RATE, 2>
+{
+    type Config = PoseidonConfig<F, WIDTH, RATE>;
+
+    // The permutation needs WIDTH advice columns of its own, the columns of the parent chip are not used
+    fn configure(
+        meta: &mut ConstraintSystem<F>,
+        _advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> PoseidonConfig<F, WIDTH, RATE> {
+        let hash_inputs = (0..WIDTH).map(|_| meta.advice_column()).collect::<Vec<_>>();
+        Self::configure(meta, hash_inputs, instance)
+    }
+
+    fn construct(config: PoseidonConfig<F, WIDTH, RATE>) -> Self {
+        Self::construct(config)
+    }
+
+    fn hash_two(
+        &self,
+        layouter: impl Layouter<F>,
+        left: AssignedCell<F, F>,
+        right: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.hash(layouter, &[left, right])
+    }
+}
@@ -0,0 +1,620 @@
+/*
+The Poseidon permutation as a Halo2 Chip, with the same gates and layout as the Pow5Chip of halo2_gadgets.
+The Pow5Chip requires an even number of partial rounds, because it computes them two per row. The specs used by
+circomlib for BN254 have an odd number of partial rounds (R_P = 57 for width 3), so this chip computes the partial
+rounds two per row as well and adds a row with a single partial round when R_P is odd.
+The PoseidonChip only uses this chip for the specs with an odd R_P, the other specs are permuted by the Pow5Chip.
+*/
+
+// The halo2_gadgets sponge instructions can't be implemented outside of the crate, therefore this chip exposes the
+// three steps of a sponge itself: the initial state, the addition of a block of inputs and the permutation.
+// Like the Pow5Chip, it only supports the x^5 S-box and a capacity of one word (WIDTH = RATE + 1).
+
+use halo2_gadgets::poseidon::{primitives::Spec, PaddedWord};
+use halo2_proofs::{
+    arithmetic::FieldExt, circuit::*, halo2curves::group::ff::Field, plonk::*, poly::Rotation,
+};
+use std::marker::PhantomData;
+
+fn pow5<F: FieldExt>(value: F) -> F {
+    value.pow_vartime(&[5])
+}
+
+#[derive(Debug, Clone)]
+pub struct PermutationConfig<F: FieldExt, const WIDTH: usize, const RATE: usize> {
+    state: [Column<Advice>; WIDTH],
+    partial_sbox: Column<Advice>,
+    rc_a: [Column<Fixed>; WIDTH],
+    rc_b: [Column<Fixed>; WIDTH],
+    s_full: Selector,
+    s_partial: Selector,
+    // only configured when the number of partial rounds is odd
+    s_partial_single: Option<Selector>,
+    s_pad_and_add: Selector,
+    half_full_rounds: usize,
+    partial_rounds: usize,
+    round_constants: Vec<[F; WIDTH]>,
+    m_reg: [[F; WIDTH]; WIDTH],
+}
+
+#[derive(Debug, Clone)]
+pub struct PermutationChip<
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
+    const WIDTH: usize,
+    const RATE: usize,
+> {
+    config: PermutationConfig<F, WIDTH, RATE>,
+    _marker: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, WIDTH, RATE>, const WIDTH: usize, const RATE: usize>
+    PermutationChip<F, S, WIDTH, RATE>
+{
+    pub fn construct(config: PermutationConfig<F, WIDTH, RATE>) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    // The state words live in the state columns, the round constants in rc_a and rc_b. The first word of the first
+    // round of a pair of partial rounds goes through the S-box in partial_sbox.
+    // rc_b is only used by the partial rounds, so it also holds the padding words added to the state
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        state: [Column<Advice>; WIDTH],
+        partial_sbox: Column<Advice>,
+        rc_a: [Column<Fixed>; WIDTH],
+        rc_b: [Column<Fixed>; WIDTH],
+    ) -> PermutationConfig<F, WIDTH, RATE> {
+        assert_eq!(RATE, WIDTH - 1);
+        assert_eq!(S::full_rounds() % 2, 0);
+
+        let half_full_rounds = S::full_rounds() / 2;
+        let partial_rounds = S::partial_rounds();
+        let (round_constants, m_reg, m_inv) = S::constants();
+
+        // the state is copied in and out of the permutation, and the padding words are copied into the state
+        for column in state {
+            meta.enable_equality(column);
+        }
+        for column in rc_b {
+            meta.enable_equality(column);
+        }
+
+        let s_full = meta.selector();
+        let s_partial = meta.selector();
+        let s_pad_and_add = meta.selector();
+
+        let pow_5 = |v: Expression<F>| {
+            let v2 = v.clone() * v.clone();
+            v2.clone() * v2 * v
+        };
+
+        // next = M * (cur + rc_a)^5
+        meta.create_gate("full round", |meta| {
+            let s = meta.query_selector(s_full);
+            (0..WIDTH)
+                .map(|next_idx| {
+                    let state_next = meta.query_advice(state[next_idx], Rotation::next());
+                    let expr = (0..WIDTH)
+                        .map(|idx| {
+                            let state_cur = meta.query_advice(state[idx], Rotation::cur());
+                            let rc_a = meta.query_fixed(rc_a[idx], Rotation::cur());
+                            pow_5(state_cur + rc_a) * m_reg[next_idx][idx]
+                        })
+                        .reduce(|acc, term| acc + term)
+                        .expect("WIDTH > 0");
+                    s.clone() * (expr - state_next)
+                })
+                .collect::<Vec<_>>()
+        });
+
+        // Two partial rounds per row. mid = M * (cur + rc_a) with the S-box on the first word, stored in
+        // partial_sbox. The second round is checked against the next state through the inverse of the MDS matrix:
+        // M^-1 * next = mid + rc_b, with the S-box on the first word
+        meta.create_gate("partial rounds", |meta| {
+            let s = meta.query_selector(s_partial);
+            let cur_0 = meta.query_advice(state[0], Rotation::cur());
+            let mid_0 = meta.query_advice(partial_sbox, Rotation::cur());
+            let rc_a0 = meta.query_fixed(rc_a[0], Rotation::cur());
+            let rc_b0 = meta.query_fixed(rc_b[0], Rotation::cur());
+
+            let mid = |idx: usize, meta: &mut VirtualCells<F>| {
+                (1..WIDTH).fold(mid_0.clone() * m_reg[idx][0], |acc, cur_idx| {
+                    let cur = meta.query_advice(state[cur_idx], Rotation::cur());
+                    let rc_a = meta.query_fixed(rc_a[cur_idx], Rotation::cur());
+                    acc + (cur + rc_a) * m_reg[idx][cur_idx]
+                })
+            };
+
+            let next = |idx: usize, meta: &mut VirtualCells<F>| {
+                (0..WIDTH)
+                    .map(|next_idx| {
+                        let next = meta.query_advice(state[next_idx], Rotation::next());
+                        next * m_inv[idx][next_idx]
+                    })
+                    .reduce(|acc, next| acc + next)
+                    .expect("WIDTH > 0")
+            };
+
+            let mut constraints = vec![
+                s.clone() * (pow_5(cur_0 + rc_a0) - mid_0.clone()),
+                s.clone() * (pow_5(mid(0, meta) + rc_b0) - next(0, meta)),
+            ];
+            for idx in 1..WIDTH {
+                let rc_b = meta.query_fixed(rc_b[idx], Rotation::cur());
+                constraints.push(s.clone() * (mid(idx, meta) + rc_b - next(idx, meta)));
+            }
+            constraints
+        });
+
+        // With an odd number of partial rounds, the last one takes a row of its own:
+        // next = M * (cur + rc_a) with the S-box on the first word
+        let s_partial_single = (partial_rounds % 2 == 1).then(|| {
+            let s_partial_single = meta.selector();
+            meta.create_gate("single partial round", |meta| {
+                let s = meta.query_selector(s_partial_single);
+                (0..WIDTH)
+                    .map(|next_idx| {
+                        let state_next = meta.query_advice(state[next_idx], Rotation::next());
+                        let expr = (0..WIDTH)
+                            .map(|idx| {
+                                let state_cur = meta.query_advice(state[idx], Rotation::cur());
+                                let rc_a = meta.query_fixed(rc_a[idx], Rotation::cur());
+                                let word = if idx == 0 {
+                                    pow_5(state_cur + rc_a)
+                                } else {
+                                    state_cur + rc_a
+                                };
+                                word * m_reg[next_idx][idx]
+                            })
+                            .reduce(|acc, term| acc + term)
+                            .expect("WIDTH > 0");
+                        s.clone() * (expr - state_next)
+                    })
+                    .collect::<Vec<_>>()
+            });
+            s_partial_single
+        });
+
+        // The inputs in the current row are added to the rate of the state in the previous row, the capacity is
+        // left unchanged
+        meta.create_gate("pad-and-add", |meta| {
+            let s = meta.query_selector(s_pad_and_add);
+            let initial_state_rate = meta.query_advice(state[RATE], Rotation::prev());
+            let output_state_rate = meta.query_advice(state[RATE], Rotation::next());
+
+            let mut constraints = (0..RATE)
+                .map(|idx| {
+                    let initial_state = meta.query_advice(state[idx], Rotation::prev());
+                    let input = meta.query_advice(state[idx], Rotation::cur());
+                    let output_state = meta.query_advice(state[idx], Rotation::next());
+                    s.clone() * (initial_state + input - output_state)
+                })
+                .collect::<Vec<_>>();
+            constraints.push(s * (initial_state_rate - output_state_rate));
+            constraints
+        });
+
+        PermutationConfig {
+            state,
+            partial_sbox,
+            rc_a,
+            rc_b,
+            s_full,
+            s_partial,
+            s_partial_single,
+            s_pad_and_add,
+            half_full_rounds,
+            partial_rounds,
+            round_constants,
+            m_reg,
+        }
+    }
+
+    // Assigns the initial state of a sponge in one row: zeros in the rate and the capacity element of the domain
+    pub fn initial_state(
+        &self,
+        mut layouter: impl Layouter<F>,
+        capacity: F,
+    ) -> Result<[AssignedCell<F, F>; WIDTH], Error> {
+        layouter.assign_region(
+            || "initial state",
+            |mut region| {
+                let state = (0..WIDTH)
+                    .map(|i| {
+                        region.assign_advice_from_constant(
+                            || format!("state_{}", i),
+                            self.config.state[i],
+                            0,
+                            if i == RATE { capacity } else { F::zero() },
+                        )
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok(state.try_into().unwrap())
+            },
+        )
+    }
+
+    // Adds a block of RATE inputs to the rate of the state in three rows: the state, the inputs and the sum.
+    // The message words are copied from their cells, the padding words are fixed in rc_b
+    pub fn add_input(
+        &self,
+        mut layouter: impl Layouter<F>,
+        state: &[AssignedCell<F, F>; WIDTH],
+        input: &[PaddedWord<F>],
+    ) -> Result<[AssignedCell<F, F>; WIDTH], Error> {
+        assert_eq!(input.len(), RATE, "a block has RATE words");
+
+        layouter.assign_region(
+            || "add input",
+            |mut region| {
+                self.config.s_pad_and_add.enable(&mut region, 1)?;
+
+                let initial_state = self.load_state(&mut region, state)?;
+
+                let input = input
+                    .iter()
+                    .enumerate()
+                    .map(|(i, word)| {
+                        let (cell, value) = match word {
+                            PaddedWord::Message(cell) => (cell.cell(), cell.value().copied()),
+                            PaddedWord::Padding(padding) => {
+                                let cell = region.assign_fixed(
+                                    || format!("load pad_{}", i),
+                                    self.config.rc_b[i],
+                                    1,
+                                    || Value::known(*padding),
+                                )?;
+                                (cell.cell(), Value::known(*padding))
+                            }
+                        };
+                        let input_cell = region.assign_advice(
+                            || format!("load input_{}", i),
+                            self.config.state[i],
+                            1,
+                            || value,
+                        )?;
+                        region.constrain_equal(cell, input_cell.cell())?;
+                        Ok(input_cell)
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+
+                let output = (0..WIDTH)
+                    .map(|i| {
+                        let value = initial_state[i].value().copied()
+                            + input
+                                .get(i)
+                                .map(|word| word.value().copied())
+                                .unwrap_or_else(|| Value::known(F::zero()));
+                        region.assign_advice(
+                            || format!("load output_{}", i),
+                            self.config.state[i],
+                            2,
+                            || value,
+                        )
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok(output.try_into().unwrap())
+            },
+        )
+    }
+
+    // Applies the permutation to the state, whose cells can live in any region: they are copied into the first row.
+    // The permutation takes one row per full round, one row per pair of partial rounds, one more row when the number
+    // of partial rounds is odd and one row for the output
+    pub fn permute(
+        &self,
+        mut layouter: impl Layouter<F>,
+        state: &[AssignedCell<F, F>; WIDTH],
+    ) -> Result<[AssignedCell<F, F>; WIDTH], Error> {
+        layouter.assign_region(
+            || "permute state",
+            |mut region| {
+                let config = &self.config;
+                let mut state = self.load_state(&mut region, state)?;
+                let mut round = 0;
+                let mut offset = 0;
+
+                for _ in 0..config.half_full_rounds {
+                    state = self.full_round(&mut region, &state, round, offset)?;
+                    round += 1;
+                    offset += 1;
+                }
+                for _ in 0..config.partial_rounds / 2 {
+                    state = self.partial_rounds(&mut region, &state, round, offset)?;
+                    round += 2;
+                    offset += 1;
+                }
+                if let Some(s_partial_single) = config.s_partial_single {
+                    state = self.single_partial_round(
+                        &mut region,
+                        s_partial_single,
+                        &state,
+                        round,
+                        offset,
+                    )?;
+                    round += 1;
+                    offset += 1;
+                }
+                for _ in 0..config.half_full_rounds {
+                    state = self.full_round(&mut region, &state, round, offset)?;
+                    round += 1;
+                    offset += 1;
+                }
+
+                Ok(state.try_into().unwrap())
+            },
+        )
+    }
+
+    // Copies the state into the first row of the region
+    fn load_state(
+        &self,
+        region: &mut Region<'_, F>,
+        state: &[AssignedCell<F, F>; WIDTH],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        state
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                word.copy_advice(
+                    || format!("load state_{}", i),
+                    region,
+                    self.config.state[i],
+                    0,
+                )
+            })
+            .collect()
+    }
+
+    fn full_round(
+        &self,
+        region: &mut Region<'_, F>,
+        state: &[AssignedCell<F, F>],
+        round: usize,
+        offset: usize,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        self.config.s_full.enable(region, offset)?;
+        let words = self
+            .add_round_constants(region, self.config.rc_a, state, round, offset)?
+            .iter()
+            .map(|word| word.map(pow5))
+            .collect::<Vec<_>>();
+        self.assign_state(region, &self.mds(&words), round + 1, offset + 1)
+    }
+
+    fn partial_rounds(
+        &self,
+        region: &mut Region<'_, F>,
+        state: &[AssignedCell<F, F>],
+        round: usize,
+        offset: usize,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        self.config.s_partial.enable(region, offset)?;
+
+        let mut words = self.add_round_constants(region, self.config.rc_a, state, round, offset)?;
+        words[0] = words[0].map(pow5);
+        region.assign_advice(
+            || format!("round_{} partial_sbox", round),
+            self.config.partial_sbox,
+            offset,
+            || words[0],
+        )?;
+
+        // the constants of the second round are in rc_b
+        let mid = self.mds(&words);
+        let mut words = mid
+            .iter()
+            .zip(self.config.round_constants[round + 1].iter())
+            .map(|(word, rc)| word.map(|word| word + rc))
+            .collect::<Vec<_>>();
+        for (i, rc) in self.config.round_constants[round + 1].iter().enumerate() {
+            region.assign_fixed(
+                || format!("round_{} rc_{}", round + 1, i),
+                self.config.rc_b[i],
+                offset,
+                || Value::known(*rc),
+            )?;
+        }
+        words[0] = words[0].map(pow5);
+        self.assign_state(region, &self.mds(&words), round + 2, offset + 1)
+    }
+
+    fn single_partial_round(
+        &self,
+        region: &mut Region<'_, F>,
+        s_partial_single: Selector,
+        state: &[AssignedCell<F, F>],
+        round: usize,
+        offset: usize,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        s_partial_single.enable(region, offset)?;
+        let mut words = self.add_round_constants(region, self.config.rc_a, state, round, offset)?;
+        words[0] = words[0].map(pow5);
+        self.assign_state(region, &self.mds(&words), round + 1, offset + 1)
+    }
+
+    // Assigns the constants of the round in the columns and returns the state words plus the constants
+    fn add_round_constants(
+        &self,
+        region: &mut Region<'_, F>,
+        columns: [Column<Fixed>; WIDTH],
+        state: &[AssignedCell<F, F>],
+        round: usize,
+        offset: usize,
+    ) -> Result<Vec<Value<F>>, Error> {
+        let round_constants = self.config.round_constants[round];
+        for (i, (column, rc)) in columns.iter().zip(round_constants.iter()).enumerate() {
+            region.assign_fixed(
+                || format!("round_{} rc_{}", round, i),
+                *column,
+                offset,
+                || Value::known(*rc),
+            )?;
+        }
+        Ok(state
+            .iter()
+            .zip(round_constants.iter())
+            .map(|(word, rc)| word.value().map(|word| *word + rc))
+            .collect())
+    }
+
+    fn mds(&self, words: &[Value<F>]) -> Vec<Value<F>> {
+        self.config
+            .m_reg
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(words.iter())
+                    .fold(Value::known(F::zero()), |acc, (m, word)| {
+                        acc + word.map(|word| word * m)
+                    })
+            })
+            .collect()
+    }
+
+    fn assign_state(
+        &self,
+        region: &mut Region<'_, F>,
+        words: &[Value<F>],
+        round: usize,
+        offset: usize,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                region.assign_advice(
+                    || format!("round_{} state_{}", round, i),
+                    self.config.state[i],
+                    offset,
+                    || *word,
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PermutationChip, PermutationConfig};
+    use crate::chips::poseidon_spec::permute;
+    use halo2_gadgets::poseidon::{
+        primitives::P128Pow5T3, PoseidonInstructions, Pow5Chip, Pow5Config, StateWord,
+    };
+    use halo2_proofs::{
+        circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::pasta::Fp,
+        plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
+    };
+
+    // Permutes the same state with the PermutationChip and with the Pow5Chip, and exposes both outputs
+    struct PermutationComparisonCircuit {
+        state: [Value<Fp>; 3],
+    }
+
+    impl Circuit<Fp> for PermutationComparisonCircuit {
+        type Config = (
+            PermutationConfig<Fp, 3, 2>,
+            Pow5Config<Fp, 3, 2>,
+            [Column<Advice>; 3],
+            Column<Instance>,
+        );
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                state: [Value::unknown(); 3],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let state = [(); 3].map(|_| meta.advice_column());
+            let partial_sbox = meta.advice_column();
+            let rc_a = [(); 3].map(|_| meta.fixed_column());
+            let rc_b = [(); 3].map(|_| meta.fixed_column());
+            let permutation_config = PermutationChip::<Fp, P128Pow5T3, 3, 2>::configure(
+                meta,
+                state,
+                partial_sbox,
+                rc_a,
+                rc_b,
+            );
+
+            let pow5_state = [(); 3].map(|_| meta.advice_column());
+            let partial_sbox = meta.advice_column();
+            let rc_a = [(); 3].map(|_| meta.fixed_column());
+            let rc_b = [(); 3].map(|_| meta.fixed_column());
+            let pow5_config =
+                Pow5Chip::configure::<P128Pow5T3>(meta, pow5_state, partial_sbox, rc_a, rc_b);
+
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+
+            (permutation_config, pow5_config, state, instance)
+        }
+
+        fn synthesize(
+            &self,
+            (permutation_config, pow5_config, state, instance): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let input: [AssignedCell<Fp, Fp>; 3] = layouter.assign_region(
+                || "assign state",
+                |mut region| {
+                    let cells = (0..3)
+                        .map(|i| {
+                            region.assign_advice(
+                                || format!("state_{}", i),
+                                state[i],
+                                0,
+                                || self.state[i],
+                            )
+                        })
+                        .collect::<Result<Vec<_>, Error>>()?;
+                    Ok(cells.try_into().unwrap())
+                },
+            )?;
+
+            let output = PermutationChip::<Fp, P128Pow5T3, 3, 2>::construct(permutation_config)
+                .permute(layouter.namespace(|| "permute"), &input)?;
+
+            let pow5_chip = Pow5Chip::construct(pow5_config);
+            let pow5_output = PoseidonInstructions::<Fp, P128Pow5T3, 3, 2>::permute(
+                &pow5_chip,
+                &mut layouter.namespace(|| "pow5 permute"),
+                &input.map(StateWord::from),
+            )?;
+
+            for (i, cell) in output
+                .into_iter()
+                .chain(pow5_output.into_iter().map(AssignedCell::from))
+                .enumerate()
+            {
+                layouter.constrain_instance(cell.cell(), instance, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_permutation_matches_pow5_chip() {
+        // P128Pow5T3 has R_P = 56, which the Pow5Chip supports
+        let state = [Fp::from(1), Fp::from(2), Fp::from(3)];
+        let mut expected = state;
+        permute::<Fp, P128Pow5T3, 3, 2>(&mut expected);
+
+        let circuit = PermutationComparisonCircuit {
+            state: state.map(Value::known),
+        };
+
+        // both outputs are the one of the native permutation
+        let public_input = [expected, expected].concat();
+        let prover = MockProver::run(8, &circuit, vec![public_input]).unwrap();
+        prover.assert_satisfied();
+    }
+}
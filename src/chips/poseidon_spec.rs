@@ -0,0 +1,310 @@
+/*
+Poseidon specifications for the BN254 scalar field, to be used alongside P128Pow5T3 (which is only defined for the pasta fields),
+and a width 5 specification for the pasta base field, which halo2_gadgets doesn't provide.
+
+The round constants and the MDS matrix are not hardcoded: they are generated by the default implementation of `Spec::constants`,
+which runs the Grain LFSR procedure of the Poseidon reference implementation. The number of rounds matches the parameters used by
+circomlib/iden3 for x^5 over BN254 (R_F = 8, R_P = 57 for width 3 and R_P = 60 for width 5), so that the permutation agrees with
+their off-chain tooling. The ConstantLength domain of halo2_gadgets puts the message first and the domain tag L * 2^64 in the
+last word of the state, while circomlib starts from [0, message], so PoseidonChip::hash doesn't compute circomlib digests:
+PoseidonChip::hash_circomlib (and poseidon::circomlib_hash outside of the circuit) permutes the circomlib state instead.
+R_P = 57 is odd, which the Pow5Chip of halo2_gadgets doesn't support: the PoseidonChip permutes these specs with the
+PermutationChip of poseidon_permutation, and the specs with an even R_P (such as P128Pow5T5Pasta) with the Pow5Chip.
+
+The number of rounds of P128Pow5T5Pasta is the output of find_FD_round_numbers in calc_round_numbers.py of the Poseidon reference
+implementation (hadeshash) for p = pallas, t = 5, alpha = 5, M = 128 with the security margin: R_F = 8, R_P = 56.
+The binding bound is the interpolation one, R_F + R_P >= 1 + ceil(log_5(2) * 128) + ceil(log_5(t)) = 58 before the margin,
+which only depends on t through ceil(log_5(t)) = 1 for t = 3 to 5. This is why width 3 (P128Pow5T3) and width 5 get the same
+R_P, while the circomlib parameters above come from an earlier version of the script. test_pasta_t5_round_numbers runs a port
+of the script.
+*/
+
+use halo2_gadgets::poseidon::primitives::Spec;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    halo2curves::{bn256::Fr, group::ff::Field, pasta::Fp},
+};
+
+// Poseidon spec for BN254 with width 3 (rate 2, capacity 1) and x^5 S-box.
+#[derive(Debug, Clone, Copy)]
+pub struct P128Pow5T3Bn256;
+
+impl Spec<Fr, 3, 2> for P128Pow5T3Bn256 {
+    fn full_rounds() -> usize {
+        8
+    }
+
+    fn partial_rounds() -> usize {
+        57
+    }
+
+    fn sbox(val: Fr) -> Fr {
+        val.pow_vartime(&[5])
+    }
+
+    fn secure_mds() -> usize {
+        0
+    }
+}
+
+// Poseidon spec for BN254 with width 5 (rate 4, capacity 1) and x^5 S-box.
+#[derive(Debug, Clone, Copy)]
+pub struct P128Pow5T5Bn256;
+
+impl Spec<Fr, 5, 4> for P128Pow5T5Bn256 {
+    fn full_rounds() -> usize {
+        8
+    }
+
+    fn partial_rounds() -> usize {
+        60
+    }
+
+    fn sbox(val: Fr) -> Fr {
+        val.pow_vartime(&[5])
+    }
+
+    fn secure_mds() -> usize {
+        0
+    }
+}
+
+// Poseidon spec for the pasta base field with width 5 (rate 4, capacity 1) and x^5 S-box.
+#[derive(Debug, Clone, Copy)]
+pub struct P128Pow5T5Pasta;
+
+impl Spec<Fp, 5, 4> for P128Pow5T5Pasta {
+    fn full_rounds() -> usize {
+        8
+    }
+
+    fn partial_rounds() -> usize {
+        56
+    }
+
+    fn sbox(val: Fp) -> Fp {
+        val.pow_vartime(&[5])
+    }
+
+    fn secure_mds() -> usize {
+        0
+    }
+}
+
+// Applies the Poseidon permutation defined by the spec S to the state, outside of the circuit.
+// The gadget in halo2_gadgets only exposes the permutation through the hashers, this is needed to compare
+// raw permutation outputs with other implementations.
+pub fn permute<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>(
+    state: &mut [F; T],
+) {
+    let (round_constants, mds, _) = S::constants();
+    let half_full_rounds = S::full_rounds() / 2;
+    let partial_rounds = S::partial_rounds();
+
+    let apply_mds = |state: &mut [F; T]| {
+        let mut new_state = [F::zero(); T];
+        for (new_word, mds_row) in new_state.iter_mut().zip(mds.iter()) {
+            for (m, word) in mds_row.iter().zip(state.iter()) {
+                *new_word += *m * *word;
+            }
+        }
+        *state = new_state;
+    };
+
+    let mut round_constants = round_constants.iter();
+
+    // first half of the full rounds, then the partial rounds, then the second half of the full rounds
+    for round in 0..(2 * half_full_rounds + partial_rounds) {
+        let rc = round_constants.next().unwrap();
+        for (word, c) in state.iter_mut().zip(rc.iter()) {
+            *word += c;
+        }
+        if round < half_full_rounds || round >= half_full_rounds + partial_rounds {
+            for word in state.iter_mut() {
+                *word = S::sbox(*word);
+            }
+        } else {
+            state[0] = S::sbox(state[0]);
+        }
+        apply_mds(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::poseidon::circomlib_hash;
+    use super::{permute, P128Pow5T3Bn256, P128Pow5T5Bn256, P128Pow5T5Pasta};
+    use halo2_gadgets::poseidon::primitives::Spec;
+    use halo2_proofs::{
+        arithmetic::FieldExt,
+        halo2curves::{
+            bn256::Fr,
+            group::ff::{Field, PrimeField},
+            pasta::Fp,
+        },
+    };
+
+    // parse a big-endian hex string such as the ones found in the Poseidon reference parameters
+    fn from_hex<F: FieldExt>(hex: &str) -> F {
+        let hex = hex.trim_start_matches("0x");
+        let mut bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect::<Vec<u8>>();
+        bytes.reverse();
+        let mut repr = F::Repr::default();
+        repr.as_mut()[..bytes.len()].copy_from_slice(&bytes);
+        F::from_repr(repr).unwrap()
+    }
+
+    #[test]
+    fn test_bn256_t3_constants() {
+        let (round_constants, mds, _) = <P128Pow5T3Bn256 as Spec<Fr, 3, 2>>::constants();
+        assert_eq!(round_constants.len(), 8 + 57);
+
+        // values from the reference parameters poseidon_params_n254_t3_alpha5_M128
+        assert_eq!(
+            round_constants[0][0],
+            from_hex("0x0ee9a592ba9a9518d05986d656f40c2114c4993c11bb29938d21d47304cd8e6e")
+        );
+        assert_eq!(
+            round_constants[0][1],
+            from_hex("0x00f1445235f2148c5986587169fc1bcd887b08d4d00868df5696fff40956e864")
+        );
+        assert_eq!(
+            mds[0][0],
+            from_hex("0x109b7f411ba0e4c9b2b70caf5c36a7b194be7c11ad24378bfedb68592ba8118b")
+        );
+    }
+
+    #[test]
+    fn test_bn256_t3_circomlib_vector() {
+        // circomlib poseidon([1, 2]) is the first word of the permutation of [0, 1, 2]
+        let mut state = [Fr::zero(), Fr::from(1), Fr::from(2)];
+        permute::<_, P128Pow5T3Bn256, 3, 2>(&mut state);
+        let expected = Fr::from_str_vartime(
+            "7853200120776062878684798364095072458815029376092732009249414926327459813530",
+        )
+        .unwrap();
+        assert_eq!(state[0], expected);
+        assert_eq!(
+            circomlib_hash::<_, P128Pow5T3Bn256, 3, 2>(&[Fr::from(1), Fr::from(2)]),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_bn256_t5_circomlib_vector() {
+        // circomlib poseidon([1, 2, 3, 4]) is the first word of the permutation of [0, 1, 2, 3, 4]
+        let mut state = [Fr::zero(), Fr::from(1), Fr::from(2), Fr::from(3), Fr::from(4)];
+        permute::<_, P128Pow5T5Bn256, 5, 4>(&mut state);
+        let expected = Fr::from_str_vartime(
+            "18821383157269793795438455681495246036402687001665670618754263018637548127333",
+        )
+        .unwrap();
+        assert_eq!(state[0], expected);
+        assert_eq!(
+            circomlib_hash::<_, P128Pow5T5Bn256, 5, 4>(&[
+                Fr::from(1),
+                Fr::from(2),
+                Fr::from(3),
+                Fr::from(4)
+            ]),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_permute_matches_gadget_hash() {
+        use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, Domain};
+
+        // the sponge of halo2_gadgets puts the message in the rate and the domain tag in the capacity
+        let message = [Fr::from(1), Fr::from(2)];
+        let mut state = [
+            message[0],
+            message[1],
+            <ConstantLength<2> as Domain<Fr, 2>>::initial_capacity_element(),
+        ];
+        permute::<_, P128Pow5T3Bn256, 3, 2>(&mut state);
+
+        let digest =
+            poseidon::Hash::<_, P128Pow5T3Bn256, ConstantLength<2>, 3, 2>::init().hash(message);
+        assert_eq!(state[0], digest);
+
+        // circomlib puts a zero capacity before the message instead, so the digest is not circomlib poseidon([1, 2]),
+        // which is computed by circomlib_hash
+        assert_ne!(digest, circomlib_hash::<_, P128Pow5T3Bn256, 3, 2>(&message));
+    }
+
+    #[test]
+    fn test_pasta_t5_round_numbers() {
+        use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+
+        // p is slightly larger than 2^254, the script only uses log2(p) through floor(log2(p) - 2) and min(M, log2(p))
+        let log_p = (Fp::NUM_BITS - 1) as f64;
+        assert_eq!(round_numbers(log_p, 5), (8, 56));
+        assert_eq!(
+            round_numbers(log_p, 5),
+            (P128Pow5T5Pasta::full_rounds(), P128Pow5T5Pasta::partial_rounds())
+        );
+
+        // the port agrees with the parameters of P128Pow5T3 from halo2_gadgets, computed with the same script
+        assert_eq!(
+            round_numbers(log_p, 3),
+            (
+                <P128Pow5T3 as Spec<Fp, 3, 2>>::full_rounds(),
+                <P128Pow5T3 as Spec<Fp, 3, 2>>::partial_rounds()
+            )
+        );
+    }
+
+    // Port of find_FD_round_numbers from calc_round_numbers.py (Poseidon reference implementation) for x^5 and 128 bits
+    // of security, with the security margin of 2 full rounds and 7.5% partial rounds. Returns (R_F, R_P)
+    fn round_numbers(log_p: f64, t: usize) -> (usize, usize) {
+        let (alpha, m, t_f) = (5f64, 128f64, t as f64);
+        let n = log_p.ceil();
+        let log_alpha = |x: f64| x.ln() / alpha.ln();
+
+        // sat_inequiv_alpha: statistical, interpolation and the three Groebner basis bounds
+        let is_secure = |r_f: f64, r_p: f64| {
+            let bounds = [
+                if m <= (log_p - (alpha - 1.0) / 2.0).floor() * (t_f + 1.0) {
+                    6.0
+                } else {
+                    10.0
+                },
+                1.0 + (log_alpha(2.0) * m.min(n)).ceil() + log_alpha(t_f).ceil() - r_p,
+                log_alpha(2.0) * m.min(log_p) - r_p,
+                t_f - 1.0 + log_alpha(2.0) * (m / (t_f + 1.0)).min(log_p / 2.0) - r_p,
+                (t_f - 2.0 + m / (2.0 * alpha.log2()) - r_p) / (t_f - 1.0),
+            ];
+            bounds.iter().all(|bound| r_f >= bound.ceil())
+        };
+
+        // the cheapest secure pair with the margin, the cost being the number of S-boxes, ties broken by fewer R_F
+        let (_, r_f, r_p) = (1..500usize)
+            .flat_map(|r_p| (4..100usize).step_by(2).map(move |r_f| (r_f, r_p)))
+            .filter(|(r_f, r_p)| is_secure(*r_f as f64, *r_p as f64))
+            .map(|(r_f, r_p)| {
+                let (r_f, r_p) = (r_f + 2, (r_p as f64 * 1.075).ceil() as usize);
+                (r_f * t + r_p, r_f, r_p)
+            })
+            .min()
+            .unwrap();
+        (r_f, r_p)
+    }
+
+    #[test]
+    fn test_pasta_t5_constants() {
+        let (round_constants, mds, mds_inv) = <P128Pow5T5Pasta as Spec<Fp, 5, 4>>::constants();
+        assert_eq!(round_constants.len(), 8 + 56);
+
+        // the generated matrix is invertible
+        for i in 0..5 {
+            for j in 0..5 {
+                let entry = (0..5).fold(Fp::zero(), |acc, k| acc + mds[i][k] * mds_inv[k][j]);
+                assert_eq!(entry, if i == j { Fp::one() } else { Fp::zero() });
+            }
+        }
+    }
+}
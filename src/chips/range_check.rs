@@ -0,0 +1,127 @@
+/*
+Range check of a cell to N_BYTES * 8 bits using a lookup table of the 256 possible bytes.
+
+The value is decomposed into a running sum z_0, z_1, ..., z_N_BYTES in a single column, where z_0 is the value
+and z_{i+1} = (z_i - byte_i) / 256. Each byte_i = z_i - 256 * z_{i+1} is looked up in the byte table and the last
+element of the running sum must be 0. This only holds if the value is smaller than 2^(8 * N_BYTES).
+
+| z        | lookup_selector | zero_selector |
+| -------- | --------------- | ------------- |
+| value    | 1               | 0             |
+| z_1      | 1               | 0             |
+| ...      | 1               | 0             |
+| z_N      | 0               | 1             |
+*/
+
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+
+#[derive(Debug, Clone)]
+pub struct RangeCheckConfig {
+    pub z: Column<Advice>,
+    pub lookup_selector: Selector,
+    pub zero_selector: Selector,
+    pub range_table: TableColumn,
+}
+#[derive(Debug, Clone)]
+pub struct RangeCheckChip<F: FieldExt, const N_BYTES: usize> {
+    config: RangeCheckConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt, const N_BYTES: usize> RangeCheckChip<F, N_BYTES> {
+    pub fn construct(config: RangeCheckConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(meta: &mut ConstraintSystem<F>, z: Column<Advice>) -> RangeCheckConfig {
+        // selectors used inside a lookup argument must be complex selectors
+        let lookup_selector = meta.complex_selector();
+        let zero_selector = meta.selector();
+        let range_table = meta.lookup_table_column();
+
+        // the value to be range checked is copied into the running sum column
+        meta.enable_equality(z);
+
+        // Enforces that z_cur - 256 * z_next is a byte when the lookup selector is enabled
+        // When the selector is disabled the looked up expression is 0, which is part of the table
+        meta.lookup("byte range check", |meta| {
+            let s = meta.query_selector(lookup_selector);
+            let z_cur = meta.query_advice(z, Rotation::cur());
+            let z_next = meta.query_advice(z, Rotation::next());
+            let byte = z_cur - z_next * Expression::Constant(F::from(256));
+            vec![(s * byte, range_table)]
+        });
+
+        // Enforces that the running sum ends at 0
+        // s * z = 0
+        meta.create_gate("running sum end", |meta| {
+            let s = meta.query_selector(zero_selector);
+            let z = meta.query_advice(z, Rotation::cur());
+            vec![s * z]
+        });
+
+        RangeCheckConfig {
+            z,
+            lookup_selector,
+            zero_selector,
+            range_table,
+        }
+    }
+
+    // Loads the byte table, it must be called once per circuit
+    pub fn load_table(&self, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "byte table",
+            |mut table| {
+                for byte in 0..256 {
+                    table.assign_cell(
+                        || "assign byte",
+                        self.config.range_table,
+                        byte,
+                        || Value::known(F::from(byte as u64)),
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    // Enforces that the value of the cell is smaller than 2^(8 * N_BYTES)
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "range check",
+            |mut region| {
+                cell.copy_advice(
+                    || "copy value to be range checked",
+                    &mut region,
+                    self.config.z,
+                    0,
+                )?;
+
+                let mut z = cell.value().map(|x| x.to_owned());
+                let inv_256 = F::from(256).invert().unwrap();
+                for row in 0..N_BYTES {
+                    self.config.lookup_selector.enable(&mut region, row)?;
+
+                    // z_{i+1} = (z_i - byte_i) / 256
+                    z = z.map(|z| {
+                        let byte = F::from((z.get_lower_128() & 0xff) as u64);
+                        (z - byte) * inv_256
+                    });
+                    region.assign_advice(|| "assign running sum", self.config.z, row + 1, || z)?;
+                }
+
+                self.config.zero_selector.enable(&mut region, N_BYTES)?;
+                Ok(())
+            },
+        )
+    }
+}
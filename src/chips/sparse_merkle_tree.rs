@@ -0,0 +1,125 @@
+use super::merkle_index::{MerkleTreeIndexChip, MerkleTreeIndexConfig};
+use super::poseidon::PoseidonChip;
+use halo2_gadgets::poseidon::primitives::Spec;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
+use std::marker::PhantomData;
+
+// Sparse Merkle tree of depth DEPTH, the position of a leaf is given by the DEPTH bits of its key.
+// A key with a value is stored in the leaf H(key, value) and an empty leaf is zero, therefore proving that the leaf
+// at the position of a key is zero proves that the key is not in the tree
+#[derive(Debug, Clone)]
+pub struct SparseMerkleTreeConfig<F: FieldExt> {
+    pub merkle_tree_index_config: MerkleTreeIndexConfig<F>,
+}
+#[derive(Debug, Clone)]
+pub struct SparseMerkleTreeChip<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> {
+    config: SparseMerkleTreeConfig<F>,
+    _marker: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> SparseMerkleTreeChip<F, S, DEPTH> {
+    pub fn construct(config: SparseMerkleTreeConfig<F>) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> SparseMerkleTreeConfig<F> {
+        // The key is the index of the leaf in the MerkleTreeIndexChip
+        let merkle_tree_index_config =
+            MerkleTreeIndexChip::<F, S, DEPTH>::configure(meta, advice, instance);
+
+        SparseMerkleTreeConfig {
+            merkle_tree_index_config,
+        }
+    }
+
+    // Assigns the key and returns its cell together with the cells of its DEPTH bits, least significant bit first
+    // The constraints can only be satisfied if the key is smaller than 2^DEPTH
+    pub fn assign_key(
+        &self,
+        layouter: impl Layouter<F>,
+        key: Value<F>,
+    ) -> Result<(AssignedCell<F, F>, [AssignedCell<F, F>; DEPTH]), Error> {
+        self.index_chip().assign_index(layouter, key)
+    }
+
+    // Returns the cell containing the leaf of a key that is in the tree, H(key, value)
+    pub fn assign_leaf(
+        &self,
+        mut layouter: impl Layouter<F>,
+        key_cell: &AssignedCell<F, F>,
+        value: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let merkle_tree_v3_config = &self.config.merkle_tree_index_config.merkle_tree_v3_config;
+        let value_cell = layouter.assign_region(
+            || "assign value",
+            |mut region| {
+                region.assign_advice(
+                    || "assign value",
+                    merkle_tree_v3_config.advice[1],
+                    0,
+                    || value,
+                )
+            },
+        )?;
+
+        let poseidon_chip =
+            PoseidonChip::<F, S, 3, 2, 2>::construct(merkle_tree_v3_config.hash_config.clone());
+        poseidon_chip.hash(
+            layouter.namespace(|| "hash leaf"),
+            &[key_cell.clone(), value_cell],
+        )
+    }
+
+    // Returns the cell containing an empty leaf, which is fixed to zero
+    pub fn assign_empty_leaf(
+        &self,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let merkle_tree_v3_config = &self.config.merkle_tree_index_config.merkle_tree_v3_config;
+        layouter.assign_region(
+            || "assign empty leaf",
+            |mut region| {
+                region.assign_advice_from_constant(
+                    || "assign empty leaf",
+                    merkle_tree_v3_config.advice[0],
+                    0,
+                    F::zero(),
+                )
+            },
+        )
+    }
+
+    // Returns the cell containing the root of the tree, starting from the leaf at the position given by the bits of the key
+    // The siblings of the empty subtrees are witnesses like any other path element
+    pub fn compute_root(
+        &self,
+        layouter: impl Layouter<F>,
+        leaf_cell: &AssignedCell<F, F>,
+        key_bits: &[AssignedCell<F, F>; DEPTH],
+        path_elements: &[Value<F>; DEPTH],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.index_chip()
+            .merkle_prove(layouter, leaf_cell, key_bits, path_elements)
+    }
+
+    // Enforce permutation check between input cell and instance column at row passed as input
+    pub fn expose_public(
+        &self,
+        layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        self.index_chip().expose_public(layouter, cell, row)
+    }
+
+    fn index_chip(&self) -> MerkleTreeIndexChip<F, S, DEPTH> {
+        MerkleTreeIndexChip::construct(self.config.merkle_tree_index_config.clone())
+    }
+}
@@ -2,7 +2,12 @@ pub mod hash_v1;
 pub mod hash_v2;
 pub mod inclusion_check;
 pub mod inclusion_check_v2;
-pub mod merkle_v1;
+pub mod merkle_public_positions;
+pub mod merkle_quaternary;
+pub mod merkle_update;
 pub mod merkle_v2;
 pub mod poseidon;
 pub mod merkle_v3;
+pub mod merkle_sum_tree;
+pub mod merkle_sum_tree_multi_asset;
+pub mod sparse_merkle_tree;
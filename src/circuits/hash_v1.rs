@@ -1,6 +1,7 @@
 use super::super::chips::hash_v1::{Hash1Chip, Hash1Config};
 
-use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
+use crate::experiments::ExperimentVisitor;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
 
 #[derive(Default)]
 struct Hash1Circuit<F> {
@@ -35,10 +36,23 @@ impl<F: FieldExt> Circuit<F> for Hash1Circuit<F> {
     }
 }
 
+// Instance used by the experiments registry
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let circuit = Hash1Circuit {
+        a: Value::known(Fp::from(2)),
+    };
+    visitor.visit("hash-1", &circuit, &[vec![Fp::from(4)]]);
+}
+
 #[cfg(test)]
 mod tests {
     use super::Hash1Circuit;
-    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+    use crate::proving::{ipa, kzg};
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+    };
     #[test]
     fn test_hash_1() {
         let k = 4;
@@ -52,4 +66,26 @@ mod tests {
         let prover = MockProver::run(k, &circuit, vec![public_inputs]).unwrap();
         assert!(prover.verify().is_err());
     }
+
+    #[test]
+    fn test_hash_1_real_prover() {
+        let circuit = Hash1Circuit {
+            a: Value::known(Fp::from(2)),
+        };
+
+        let public_inputs = vec![vec![Fp::from(4)]];
+        let wrong_public_inputs = vec![vec![Fp::from(8)]];
+        ipa::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
+
+    #[test]
+    fn test_hash_1_kzg() {
+        let circuit = Hash1Circuit {
+            a: Value::known(Fr::from(2)),
+        };
+        let public_inputs = vec![vec![Fr::from(4)]];
+
+        let wrong_public_inputs = vec![vec![Fr::from(8)]];
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
 }
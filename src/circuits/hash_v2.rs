@@ -1,6 +1,7 @@
 use super::super::chips::hash_v2::{Hash2Chip, Hash2Config};
 
-use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
+use crate::experiments::ExperimentVisitor;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
 
 #[derive(Default)]
 struct Hash2Circuit<F> {
@@ -39,10 +40,24 @@ impl<F: FieldExt> Circuit<F> for Hash2Circuit<F> {
     }
 }
 
+// Instance used by the experiments registry
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let circuit = Hash2Circuit {
+        a: Value::known(Fp::from(2)),
+        b: Value::known(Fp::from(7)),
+    };
+    visitor.visit("hash-2", &circuit, &[vec![Fp::from(9)]]);
+}
+
 #[cfg(test)]
 mod tests {
     use super::Hash2Circuit;
-    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+    use crate::proving::{ipa, kzg};
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+    };
 
     #[test]
     fn test_hash_2() {
@@ -61,4 +76,28 @@ mod tests {
         let prover = MockProver::run(k, &circuit, vec![public_inputs]).unwrap();
         assert!(prover.verify().is_err());
     }
+
+    #[test]
+    fn test_hash_2_real_prover() {
+        let circuit = Hash2Circuit {
+            a: Value::known(Fp::from(2)),
+            b: Value::known(Fp::from(7)),
+        };
+
+        let public_inputs = vec![vec![Fp::from(9)]];
+        let wrong_public_inputs = vec![vec![Fp::from(8)]];
+        ipa::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
+
+    #[test]
+    fn test_hash_2_kzg() {
+        let circuit = Hash2Circuit {
+            a: Value::known(Fr::from(2)),
+            b: Value::known(Fr::from(7)),
+        };
+        let public_inputs = vec![vec![Fr::from(9)]];
+
+        let wrong_public_inputs = vec![vec![Fr::from(8)]];
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
 }
@@ -1,22 +1,26 @@
 use super::super::chips::inclusion_check::{InclusionCheckChip, InclusionCheckConfig};
 
-use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
-
-#[derive(Default)]
-
-// define circuit struct using array of usernames and balances
-struct MyCircuit<F> {
-    pub usernames: [Value<F>; 10],
-    pub balances: [Value<F>; 10],
-    pub inclusion_index: u8,
+use crate::experiments::ExperimentVisitor;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
+
+// define circuit struct using arrays of N usernames and balances
+struct MyCircuit<F, const N: usize> {
+    pub usernames: [Value<F>; N],
+    pub balances: [Value<F>; N],
+    pub inclusion_index: usize,
 }
 
-impl<F: FieldExt> Circuit<F> for MyCircuit<F> {
+impl<F: FieldExt, const N: usize> Circuit<F> for MyCircuit<F, N> {
     type Config = InclusionCheckConfig;
     type FloorPlanner = SimpleFloorPlanner;
 
+    // The inclusion index determines which row is exposed, so it is part of the shape of the circuit
     fn without_witnesses(&self) -> Self {
-        Self::default()
+        Self {
+            usernames: [Value::unknown(); N],
+            balances: [Value::unknown(); N],
+            inclusion_index: self.inclusion_index,
+        }
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
@@ -34,12 +38,16 @@ impl<F: FieldExt> Circuit<F> for MyCircuit<F> {
     ) -> Result<(), Error> {
         // We create a new instance of chip using the config passed as input
         let chip = InclusionCheckChip::<F>::construct(config);
+        // an inclusion index out of the table would leave the instance column unconstrained
+        if self.inclusion_index >= N {
+            return Err(Error::Synthesis);
+        }
 
         // loop over the usernames array and assign the rows
         for _i in 0..self.usernames.len() {
             // if row is equal to the inclusion index, assign the value using the assign_inclusion_check_row function
             // else assign the value using the assign_generic_row function
-            if (_i as u8) == self.inclusion_index {
+            if _i == self.inclusion_index {
                 // extract username and balances cell from here!
                 let (username_cell, balance_cell) = chip.assign_inclusion_check_row(
                     layouter.namespace(|| "inclusion row"),
@@ -65,11 +73,27 @@ impl<F: FieldExt> Circuit<F> for MyCircuit<F> {
     }
 }
 
+// Instance used by the experiments registry: the row 7 of a table of 10 users is exposed
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let circuit = MyCircuit::<Fp, 10> {
+        usernames: core::array::from_fn(|i| Value::known(Fp::from(i as u64))),
+        balances: core::array::from_fn(|i| Value::known(Fp::from(i as u64 * 2))),
+        inclusion_index: 7,
+    };
+    visitor.visit("inclusion-check-1", &circuit, &[vec![Fp::from(7), Fp::from(14)]]);
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::MyCircuit;
-    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+    use crate::proving::{ipa, kzg};
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+        plonk::Error,
+    };
     #[test]
     fn test_inclusion_check_1() {
         let k = 4;
@@ -97,7 +121,7 @@ mod tests {
         // 8        | 16
         // 9        | 18
 
-        let circuit = MyCircuit::<Fp> {
+        let circuit = MyCircuit::<Fp, 10> {
             usernames,
             balances,
             inclusion_index: 7,
@@ -118,31 +142,66 @@ mod tests {
         let prover = MockProver::run(k, &circuit, vec![public_input_invalid2]).unwrap();
         assert!(prover.verify().is_err());
     }
-}
 
-#[cfg(feature = "dev-graph")]
-#[test]
-fn print_inclusion_check() {
-    use halo2_proofs::halo2curves::pasta::Fp;
-    use plotters::prelude::*;
-
-    let root =
-        BitMapBackend::new("prints/inclusion-check-1-layout.png", (1024, 3096)).into_drawing_area();
-    root.fill(&WHITE).unwrap();
-    let root = root
-        .titled("Inclusion Check 1 Layout", ("sans-serif", 60))
-        .unwrap();
-
-    let mut usernames: [Value<Fp>; 10] = [Value::known(Fp::from(0)); 10];
-    let mut balances: [Value<Fp>; 10] = [Value::known(Fp::from(0)); 10];
-
-    let circuit = MyCircuit::<Fp> {
-        usernames,
-        balances,
-        inclusion_index: 2,
-    };
+    #[test]
+    fn test_inclusion_check_1_large_table() {
+        let k = 11;
+
+        // the table has more rows than a u8 can index, the entry 300 is exposed
+        let circuit = MyCircuit::<Fp, 1000> {
+            usernames: core::array::from_fn(|i| Value::known(Fp::from(i as u64))),
+            balances: core::array::from_fn(|i| Value::known(Fp::from(i as u64 * 2))),
+            inclusion_index: 300,
+        };
+
+        let public_input_valid = vec![Fp::from(300), Fp::from(600)];
+        let prover = MockProver::run(k, &circuit, vec![public_input_valid]).unwrap();
+        prover.assert_satisfied();
 
-    halo2_proofs::dev::CircuitLayout::default()
-        .render(3, &circuit, &root)
-        .unwrap();
+        // the entry at the inclusion index modulo 256 is not the one exposed
+        let public_input_invalid = vec![Fp::from(44), Fp::from(88)];
+        let prover = MockProver::run(k, &circuit, vec![public_input_invalid]).unwrap();
+        assert!(prover.verify().is_err());
+
+        let public_input_invalid2 = vec![Fp::from(999), Fp::from(1998)];
+        let prover = MockProver::run(k, &circuit, vec![public_input_invalid2]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_inclusion_check_1_index_out_of_range() {
+        let circuit = MyCircuit::<Fp, 10> {
+            usernames: core::array::from_fn(|i| Value::known(Fp::from(i as u64))),
+            balances: core::array::from_fn(|i| Value::known(Fp::from(i as u64 * 2))),
+            inclusion_index: 10,
+        };
+        let result = MockProver::run(4, &circuit, vec![vec![Fp::from(9), Fp::from(18)]]);
+        assert!(matches!(result, Err(Error::Synthesis)));
+    }
+
+    #[test]
+    fn test_inclusion_check_1_real_prover() {
+        let circuit = MyCircuit::<Fp, 10> {
+            usernames: core::array::from_fn(|i| Value::known(Fp::from(i as u64))),
+            balances: core::array::from_fn(|i| Value::known(Fp::from(i as u64 * 2))),
+            inclusion_index: 7,
+        };
+
+        let public_inputs = vec![vec![Fp::from(7), Fp::from(14)]];
+        let wrong_public_inputs = vec![vec![Fp::from(8), Fp::from(16)]];
+        ipa::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
+
+    #[test]
+    fn test_inclusion_check_1_kzg() {
+        let circuit = MyCircuit::<Fr, 10> {
+            usernames: core::array::from_fn(|i| Value::known(Fr::from(i as u64))),
+            balances: core::array::from_fn(|i| Value::known(Fr::from(i as u64 * 2))),
+            inclusion_index: 7,
+        };
+        let public_inputs = vec![vec![Fr::from(7), Fr::from(14)]];
+
+        let wrong_public_inputs = vec![vec![Fr::from(8), Fr::from(16)]];
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
 }
@@ -1,22 +1,28 @@
 use super::super::chips::inclusion_check_v2::{InclusionCheckV2Chip, InclusionCheckV2Config};
 
-use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
-
-#[derive(Default)]
-// define circuit struct using array of usernames and balances
-struct MyCircuit<F> {
-    pub usernames: [Value<F>; 10],
-    pub balances: [Value<F>; 10],
-    pub inclusion_index: u8,
+use crate::experiments::ExperimentVisitor;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
+
+// define circuit struct using arrays of N usernames and balances
+struct MyCircuit<F, const N: usize> {
+    pub usernames: [Value<F>; N],
+    pub balances: [Value<F>; N],
+    pub inclusion_index: usize,
     pub zero_val: Value<F>,
 }
 
-impl<F: FieldExt> Circuit<F> for MyCircuit<F> {
+impl<F: FieldExt, const N: usize> Circuit<F> for MyCircuit<F, N> {
     type Config = InclusionCheckV2Config;
     type FloorPlanner = SimpleFloorPlanner;
 
+    // The inclusion index determines where the selector is enabled, so it is part of the shape of the circuit
     fn without_witnesses(&self) -> Self {
-        Self::default()
+        Self {
+            usernames: [Value::unknown(); N],
+            balances: [Value::unknown(); N],
+            inclusion_index: self.inclusion_index,
+            zero_val: Value::unknown(),
+        }
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
@@ -48,8 +54,8 @@ impl<F: FieldExt> Circuit<F> for MyCircuit<F> {
 
         let (user_acc_last_row_cell, balance_acc_last_row_cell) = chip.assign_rows(
             layouter.namespace(|| "init table"),
-            self.usernames,
-            self.balances,
+            &self.usernames,
+            &self.balances,
             self.zero_val,
             self.inclusion_index,
         )?;
@@ -69,11 +75,28 @@ impl<F: FieldExt> Circuit<F> for MyCircuit<F> {
     }
 }
 
+// Instance used by the experiments registry: the row 7 of a table of 10 users is accumulated
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let circuit = MyCircuit::<Fp, 10> {
+        usernames: core::array::from_fn(|i| Value::known(Fp::from(i as u64))),
+        balances: core::array::from_fn(|i| Value::known(Fp::from(i as u64 * 2))),
+        inclusion_index: 7,
+        zero_val: Value::known(Fp::from(0)),
+    };
+    visitor.visit("inclusion-check-2", &circuit, &[vec![Fp::from(7), Fp::from(14)]]);
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::MyCircuit;
-    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+    use crate::proving::{ipa, kzg, min_k};
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+        plonk::Error,
+    };
 
     #[test]
     fn test_inclusion_check_2() {
@@ -102,7 +125,7 @@ mod tests {
         // 8        | 16
         // 9        | 18
 
-        let circuit = MyCircuit::<Fp> {
+        let circuit = MyCircuit::<Fp, 10> {
             usernames,
             balances,
             inclusion_index: 7,
@@ -124,4 +147,91 @@ mod tests {
         let prover = MockProver::run(k, &circuit, vec![public_input_invalid2]).unwrap();
         assert!(prover.verify().is_err());
     }
+
+    #[test]
+    fn test_inclusion_check_2_large_table() {
+        let k = 11;
+
+        // the table has more rows than a u8 can index, the entry 300 is accumulated
+        let circuit = MyCircuit::<Fp, 1000> {
+            usernames: core::array::from_fn(|i| Value::known(Fp::from(i as u64))),
+            balances: core::array::from_fn(|i| Value::known(Fp::from(i as u64 * 2))),
+            inclusion_index: 300,
+            zero_val: Value::known(Fp::zero()),
+        };
+
+        let public_input_valid = vec![Fp::from(300), Fp::from(600)];
+        let prover = MockProver::run(k, &circuit, vec![public_input_valid]).unwrap();
+        prover.assert_satisfied();
+
+        // the entry at the inclusion index modulo 256 is not the one accumulated
+        let public_input_invalid = vec![Fp::from(44), Fp::from(88)];
+        let prover = MockProver::run(k, &circuit, vec![public_input_invalid]).unwrap();
+        assert!(prover.verify().is_err());
+
+        let public_input_invalid2 = vec![Fp::from(999), Fp::from(1998)];
+        let prover = MockProver::run(k, &circuit, vec![public_input_invalid2]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_inclusion_check_2_thousands_of_rows() {
+        // the table of 3000 users and the initial row of the accumulators need 2^12 rows
+        let circuit = MyCircuit::<Fp, 3000> {
+            usernames: core::array::from_fn(|i| Value::known(Fp::from(i as u64))),
+            balances: core::array::from_fn(|i| Value::known(Fp::from(i as u64 * 2))),
+            inclusion_index: 2500,
+            zero_val: Value::known(Fp::zero()),
+        };
+        let public_inputs = vec![vec![Fp::from(2500), Fp::from(5000)]];
+        let k = min_k(&circuit, &public_inputs).unwrap();
+        assert_eq!(k, 12);
+
+        let prover = MockProver::run(k, &circuit, public_inputs).unwrap();
+        prover.assert_satisfied();
+
+        let wrong_public_inputs = vec![vec![Fp::from(2501), Fp::from(5002)]];
+        let prover = MockProver::run(k, &circuit, wrong_public_inputs).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_inclusion_check_2_index_out_of_range() {
+        let circuit = MyCircuit::<Fp, 10> {
+            usernames: core::array::from_fn(|i| Value::known(Fp::from(i as u64))),
+            balances: core::array::from_fn(|i| Value::known(Fp::from(i as u64 * 2))),
+            inclusion_index: 10,
+            zero_val: Value::known(Fp::zero()),
+        };
+        let result = MockProver::run(5, &circuit, vec![vec![Fp::from(9), Fp::from(18)]]);
+        assert!(matches!(result, Err(Error::Synthesis)));
+    }
+
+    #[test]
+    fn test_inclusion_check_2_real_prover() {
+        let circuit = MyCircuit::<Fp, 10> {
+            usernames: core::array::from_fn(|i| Value::known(Fp::from(i as u64))),
+            balances: core::array::from_fn(|i| Value::known(Fp::from(i as u64 * 2))),
+            inclusion_index: 7,
+            zero_val: Value::known(Fp::zero()),
+        };
+
+        let public_inputs = vec![vec![Fp::from(7), Fp::from(14)]];
+        let wrong_public_inputs = vec![vec![Fp::from(8), Fp::from(16)]];
+        ipa::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
+
+    #[test]
+    fn test_inclusion_check_2_kzg() {
+        let circuit = MyCircuit::<Fr, 10> {
+            usernames: core::array::from_fn(|i| Value::known(Fr::from(i as u64))),
+            balances: core::array::from_fn(|i| Value::known(Fr::from(i as u64 * 2))),
+            inclusion_index: 7,
+            zero_val: Value::known(Fr::from(0)),
+        };
+        let public_inputs = vec![vec![Fr::from(7), Fr::from(14)]];
+
+        let wrong_public_inputs = vec![vec![Fr::from(8), Fr::from(16)]];
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
 }
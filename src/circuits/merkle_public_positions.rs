@@ -0,0 +1,330 @@
+use super::super::chips::merkle_public_positions::{
+    MerkleTreePublicPositionsChip, MerkleTreePublicPositionsConfig,
+};
+use super::super::chips::poseidon::{PoseidonChip, PoseidonConfig};
+use crate::experiments::ExperimentVisitor;
+use crate::merkle_tree::{MerkleProof, MerkleTree};
+use halo2_gadgets::poseidon::primitives::{P128Pow5T3, Spec};
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
+use std::marker::PhantomData;
+
+// Proves that K leaves are in the same tree of depth DEPTH, hashed with Poseidon as in the MerkleTreeV3Circuit.
+// The positions of the leaves are part of the shape of the circuit, like the depth, so they are public. The public
+// inputs are the root (row 0) and the leaves (rows 1 to K), the positions aren't in the instance column: the verifying
+// key is bound to one set of positions, so every set of positions needs its own key generation
+// The circuit doesn't hide the positions: anyone holding the verifying key learns which leaves of the tree are
+// proven. When the positions must stay private, each leaf has to be proven with the MerkleTreeV3Circuit instead
+pub struct MerkleTreePublicPositionsCircuit<
+    F: FieldExt,
+    S: Spec<F, 3, 2>,
+    const DEPTH: usize,
+    const K: usize,
+> {
+    pub public_leaf_indices: [usize; K],
+    pub leaves: [Value<F>; K],
+    pub path_elements: [[Value<F>; DEPTH]; K],
+    _spec: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize, const K: usize>
+    MerkleTreePublicPositionsCircuit<F, S, DEPTH, K>
+{
+    pub fn new(
+        public_leaf_indices: [usize; K],
+        leaves: [Value<F>; K],
+        path_elements: [[Value<F>; DEPTH]; K],
+    ) -> Self {
+        Self {
+            public_leaf_indices,
+            leaves,
+            path_elements,
+            _spec: PhantomData,
+        }
+    }
+
+    // Builds the circuit out of K proofs generated by the same merkle_tree::MerkleTree, of depth DEPTH
+    pub fn from_proofs(proofs: &[MerkleProof<F>]) -> Self {
+        assert_eq!(
+            proofs.len(),
+            K,
+            "{} proofs provided, the circuit expects {}",
+            proofs.len(),
+            K
+        );
+        for proof in proofs {
+            assert_eq!(
+                proof.path_elements.len(),
+                DEPTH,
+                "the proof has {} levels, the circuit expects {}",
+                proof.path_elements.len(),
+                DEPTH
+            );
+        }
+
+        // the leaf index is the integer whose bits are the path indices
+        let public_leaf_indices = std::array::from_fn(|i| {
+            proofs[i]
+                .path_indices
+                .iter()
+                .enumerate()
+                .filter(|(_, bit)| **bit != F::zero())
+                .map(|(level, _)| 1 << level)
+                .sum()
+        });
+        Self::new(
+            public_leaf_indices,
+            std::array::from_fn(|i| Value::known(proofs[i].leaf)),
+            std::array::from_fn(|i| {
+                std::array::from_fn(|level| Value::known(proofs[i].path_elements[level]))
+            }),
+        )
+    }
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize, const K: usize> Circuit<F>
+    for MerkleTreePublicPositionsCircuit<F, S, DEPTH, K>
+{
+    type Config = MerkleTreePublicPositionsConfig<PoseidonConfig<F, 3, 2>>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    // the positions of the leaves are kept, since they determine the shape of the circuit
+    fn without_witnesses(&self) -> Self {
+        Self::new(
+            self.public_leaf_indices,
+            [Value::unknown(); K],
+            [[Value::unknown(); DEPTH]; K],
+        )
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+
+        MerkleTreePublicPositionsChip::<F, PoseidonChip<F, S, 3, 2, 2>>::configure(
+            meta,
+            [col_a, col_b, col_c],
+            instance,
+        )
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip =
+            MerkleTreePublicPositionsChip::<F, PoseidonChip<F, S, 3, 2, 2>>::construct(config);
+
+        let leaf_cells =
+            chip.assign_leaves(layouter.namespace(|| "assign leaves"), &self.leaves)?;
+        for (i, leaf_cell) in leaf_cells.iter().enumerate() {
+            chip.expose_public(layouter.namespace(|| "public leaf"), leaf_cell, 1 + i)?;
+        }
+
+        let path_elements = self
+            .path_elements
+            .iter()
+            .map(|path| path.as_slice())
+            .collect::<Vec<_>>();
+        let root = chip.merkle_prove_batch(
+            layouter.namespace(|| "merkle prove batch"),
+            &self.public_leaf_indices,
+            &leaf_cells,
+            &path_elements,
+        )?;
+        chip.expose_public(layouter.namespace(|| "public root"), &root, 0)?;
+        Ok(())
+    }
+}
+
+// Instance used by the experiments registry, 4 leaves of a tree of depth 5
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let leaves = (0..32u64)
+        .map(|x| Fp::from(x * 3 + 99))
+        .collect::<Vec<Fp>>();
+    let tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5);
+    let proofs = [2, 3, 13, 30].map(|index| tree.proof(index));
+
+    let circuit = MerkleTreePublicPositionsCircuit::<Fp, P128Pow5T3, 5, 4>::from_proofs(&proofs);
+    let mut public_inputs = vec![tree.root()];
+    public_inputs.extend(proofs.iter().map(|proof| proof.leaf));
+    visitor.visit("merkle-tree-public-positions", &circuit, &[public_inputs]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MerkleTreePublicPositionsCircuit;
+    use crate::chips::poseidon_spec::P128Pow5T3Bn256;
+    use crate::cost::circuit_cost;
+    use crate::merkle_tree::{MerkleProof, MerkleTree};
+    use crate::proving::{kzg, min_k};
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+        plonk::Error,
+    };
+
+    fn tree() -> MerkleTree<Fp, P128Pow5T3> {
+        let leaves = (0..32u64)
+            .map(|x| Fp::from(x * 3 + 99))
+            .collect::<Vec<Fp>>();
+        MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5)
+    }
+
+    fn public_inputs(root: Fp, proofs: &[MerkleProof<Fp>]) -> Vec<Fp> {
+        let mut public_inputs = vec![root];
+        public_inputs.extend(proofs.iter().map(|proof| proof.leaf));
+        public_inputs
+    }
+
+    #[test]
+    fn test_merkle_tree_public_positions() {
+        let tree = tree();
+
+        // siblings and leaves sharing upper levels, leaves only sharing the root, consecutive leaves
+        for indices in [vec![2, 3, 13, 30], vec![0, 31, 16, 15], vec![8, 9, 10, 11]] {
+            let proofs = indices
+                .iter()
+                .map(|index| tree.proof(*index))
+                .collect::<Vec<_>>();
+            let circuit =
+                MerkleTreePublicPositionsCircuit::<Fp, P128Pow5T3, 5, 4>::from_proofs(&proofs);
+
+            let public_input = public_inputs(tree.root(), &proofs);
+            let valid_prover = MockProver::run(11, &circuit, vec![public_input.clone()]).unwrap();
+            valid_prover.assert_satisfied();
+
+            for row in 0..public_input.len() {
+                let mut wrong_public_input = public_input.clone();
+                wrong_public_input[row] += Fp::from(1);
+                let invalid_prover =
+                    MockProver::run(11, &circuit, vec![wrong_public_input]).unwrap();
+                assert!(invalid_prover.verify().is_err());
+            }
+        }
+
+        let proofs = [tree.proof(7)];
+        let circuit =
+            MerkleTreePublicPositionsCircuit::<Fp, P128Pow5T3, 5, 1>::from_proofs(&proofs);
+        let valid_prover =
+            MockProver::run(10, &circuit, vec![public_inputs(tree.root(), &proofs)]).unwrap();
+        valid_prover.assert_satisfied();
+    }
+
+    #[test]
+    fn test_merkle_tree_public_positions_wrong_leaf() {
+        let tree = tree();
+        let proofs = [2, 3, 13, 30].map(|index| tree.proof(index));
+        let public_input = public_inputs(tree.root(), &proofs);
+
+        // a leaf that is not in the tree, even though its sibling is
+        let mut circuit =
+            MerkleTreePublicPositionsCircuit::<Fp, P128Pow5T3, 5, 4>::from_proofs(&proofs);
+        circuit.leaves[1] = Value::known(Fp::from(1000));
+        let mut wrong_public_input = public_input.clone();
+        wrong_public_input[2] = Fp::from(1000);
+        let invalid_prover = MockProver::run(11, &circuit, vec![wrong_public_input]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+
+        // a leaf at another position
+        let mut circuit =
+            MerkleTreePublicPositionsCircuit::<Fp, P128Pow5T3, 5, 4>::from_proofs(&proofs);
+        circuit.public_leaf_indices[2] = 12;
+        let invalid_prover = MockProver::run(11, &circuit, vec![public_input.clone()]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+
+        // a position used twice or out of the tree is rejected at synthesis
+        for index in [3, 32] {
+            let mut circuit =
+                MerkleTreePublicPositionsCircuit::<Fp, P128Pow5T3, 5, 4>::from_proofs(&proofs);
+            circuit.public_leaf_indices[2] = index;
+            let result = MockProver::run(11, &circuit, vec![public_input.clone()]);
+            assert!(matches!(result, Err(Error::Synthesis)));
+        }
+    }
+
+    #[test]
+    fn test_merkle_tree_public_positions_shared_nodes() {
+        let tree = tree();
+        let rows = |indices: [usize; 4]| {
+            let proofs = indices.map(|index| tree.proof(index));
+            let circuit =
+                MerkleTreePublicPositionsCircuit::<Fp, P128Pow5T3, 5, 4>::from_proofs(&proofs);
+            let public_input = public_inputs(tree.root(), &proofs);
+            circuit_cost("merkle-tree-public-positions", &circuit, &[public_input])
+                .unwrap()
+                .rows
+        };
+
+        let proofs = [tree.proof(8)];
+        let circuit =
+            MerkleTreePublicPositionsCircuit::<Fp, P128Pow5T3, 5, 1>::from_proofs(&proofs);
+        let public_input = public_inputs(tree.root(), &proofs);
+        let single_leaf_rows =
+            circuit_cost("merkle-tree-public-positions", &circuit, &[public_input])
+                .unwrap()
+                .rows;
+
+        // the 5 levels of one leaf take 5 hashes. Leaves 8 to 11 take 6 hashes instead of 20, since the pairs of
+        // siblings are hashed together and the 3 upper levels are shared. Leaves 0, 8, 16 and 24 only share the 2
+        // upper levels and take 15 hashes
+        assert!(rows([8, 9, 10, 11]) < 2 * single_leaf_rows);
+        assert!(rows([0, 8, 16, 24]) < 4 * single_leaf_rows);
+        assert!(rows([0, 8, 16, 24]) > rows([8, 9, 10, 11]));
+    }
+
+    #[test]
+    fn test_merkle_tree_public_positions_kzg() {
+        let leaves = (0..32u64)
+            .map(|x| Fr::from(x * 3 + 99))
+            .collect::<Vec<Fr>>();
+        let tree = MerkleTree::<Fr, P128Pow5T3Bn256>::new(&leaves, 5);
+        let proofs = [2, 3, 13, 30].map(|index| tree.proof(index));
+        let circuit =
+            MerkleTreePublicPositionsCircuit::<Fr, P128Pow5T3Bn256, 5, 4>::from_proofs(&proofs);
+
+        let mut public_input = vec![tree.root()];
+        public_input.extend(proofs.iter().map(|proof| proof.leaf));
+        let public_inputs = vec![public_input];
+
+        let mut wrong_public_inputs = public_inputs.clone();
+        wrong_public_inputs[0][0] = Fr::from(0);
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
+
+    #[test]
+    fn test_merkle_tree_public_positions_in_verifying_key() {
+        let leaves = (0..32u64)
+            .map(|x| Fr::from(x * 3 + 99))
+            .collect::<Vec<Fr>>();
+        let tree = MerkleTree::<Fr, P128Pow5T3Bn256>::new(&leaves, 5);
+        let batch = |indices: [usize; 4]| {
+            let proofs = indices.map(|index| tree.proof(index));
+            let mut public_input = vec![tree.root()];
+            public_input.extend(proofs.iter().map(|proof| proof.leaf));
+            (
+                MerkleTreePublicPositionsCircuit::<Fr, P128Pow5T3Bn256, 5, 4>::from_proofs(&proofs),
+                vec![public_input],
+            )
+        };
+
+        // 30 and 29 have their sibling out of the batch and only share the root with 13, so both sets of positions
+        // take the same number of rows, but they are different circuits with different verifying keys
+        let (circuit, public_inputs) = batch([2, 3, 13, 30]);
+        let (other_circuit, other_public_inputs) = batch([2, 3, 13, 29]);
+        let params = kzg::setup(min_k(&circuit, &public_inputs).unwrap(), 42);
+        let pk = kzg::keys(&params, &circuit).unwrap();
+        let other_vk = kzg::verifying_key(&params, &other_circuit).unwrap();
+
+        // a proof is only accepted by the verifying key generated for its positions, even when the leaves of the
+        // other positions are public
+        let proof = kzg::full_prover(&params, &pk, &circuit, &public_inputs).unwrap();
+        assert!(kzg::full_verifier(&params, pk.get_vk(), &proof, &public_inputs).is_ok());
+        assert!(kzg::full_verifier(&params, &other_vk, &proof, &public_inputs).is_err());
+        assert!(kzg::full_verifier(&params, &other_vk, &proof, &other_public_inputs).is_err());
+    }
+}
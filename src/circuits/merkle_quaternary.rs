@@ -0,0 +1,231 @@
+use super::super::chips::merkle_quaternary::{
+    QuaternaryMerkleTreeChip, QuaternaryMerkleTreeConfig,
+};
+use crate::chips::poseidon_spec::P128Pow5T5Pasta;
+use crate::experiments::ExperimentVisitor;
+use crate::merkle_tree::{QuaternaryMerkleProof, QuaternaryMerkleTree};
+use halo2_gadgets::poseidon::primitives::Spec;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
+use std::marker::PhantomData;
+
+// Proves that the leaf (row 0) is in the quaternary tree of depth DEPTH whose root is at row 1.
+// path_elements[i] are the three siblings of the node at level i, path_indices[i] its position (0 to 3)
+pub struct QuaternaryMerkleTreeCircuit<F: FieldExt, S: Spec<F, 5, 4>, const DEPTH: usize> {
+    pub leaf: Value<F>,
+    pub path_elements: [[Value<F>; 3]; DEPTH],
+    pub path_indices: [Value<F>; DEPTH],
+    _spec: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 5, 4>, const DEPTH: usize> QuaternaryMerkleTreeCircuit<F, S, DEPTH> {
+    pub fn new(
+        leaf: Value<F>,
+        path_elements: [[Value<F>; 3]; DEPTH],
+        path_indices: [Value<F>; DEPTH],
+    ) -> Self {
+        Self {
+            leaf,
+            path_elements,
+            path_indices,
+            _spec: PhantomData,
+        }
+    }
+
+    // Builds the circuit out of a proof generated by merkle_tree::QuaternaryMerkleTree, the tree must have depth DEPTH
+    pub fn from_proof(proof: &QuaternaryMerkleProof<F>) -> Self {
+        assert_eq!(
+            proof.path_elements.len(),
+            DEPTH,
+            "the proof has {} levels, the circuit expects {}",
+            proof.path_elements.len(),
+            DEPTH
+        );
+        Self::new(
+            Value::known(proof.leaf),
+            std::array::from_fn(|i| proof.path_elements[i].map(Value::known)),
+            std::array::from_fn(|i| Value::known(proof.path_indices[i])),
+        )
+    }
+}
+
+impl<F: FieldExt, S: Spec<F, 5, 4>, const DEPTH: usize> Circuit<F>
+    for QuaternaryMerkleTreeCircuit<F, S, DEPTH>
+{
+    type Config = QuaternaryMerkleTreeConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new(
+            Value::unknown(),
+            [[Value::unknown(); 3]; DEPTH],
+            [Value::unknown(); DEPTH],
+        )
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+
+        QuaternaryMerkleTreeChip::<F, S>::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = QuaternaryMerkleTreeChip::<F, S>::construct(config);
+        let leaf_cell = chip.assign_leaf(layouter.namespace(|| "assign leaf"), self.leaf)?;
+        chip.expose_public(layouter.namespace(|| "public leaf"), &leaf_cell, 0)?;
+
+        // starting from the leaf, compute the digest of every level up to the root
+        let mut digest = leaf_cell;
+        for (siblings, index) in self.path_elements.iter().zip(self.path_indices.iter()) {
+            digest = chip.merkle_prove_layer(
+                layouter.namespace(|| "merkle prove layer"),
+                &digest,
+                *siblings,
+                *index,
+            )?;
+        }
+        chip.expose_public(layouter.namespace(|| "public root"), &digest, 1)?;
+        Ok(())
+    }
+}
+
+// Instance used by the experiments registry, a leaf of a tree of depth 3 (64 leaves)
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let leaves = (0..64u64)
+        .map(|x| Fp::from(x * 3 + 99))
+        .collect::<Vec<Fp>>();
+    let tree = QuaternaryMerkleTree::<Fp, P128Pow5T5Pasta>::new(&leaves, 3);
+    let proof = tree.proof(27);
+
+    let circuit = QuaternaryMerkleTreeCircuit::<Fp, P128Pow5T5Pasta, 3>::from_proof(&proof);
+    visitor.visit(
+        "merkle-tree-quaternary",
+        &circuit,
+        &[vec![proof.leaf, proof.root]],
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QuaternaryMerkleTreeCircuit;
+    use crate::chips::poseidon_spec::{P128Pow5T5Bn256, P128Pow5T5Pasta};
+    use crate::circuits::merkle_v3::MerkleTreeV3Circuit;
+    use crate::cost::circuit_cost;
+    use crate::merkle_tree::{MerkleTree, QuaternaryMerkleTree};
+    use crate::proving::kzg;
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+    };
+
+    fn leaves() -> Vec<Fp> {
+        (0..64u64).map(|x| Fp::from(x * 3 + 99)).collect()
+    }
+
+    #[test]
+    fn test_merkle_tree_quaternary() {
+        let tree = QuaternaryMerkleTree::<Fp, P128Pow5T5Pasta>::new(&leaves(), 3);
+
+        // every position among the siblings is used at some level
+        for index in [0, 27, 45, 63] {
+            let proof = tree.proof(index);
+            let circuit = QuaternaryMerkleTreeCircuit::<Fp, P128Pow5T5Pasta, 3>::from_proof(&proof);
+
+            let public_input = vec![proof.leaf, proof.root];
+            let valid_prover = MockProver::run(10, &circuit, vec![public_input.clone()]).unwrap();
+            valid_prover.assert_satisfied();
+
+            for row in 0..public_input.len() {
+                let mut wrong_public_input = public_input.clone();
+                wrong_public_input[row] += Fp::from(1);
+                let invalid_prover =
+                    MockProver::run(10, &circuit, vec![wrong_public_input]).unwrap();
+                assert!(invalid_prover.verify().is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_merkle_tree_quaternary_wrong_index() {
+        let tree = QuaternaryMerkleTree::<Fp, P128Pow5T5Pasta>::new(&leaves(), 3);
+        let proof = tree.proof(27);
+        let public_input = vec![proof.leaf, proof.root];
+
+        // the leaf 27 is the last child of its parent, it can't be placed at another position
+        for index in 0..3 {
+            let mut circuit =
+                QuaternaryMerkleTreeCircuit::<Fp, P128Pow5T5Pasta, 3>::from_proof(&proof);
+            circuit.path_indices[0] = Value::known(Fp::from(index));
+            let invalid_prover = MockProver::run(10, &circuit, vec![public_input.clone()]).unwrap();
+            assert!(invalid_prover.verify().is_err());
+        }
+
+        // an index that is not a position fails the bool constraint instead of panicking
+        for index in [4, 7, 1 << 20] {
+            let mut circuit =
+                QuaternaryMerkleTreeCircuit::<Fp, P128Pow5T5Pasta, 3>::from_proof(&proof);
+            circuit.path_indices[0] = Value::known(Fp::from(index));
+            let invalid_prover = MockProver::run(10, &circuit, vec![public_input.clone()]).unwrap();
+            assert!(invalid_prover.verify().is_err());
+        }
+
+        // the siblings are ordered
+        let mut circuit = QuaternaryMerkleTreeCircuit::<Fp, P128Pow5T5Pasta, 3>::from_proof(&proof);
+        circuit.path_elements[1].swap(0, 1);
+        let invalid_prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_merkle_tree_quaternary_rows() {
+        // a quaternary tree of depth 3 holds as many leaves as a binary tree of depth 6, with half the hashes
+        let leaves = leaves();
+        let quaternary_tree = QuaternaryMerkleTree::<Fp, P128Pow5T5Pasta>::new(&leaves, 3);
+        let quaternary_proof = quaternary_tree.proof(27);
+        let quaternary_circuit =
+            QuaternaryMerkleTreeCircuit::<Fp, P128Pow5T5Pasta, 3>::from_proof(&quaternary_proof);
+        let quaternary_rows = circuit_cost(
+            "merkle-tree-quaternary",
+            &quaternary_circuit,
+            &[vec![quaternary_proof.leaf, quaternary_proof.root]],
+        )
+        .unwrap()
+        .rows;
+
+        let binary_tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 6);
+        let binary_proof = binary_tree.proof(27);
+        let binary_circuit = MerkleTreeV3Circuit::<Fp, P128Pow5T3, 6>::from_proof(&binary_proof);
+        let binary_rows = circuit_cost(
+            "merkle-tree-3",
+            &binary_circuit,
+            &[vec![binary_proof.leaf, binary_proof.root]],
+        )
+        .unwrap()
+        .rows;
+
+        assert!(quaternary_rows < binary_rows);
+    }
+
+    #[test]
+    fn test_merkle_tree_quaternary_kzg() {
+        let leaves = (0..64u64)
+            .map(|x| Fr::from(x * 3 + 99))
+            .collect::<Vec<Fr>>();
+        let tree = QuaternaryMerkleTree::<Fr, P128Pow5T5Bn256>::new(&leaves, 3);
+        let proof = tree.proof(45);
+        let circuit = QuaternaryMerkleTreeCircuit::<Fr, P128Pow5T5Bn256, 3>::from_proof(&proof);
+        let public_inputs = vec![vec![proof.leaf, proof.root]];
+
+        let mut wrong_public_inputs = public_inputs.clone();
+        wrong_public_inputs[0][1] = Fr::from(0);
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
+}
@@ -0,0 +1,263 @@
+use super::super::chips::merkle_sum_tree::{MerkleSumTreeChip, MerkleSumTreeConfig};
+use crate::experiments::ExperimentVisitor;
+use crate::merkle_tree::{MerkleSumProof, MerkleSumTree};
+use halo2_gadgets::poseidon::primitives::{P128Pow5T3, Spec};
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
+use std::marker::PhantomData;
+
+// The depth of the tree is a compile-time parameter, so that the shape of the circuit doesn't depend on the witnesses
+pub struct MerkleSumTreeCircuit<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> {
+    pub username: Value<F>,
+    pub balance: Value<F>,
+    pub path_element_hashes: [Value<F>; DEPTH],
+    pub path_element_sums: [Value<F>; DEPTH],
+    pub path_indices: [Value<F>; DEPTH],
+    _spec: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> MerkleSumTreeCircuit<F, S, DEPTH> {
+    pub fn new(
+        username: Value<F>,
+        balance: Value<F>,
+        path_element_hashes: [Value<F>; DEPTH],
+        path_element_sums: [Value<F>; DEPTH],
+        path_indices: [Value<F>; DEPTH],
+    ) -> Self {
+        Self {
+            username,
+            balance,
+            path_element_hashes,
+            path_element_sums,
+            path_indices,
+            _spec: PhantomData,
+        }
+    }
+
+    // Builds the circuit out of a proof generated by merkle_tree::MerkleSumTree, the tree must have depth DEPTH
+    pub fn from_proof(proof: &MerkleSumProof<F>) -> Self {
+        assert_eq!(
+            proof.path_element_hashes.len(),
+            DEPTH,
+            "the proof has {} levels, the circuit expects {}",
+            proof.path_element_hashes.len(),
+            DEPTH
+        );
+        Self::new(
+            Value::known(proof.username),
+            Value::known(proof.balance),
+            std::array::from_fn(|i| Value::known(proof.path_element_hashes[i])),
+            std::array::from_fn(|i| Value::known(proof.path_element_sums[i])),
+            std::array::from_fn(|i| Value::known(proof.path_indices[i])),
+        )
+    }
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> Circuit<F>
+    for MerkleSumTreeCircuit<F, S, DEPTH>
+{
+    type Config = MerkleSumTreeConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new(
+            Value::unknown(),
+            Value::unknown(),
+            [Value::unknown(); DEPTH],
+            [Value::unknown(); DEPTH],
+            [Value::unknown(); DEPTH],
+        )
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+
+        MerkleSumTreeChip::<F, S>::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = MerkleSumTreeChip::<F, S>::construct(config);
+        chip.load_range_check_table(layouter.namespace(|| "load range check table"))?;
+
+        let (leaf_hash, leaf_sum) = chip.assign_leaf(
+            layouter.namespace(|| "assign leaf"),
+            self.username,
+            self.balance,
+        )?;
+        chip.expose_public(layouter.namespace(|| "public leaf hash"), &leaf_hash, 0)?;
+
+        // starting from the leaf, compute the hash and the sum of every node up to the root
+        let mut node_hash = leaf_hash;
+        let mut node_sum = leaf_sum;
+        for i in 0..self.path_element_hashes.len() {
+            (node_hash, node_sum) = chip.merkle_prove_layer(
+                layouter.namespace(|| "merkle sum prove layer"),
+                &node_hash,
+                &node_sum,
+                self.path_element_hashes[i],
+                self.path_element_sums[i],
+                self.path_indices[i],
+            )?;
+        }
+
+        chip.expose_public(layouter.namespace(|| "public root hash"), &node_hash, 1)?;
+        chip.expose_public(layouter.namespace(|| "public root sum"), &node_sum, 2)?;
+        Ok(())
+    }
+}
+
+// Instance used by the experiments registry, a tree of 16 users
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let entries = (0..16u64)
+        .map(|i| (Fp::from(i + 1), Fp::from(i * 1000 + 7)))
+        .collect::<Vec<(Fp, Fp)>>();
+    let tree = MerkleSumTree::<Fp, P128Pow5T3>::new(&entries, 4);
+    let (root_hash, root_sum) = tree.root();
+    let proof = tree.proof(5);
+
+    let circuit = MerkleSumTreeCircuit::<Fp, P128Pow5T3, 4>::from_proof(&proof);
+    visitor.visit(
+        "merkle-sum-tree",
+        &circuit,
+        &[vec![proof.leaf_hash, root_hash, root_sum]],
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MerkleSumTreeCircuit;
+    use crate::chips::poseidon_spec::P128Pow5T3Bn256;
+    use crate::merkle_tree::MerkleSumTree;
+    use crate::proving::{ipa, kzg};
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::{
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+    };
+
+    #[test]
+    fn test_merkle_sum_tree() {
+        let entries = (0..16u64)
+            .map(|i| (Fp::from(i + 1), Fp::from(i * 1000 + 7)))
+            .collect::<Vec<(Fp, Fp)>>();
+        let tree = MerkleSumTree::<Fp, P128Pow5T3>::new(&entries, 4);
+        let (root_hash, root_sum) = tree.root();
+
+        for index in [0, 5, 15] {
+            let proof = tree.proof(index);
+            let circuit = MerkleSumTreeCircuit::<Fp, P128Pow5T3, 4>::from_proof(&proof);
+
+            let correct_public_input = vec![proof.leaf_hash, root_hash, root_sum];
+            let valid_prover = MockProver::run(10, &circuit, vec![correct_public_input]).unwrap();
+            valid_prover.assert_satisfied();
+
+            // the total sum of the liabilities cannot be understated
+            let wrong_public_input = vec![proof.leaf_hash, root_hash, root_sum - Fp::from(1)];
+            let invalid_prover = MockProver::run(10, &circuit, vec![wrong_public_input]).unwrap();
+            assert!(invalid_prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_merkle_sum_tree_wrong_balance() {
+        let entries = (0..16u64)
+            .map(|i| (Fp::from(i + 1), Fp::from(i * 1000 + 7)))
+            .collect::<Vec<(Fp, Fp)>>();
+        let tree = MerkleSumTree::<Fp, P128Pow5T3>::new(&entries, 4);
+        let (root_hash, root_sum) = tree.root();
+
+        // lowering the balance of the user breaks the leaf hash and the root
+        let mut proof = tree.proof(3);
+        proof.balance = Fp::from(0);
+        let circuit = MerkleSumTreeCircuit::<Fp, P128Pow5T3, 4>::from_proof(&proof);
+
+        let public_input = vec![tree.proof(3).leaf_hash, root_hash, root_sum];
+        let invalid_prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+
+        // a sibling sum that does not match the sibling hash breaks the root hash
+        let mut proof = tree.proof(3);
+        proof.path_element_sums[0] += Fp::from(1);
+        let circuit = MerkleSumTreeCircuit::<Fp, P128Pow5T3, 4>::from_proof(&proof);
+
+        let public_input = vec![proof.leaf_hash, root_hash, root_sum + Fp::from(1)];
+        let invalid_prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_merkle_sum_tree_overflow() {
+        // a malicious prover adds a user with a "negative" balance p - 1000 to cancel out the balance of another user
+        let mut entries = (0..16u64)
+            .map(|i| (Fp::from(i + 1), Fp::from(1000)))
+            .collect::<Vec<(Fp, Fp)>>();
+        entries[9].1 = -Fp::from(1000);
+        let tree = MerkleSumTree::<Fp, P128Pow5T3>::new(&entries, 4);
+        let (root_hash, root_sum) = tree.root();
+        assert_eq!(root_sum, Fp::from(14 * 1000));
+
+        // the balance of the malicious user is rejected by the range check of the leaf
+        let proof = tree.proof(9);
+        let circuit = MerkleSumTreeCircuit::<Fp, P128Pow5T3, 4>::from_proof(&proof);
+        let public_input = vec![proof.leaf_hash, root_hash, root_sum];
+        let invalid_prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+
+        // the proof of the sibling is rejected as well, because of the range check of the sibling sum
+        let proof = tree.proof(8);
+        let circuit = MerkleSumTreeCircuit::<Fp, P128Pow5T3, 4>::from_proof(&proof);
+        let public_input = vec![proof.leaf_hash, root_hash, root_sum];
+        let invalid_prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+
+        // a balance right above 64 bits is rejected too, while the largest 64 bits balance is accepted
+        for (balance, valid) in [
+            (Fp::from(u64::MAX) + Fp::from(1), false),
+            (Fp::from(u64::MAX), true),
+        ] {
+            let tree = MerkleSumTree::<Fp, P128Pow5T3>::new(&[(Fp::from(1), balance)], 4);
+            let (root_hash, root_sum) = tree.root();
+            let proof = tree.proof(0);
+            let circuit = MerkleSumTreeCircuit::<Fp, P128Pow5T3, 4>::from_proof(&proof);
+            let public_input = vec![proof.leaf_hash, root_hash, root_sum];
+            let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+            assert_eq!(prover.verify().is_ok(), valid);
+        }
+    }
+
+    #[test]
+    fn test_merkle_sum_tree_real_prover() {
+        let entries = (0..16u64)
+            .map(|i| (Fp::from(i + 1), Fp::from(i * 1000 + 7)))
+            .collect::<Vec<(Fp, Fp)>>();
+        let tree = MerkleSumTree::<Fp, P128Pow5T3>::new(&entries, 4);
+        let (root_hash, root_sum) = tree.root();
+        let proof = tree.proof(5);
+        let circuit = MerkleSumTreeCircuit::<Fp, P128Pow5T3, 4>::from_proof(&proof);
+
+        let public_inputs = vec![vec![proof.leaf_hash, root_hash, root_sum]];
+        let wrong_public_inputs = vec![vec![proof.leaf_hash, root_hash, root_sum - Fp::from(1)]];
+        ipa::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
+
+    #[test]
+    fn test_merkle_sum_tree_kzg() {
+        let entries = (0..16u64)
+            .map(|i| (Fr::from(i + 1), Fr::from(i * 1000 + 7)))
+            .collect::<Vec<(Fr, Fr)>>();
+        let tree = MerkleSumTree::<Fr, P128Pow5T3Bn256>::new(&entries, 4);
+        let (root_hash, root_sum) = tree.root();
+        let proof = tree.proof(5);
+        let circuit = MerkleSumTreeCircuit::<Fr, P128Pow5T3Bn256, 4>::from_proof(&proof);
+        let public_inputs = vec![vec![proof.leaf_hash, root_hash, root_sum]];
+
+        let wrong_public_inputs = vec![vec![proof.leaf_hash, root_hash, root_sum - Fr::from(1)]];
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
+}
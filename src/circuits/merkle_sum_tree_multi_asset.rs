@@ -0,0 +1,298 @@
+use super::super::chips::merkle_sum_tree_multi_asset::{
+    MerkleSumTreeMultiAssetChip, MerkleSumTreeMultiAssetConfig,
+};
+use crate::experiments::ExperimentVisitor;
+use crate::merkle_tree::{MerkleSumMultiAssetProof, MerkleSumMultiAssetTree};
+use halo2_gadgets::poseidon::primitives::{P128Pow5T3, Spec};
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
+use std::marker::PhantomData;
+
+// DEPTH is the number of levels of the tree above the leaves, N_ASSETS the number of balances of each user
+pub struct MerkleSumTreeMultiAssetCircuit<
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
+    const WIDTH: usize,
+    const RATE: usize,
+    const N_ASSETS: usize,
+    const DEPTH: usize,
+> {
+    pub username: Value<F>,
+    pub balances: [Value<F>; N_ASSETS],
+    pub path_element_hashes: [Value<F>; DEPTH],
+    pub path_element_sums: [[Value<F>; N_ASSETS]; DEPTH],
+    pub path_indices: [Value<F>; DEPTH],
+    _spec: PhantomData<S>,
+}
+
+impl<
+        F: FieldExt,
+        S: Spec<F, WIDTH, RATE>,
+        const WIDTH: usize,
+        const RATE: usize,
+        const N_ASSETS: usize,
+        const DEPTH: usize,
+    > MerkleSumTreeMultiAssetCircuit<F, S, WIDTH, RATE, N_ASSETS, DEPTH>
+{
+    pub fn new(
+        username: Value<F>,
+        balances: [Value<F>; N_ASSETS],
+        path_element_hashes: [Value<F>; DEPTH],
+        path_element_sums: [[Value<F>; N_ASSETS]; DEPTH],
+        path_indices: [Value<F>; DEPTH],
+    ) -> Self {
+        Self {
+            username,
+            balances,
+            path_element_hashes,
+            path_element_sums,
+            path_indices,
+            _spec: PhantomData,
+        }
+    }
+
+    // Builds the circuit out of a proof generated by merkle_tree::MerkleSumMultiAssetTree, the tree must have
+    // depth DEPTH and every user must hold N_ASSETS balances
+    pub fn from_proof(proof: &MerkleSumMultiAssetProof<F, N_ASSETS>) -> Self {
+        assert_eq!(
+            proof.path_element_hashes.len(),
+            DEPTH,
+            "the proof has {} levels, the circuit expects {}",
+            proof.path_element_hashes.len(),
+            DEPTH
+        );
+        assert_eq!(
+            proof.path_element_sums.len(),
+            DEPTH,
+            "the proof has the sums of {} levels, the circuit expects {}",
+            proof.path_element_sums.len(),
+            DEPTH
+        );
+        assert_eq!(
+            proof.balances.len(),
+            N_ASSETS,
+            "the proof has {} assets, the circuit expects {}",
+            proof.balances.len(),
+            N_ASSETS
+        );
+        Self::new(
+            Value::known(proof.username),
+            proof.balances.map(Value::known),
+            std::array::from_fn(|i| Value::known(proof.path_element_hashes[i])),
+            std::array::from_fn(|i| proof.path_element_sums[i].map(Value::known)),
+            std::array::from_fn(|i| Value::known(proof.path_indices[i])),
+        )
+    }
+}
+
+impl<
+        F: FieldExt,
+        S: Spec<F, WIDTH, RATE>,
+        const WIDTH: usize,
+        const RATE: usize,
+        const N_ASSETS: usize,
+        const DEPTH: usize,
+    > Circuit<F> for MerkleSumTreeMultiAssetCircuit<F, S, WIDTH, RATE, N_ASSETS, DEPTH>
+{
+    type Config = MerkleSumTreeMultiAssetConfig<F, WIDTH, RATE>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new(
+            Value::unknown(),
+            [Value::unknown(); N_ASSETS],
+            [Value::unknown(); DEPTH],
+            [[Value::unknown(); N_ASSETS]; DEPTH],
+            [Value::unknown(); DEPTH],
+        )
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+
+        MerkleSumTreeMultiAssetChip::<F, S, WIDTH, RATE, N_ASSETS>::configure(
+            meta,
+            [col_a, col_b, col_c],
+            instance,
+        )
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = MerkleSumTreeMultiAssetChip::<F, S, WIDTH, RATE, N_ASSETS>::construct(config);
+        chip.load_range_check_table(layouter.namespace(|| "load range check table"))?;
+
+        let (leaf_hash, leaf_sums) = chip.assign_leaf(
+            layouter.namespace(|| "assign leaf"),
+            self.username,
+            self.balances,
+        )?;
+        chip.expose_public(layouter.namespace(|| "public leaf hash"), &leaf_hash, 0)?;
+
+        // starting from the leaf, compute the hash and the sums of every node up to the root
+        let mut node_hash = leaf_hash;
+        let mut node_sums = leaf_sums;
+        for i in 0..self.path_element_hashes.len() {
+            (node_hash, node_sums) = chip.merkle_prove_layer(
+                layouter.namespace(|| "merkle sum prove layer"),
+                &node_hash,
+                &node_sums,
+                self.path_element_hashes[i],
+                self.path_element_sums[i],
+                self.path_indices[i],
+            )?;
+        }
+
+        // the root hash is exposed at row 1, the total of the asset i at row 2 + i
+        chip.expose_public(layouter.namespace(|| "public root hash"), &node_hash, 1)?;
+        for (i, node_sum) in node_sums.iter().enumerate() {
+            chip.expose_public(layouter.namespace(|| "public root sum"), node_sum, 2 + i)?;
+        }
+        Ok(())
+    }
+}
+
+// Instance used by the experiments registry, a tree of 8 users with 3 assets
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let entries = (0..8u64)
+        .map(|i| {
+            (
+                Fp::from(i + 1),
+                [Fp::from(i * 3), Fp::from(i * 50 + 1), Fp::from(7)],
+            )
+        })
+        .collect::<Vec<(Fp, [Fp; 3])>>();
+    let tree = MerkleSumMultiAssetTree::<Fp, P128Pow5T3, 3, 2, 3>::new(&entries, 3);
+    let (root_hash, root_sums) = tree.root();
+    let proof = tree.proof(3);
+
+    let circuit = MerkleSumTreeMultiAssetCircuit::<Fp, P128Pow5T3, 3, 2, 3, 3>::from_proof(&proof);
+
+    let mut public_input = vec![proof.leaf_hash, root_hash];
+    public_input.extend(root_sums);
+    visitor.visit("merkle-sum-tree-multi-asset", &circuit, &[public_input]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MerkleSumTreeMultiAssetCircuit;
+    use crate::chips::poseidon_spec::P128Pow5T5Bn256;
+    use crate::merkle_tree::MerkleSumMultiAssetTree;
+    use crate::proving::kzg;
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::{
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+    };
+
+    #[test]
+    fn test_merkle_sum_tree_multi_asset() {
+        let entries = (0..8u64)
+            .map(|i| {
+                (
+                    Fp::from(i + 1),
+                    [Fp::from(i * 3), Fp::from(i * 50 + 1), Fp::from(7)],
+                )
+            })
+            .collect::<Vec<(Fp, [Fp; 3])>>();
+        let tree = MerkleSumMultiAssetTree::<Fp, P128Pow5T3, 3, 2, 3>::new(&entries, 3);
+        let (root_hash, root_sums) = tree.root();
+
+        for index in [0, 3, 6] {
+            let proof = tree.proof(index);
+            let circuit =
+                MerkleSumTreeMultiAssetCircuit::<Fp, P128Pow5T3, 3, 2, 3, 3>::from_proof(&proof);
+
+            let mut public_input = vec![proof.leaf_hash, root_hash];
+            public_input.extend(root_sums);
+            let valid_prover = MockProver::run(11, &circuit, vec![public_input.clone()]).unwrap();
+            valid_prover.assert_satisfied();
+
+            // understating the total of any asset is rejected
+            for asset in 0..3 {
+                let mut wrong_public_input = public_input.clone();
+                wrong_public_input[2 + asset] -= Fp::from(1);
+                let invalid_prover =
+                    MockProver::run(11, &circuit, vec![wrong_public_input]).unwrap();
+                assert!(invalid_prover.verify().is_err());
+            }
+        }
+
+        // lowering the balance of one asset of the user breaks the root hash
+        let mut proof = tree.proof(5);
+        proof.balances[1] = Fp::from(0);
+        let circuit =
+            MerkleSumTreeMultiAssetCircuit::<Fp, P128Pow5T3, 3, 2, 3, 3>::from_proof(&proof);
+
+        let mut public_input = vec![tree.proof(5).leaf_hash, root_hash];
+        public_input.extend(root_sums);
+        let invalid_prover = MockProver::run(11, &circuit, vec![public_input]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_merkle_sum_tree_multi_asset_overflow() {
+        // the balance of the asset 1 of the user 2 is "negative" and cancels out the balance of the user 3
+        let mut entries = (0..8u64)
+            .map(|i| (Fp::from(i + 1), [Fp::from(10), Fp::from(10), Fp::from(10)]))
+            .collect::<Vec<(Fp, [Fp; 3])>>();
+        entries[2].1[1] = -Fp::from(10);
+        let tree = MerkleSumMultiAssetTree::<Fp, P128Pow5T3, 3, 2, 3>::new(&entries, 3);
+        let (root_hash, root_sums) = tree.root();
+
+        for index in [2, 3] {
+            let proof = tree.proof(index);
+            let circuit =
+                MerkleSumTreeMultiAssetCircuit::<Fp, P128Pow5T3, 3, 2, 3, 3>::from_proof(&proof);
+
+            let mut public_input = vec![proof.leaf_hash, root_hash];
+            public_input.extend(root_sums);
+            let invalid_prover = MockProver::run(11, &circuit, vec![public_input]).unwrap();
+            assert!(invalid_prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_merkle_sum_tree_multi_asset_bn256_width_5() {
+        // a wider permutation absorbs the nodes with fewer permutations
+        let entries = (0..4u64)
+            .map(|i| (Fr::from(i + 1), [Fr::from(i * 3), Fr::from(i * 50 + 1)]))
+            .collect::<Vec<(Fr, [Fr; 2])>>();
+        let tree = MerkleSumMultiAssetTree::<Fr, P128Pow5T5Bn256, 5, 4, 2>::new(&entries, 2);
+        let (root_hash, root_sums) = tree.root();
+
+        let proof = tree.proof(2);
+        let circuit =
+            MerkleSumTreeMultiAssetCircuit::<Fr, P128Pow5T5Bn256, 5, 4, 2, 2>::from_proof(&proof);
+
+        let mut public_input = vec![proof.leaf_hash, root_hash];
+        public_input.extend(root_sums);
+        let valid_prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        valid_prover.assert_satisfied();
+    }
+
+    #[test]
+    fn test_merkle_sum_tree_multi_asset_kzg() {
+        let entries = (0..4u64)
+            .map(|i| (Fr::from(i + 1), [Fr::from(i * 3), Fr::from(i * 50 + 1)]))
+            .collect::<Vec<(Fr, [Fr; 2])>>();
+        let tree = MerkleSumMultiAssetTree::<Fr, P128Pow5T5Bn256, 5, 4, 2>::new(&entries, 2);
+        let (root_hash, root_sums) = tree.root();
+        let proof = tree.proof(1);
+        let circuit =
+            MerkleSumTreeMultiAssetCircuit::<Fr, P128Pow5T5Bn256, 5, 4, 2, 2>::from_proof(&proof);
+
+        let mut public_input = vec![proof.leaf_hash, root_hash];
+        public_input.extend(root_sums);
+        let public_inputs = vec![public_input];
+
+        let mut wrong_public_inputs = public_inputs.clone();
+        wrong_public_inputs[0][3] += Fr::from(1);
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
+}
@@ -0,0 +1,279 @@
+use super::super::chips::merkle_update::{MerkleUpdateChip, MerkleUpdateConfig};
+use crate::experiments::ExperimentVisitor;
+use crate::merkle_tree::{MerkleTree, MerkleUpdateProof};
+use halo2_gadgets::poseidon::primitives::{P128Pow5T3, Spec};
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
+use std::marker::PhantomData;
+
+// Witnesses of the replacement of a leaf of a tree of depth DEPTH
+#[derive(Debug, Clone, Copy)]
+pub struct MerkleUpdate<F: FieldExt, const DEPTH: usize> {
+    pub old_leaf: Value<F>,
+    pub new_leaf: Value<F>,
+    pub path_elements: [Value<F>; DEPTH],
+    pub path_indices: [Value<F>; DEPTH],
+}
+
+impl<F: FieldExt, const DEPTH: usize> MerkleUpdate<F, DEPTH> {
+    pub fn unknown() -> Self {
+        Self {
+            old_leaf: Value::unknown(),
+            new_leaf: Value::unknown(),
+            path_elements: [Value::unknown(); DEPTH],
+            path_indices: [Value::unknown(); DEPTH],
+        }
+    }
+
+    // The proof must come from merkle_tree::MerkleTree::update on a tree of depth DEPTH
+    pub fn from_proof(proof: &MerkleUpdateProof<F>) -> Self {
+        assert_eq!(
+            proof.path_elements.len(),
+            DEPTH,
+            "the proof has {} levels, the circuit expects {}",
+            proof.path_elements.len(),
+            DEPTH
+        );
+        Self {
+            old_leaf: Value::known(proof.old_leaf),
+            new_leaf: Value::known(proof.new_leaf),
+            path_elements: std::array::from_fn(|i| Value::known(proof.path_elements[i])),
+            path_indices: std::array::from_fn(|i| Value::known(proof.path_indices[i])),
+        }
+    }
+}
+
+// Proves a batch of N_UPDATES updates applied one after the other to the same tree.
+// The public inputs are the root before the first update (row 0), the root after the last update (row 1), then the
+// old leaf and the new leaf of every update (rows 2 + 2 * i and 3 + 2 * i). The intermediate roots stay private
+pub struct MerkleUpdateCircuit<
+    F: FieldExt,
+    S: Spec<F, 3, 2>,
+    const DEPTH: usize,
+    const N_UPDATES: usize,
+> {
+    pub updates: [MerkleUpdate<F, DEPTH>; N_UPDATES],
+    _spec: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize, const N_UPDATES: usize>
+    MerkleUpdateCircuit<F, S, DEPTH, N_UPDATES>
+{
+    // Without updates the circuit would expose no root and accept any public input, so N_UPDATES = 0 doesn't compile
+    const AT_LEAST_ONE_UPDATE: () = assert!(N_UPDATES > 0, "the circuit needs at least one update");
+
+    pub fn new(updates: [MerkleUpdate<F, DEPTH>; N_UPDATES]) -> Self {
+        let () = Self::AT_LEAST_ONE_UPDATE;
+        Self {
+            updates,
+            _spec: PhantomData,
+        }
+    }
+
+    // Builds the circuit out of the proofs of consecutive updates of the same tree
+    pub fn from_proofs(proofs: &[MerkleUpdateProof<F>]) -> Self {
+        assert_eq!(
+            proofs.len(),
+            N_UPDATES,
+            "{} updates provided, the circuit expects {}",
+            proofs.len(),
+            N_UPDATES
+        );
+        Self::new(std::array::from_fn(|i| {
+            MerkleUpdate::from_proof(&proofs[i])
+        }))
+    }
+
+    // Public inputs matching the proofs of the updates
+    pub fn public_inputs(proofs: &[MerkleUpdateProof<F>]) -> Vec<F> {
+        let () = Self::AT_LEAST_ONE_UPDATE;
+        assert_eq!(
+            proofs.len(),
+            N_UPDATES,
+            "{} updates provided, the circuit expects {}",
+            proofs.len(),
+            N_UPDATES
+        );
+        let mut public_inputs = vec![proofs[0].old_root, proofs[proofs.len() - 1].new_root];
+        for proof in proofs {
+            public_inputs.push(proof.old_leaf);
+            public_inputs.push(proof.new_leaf);
+        }
+        public_inputs
+    }
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize, const N_UPDATES: usize> Circuit<F>
+    for MerkleUpdateCircuit<F, S, DEPTH, N_UPDATES>
+{
+    type Config = MerkleUpdateConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new([MerkleUpdate::unknown(); N_UPDATES])
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+
+        MerkleUpdateChip::<F, S>::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = MerkleUpdateChip::<F, S>::construct(config);
+
+        let mut updates = Vec::with_capacity(N_UPDATES);
+        for (i, update) in self.updates.iter().enumerate() {
+            let assigned_update = chip.update(
+                layouter.namespace(|| format!("update {}", i)),
+                update.old_leaf,
+                update.new_leaf,
+                &update.path_elements,
+                &update.path_indices,
+            )?;
+            chip.expose_public(
+                layouter.namespace(|| "public old leaf"),
+                &assigned_update.old_leaf,
+                2 + 2 * i,
+            )?;
+            chip.expose_public(
+                layouter.namespace(|| "public new leaf"),
+                &assigned_update.new_leaf,
+                3 + 2 * i,
+            )?;
+
+            // every update starts from the root left by the previous one
+            if let Some(previous_update) = updates.last() {
+                chip.chain(
+                    layouter.namespace(|| "chain updates"),
+                    previous_update,
+                    &assigned_update,
+                )?;
+            }
+            updates.push(assigned_update);
+        }
+
+        if let (Some(first_update), Some(last_update)) = (updates.first(), updates.last()) {
+            chip.expose_public(
+                layouter.namespace(|| "public old root"),
+                &first_update.old_root,
+                0,
+            )?;
+            chip.expose_public(
+                layouter.namespace(|| "public new root"),
+                &last_update.new_root,
+                1,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Applies the updates (index, new leaf) one after the other to a tree of depth 5 and returns their proofs, used by the
+// experiments registry and by the tests
+fn update_proofs<F: FieldExt, S: Spec<F, 3, 2>>(
+    updates: &[(usize, u64)],
+) -> Vec<MerkleUpdateProof<F>> {
+    let leaves = (0..32u64).map(|x| F::from(x * 3 + 99)).collect::<Vec<F>>();
+    let mut tree = MerkleTree::<F, S>::new(&leaves, 5);
+    updates
+        .iter()
+        .map(|(index, leaf)| tree.update(*index, F::from(*leaf)))
+        .collect()
+}
+
+// Instance used by the experiments registry, 3 updates of a tree of depth 5
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let proofs = update_proofs::<Fp, P128Pow5T3>(&[(13, 1000), (2, 2000), (13, 3000)]);
+
+    let circuit = MerkleUpdateCircuit::<Fp, P128Pow5T3, 5, 3>::from_proofs(&proofs);
+    let public_inputs = MerkleUpdateCircuit::<Fp, P128Pow5T3, 5, 3>::public_inputs(&proofs);
+    visitor.visit("merkle-update", &circuit, &[public_inputs]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{update_proofs, MerkleUpdateCircuit};
+    use crate::chips::poseidon_spec::P128Pow5T3Bn256;
+    use crate::proving::kzg;
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::{
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+    };
+
+    type UpdateCircuit<const N_UPDATES: usize> = MerkleUpdateCircuit<Fp, P128Pow5T3, 5, N_UPDATES>;
+
+    #[test]
+    fn test_merkle_update() {
+        let proofs = update_proofs::<Fp, P128Pow5T3>(&[(13, 1000)]);
+        let circuit = UpdateCircuit::<1>::from_proofs(&proofs);
+        let public_input = UpdateCircuit::<1>::public_inputs(&proofs);
+
+        let valid_prover = MockProver::run(11, &circuit, vec![public_input.clone()]).unwrap();
+        valid_prover.assert_satisfied();
+
+        // every public input is bound: the roots and the leaves
+        for row in 0..public_input.len() {
+            let mut wrong_public_input = public_input.clone();
+            wrong_public_input[row] += Fp::from(1);
+            let invalid_prover = MockProver::run(11, &circuit, vec![wrong_public_input]).unwrap();
+            assert!(invalid_prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_merkle_update_different_positions() {
+        // the old leaf and the new leaf share the same path, so the old leaf of position 13 cannot be replaced by
+        // a new leaf at position 14
+        let proofs = update_proofs::<Fp, P128Pow5T3>(&[(13, 1000)]);
+        let mut other_proofs = update_proofs::<Fp, P128Pow5T3>(&[(14, 1000)]);
+        other_proofs[0].old_leaf = proofs[0].old_leaf;
+        let circuit = UpdateCircuit::<1>::from_proofs(&other_proofs);
+        let public_input = UpdateCircuit::<1>::public_inputs(&other_proofs);
+
+        let invalid_prover = MockProver::run(11, &circuit, vec![public_input]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_merkle_update_batch() {
+        // the same position can be updated more than once
+        let proofs =
+            update_proofs::<Fp, P128Pow5T3>(&[(13, 1000), (2, 2000), (13, 3000), (31, 4000)]);
+        let circuit = UpdateCircuit::<4>::from_proofs(&proofs);
+        let public_input = UpdateCircuit::<4>::public_inputs(&proofs);
+
+        let valid_prover = MockProver::run(12, &circuit, vec![public_input.clone()]).unwrap();
+        valid_prover.assert_satisfied();
+
+        // the updates must be applied in order: each one starts from the root left by the previous one
+        let mut swapped_proofs = proofs.clone();
+        swapped_proofs.swap(1, 2);
+        let circuit = UpdateCircuit::<4>::from_proofs(&swapped_proofs);
+        let mut public_input = UpdateCircuit::<4>::public_inputs(&swapped_proofs);
+        public_input[0] = proofs[0].old_root;
+        public_input[1] = proofs[3].new_root;
+        let invalid_prover = MockProver::run(12, &circuit, vec![public_input]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_merkle_update_kzg() {
+        let proofs = update_proofs::<Fr, P128Pow5T3Bn256>(&[(13, 1000), (2, 2000)]);
+
+        let circuit = MerkleUpdateCircuit::<Fr, P128Pow5T3Bn256, 5, 2>::from_proofs(&proofs);
+        let public_inputs =
+            vec![MerkleUpdateCircuit::<Fr, P128Pow5T3Bn256, 5, 2>::public_inputs(&proofs)];
+
+        let mut wrong_public_inputs = public_inputs.clone();
+        wrong_public_inputs[0][1] = Fr::from(0);
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
+}
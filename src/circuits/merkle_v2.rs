@@ -1,19 +1,24 @@
 use super::super::chips::merkle_v2::{MerkleTreeV2Chip, MerkleTreeV2Config};
-use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
+use crate::experiments::ExperimentVisitor;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
 
-#[derive(Default)]
-struct MerkleTreeV2Circuit<F> {
+// The depth of the tree is a compile-time parameter, so that the shape of the circuit doesn't depend on the witnesses
+struct MerkleTreeV2Circuit<F, const DEPTH: usize> {
     pub leaf: Value<F>,
-    pub path_elements: Vec<Value<F>>,
-    pub path_indices: Vec<Value<F>>,
+    pub path_elements: [Value<F>; DEPTH],
+    pub path_indices: [Value<F>; DEPTH],
 }
 
-impl<F: FieldExt> Circuit<F> for MerkleTreeV2Circuit<F> {
+impl<F: FieldExt, const DEPTH: usize> Circuit<F> for MerkleTreeV2Circuit<F, DEPTH> {
     type Config = MerkleTreeV2Config;
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
-        Self::default()
+        Self {
+            leaf: Value::unknown(),
+            path_elements: [Value::unknown(); DEPTH],
+            path_indices: [Value::unknown(); DEPTH],
+        }
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
@@ -34,26 +39,17 @@ impl<F: FieldExt> Circuit<F> for MerkleTreeV2Circuit<F> {
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
         let chip = MerkleTreeV2Chip::construct(config);
-        let leaf_cell = chip.assing_leaf(layouter.namespace(|| "assign leaf"), self.leaf)?;
-        chip.expose_public(layouter.namespace(|| "public leaf"), &leaf_cell, 0);
-
-        // apply it for level 0 of the merkle tree
-        // node cell passed as input is the leaf cell
-        let mut digest = chip.merkle_prove_layer(
-            layouter.namespace(|| "merkle_prove"),
-            &leaf_cell,
-            self.path_elements[0],
-            self.path_indices[0],
-        )?;
-
-        // apply it for the remaining levels of the merkle tree
-        // node cell passed as input is the digest cell
-        for i in 1..self.path_elements.len() {
+        let leaf_cell = chip.assign_leaf(layouter.namespace(|| "assign leaf"), self.leaf)?;
+        chip.expose_public(layouter.namespace(|| "public leaf"), &leaf_cell, 0)?;
+
+        // the node of level 0 is the leaf, the node of each next level is the digest of the previous one
+        let mut digest = leaf_cell;
+        for (path_element, path_index) in self.path_elements.iter().zip(self.path_indices.iter()) {
             digest = chip.merkle_prove_layer(
-                layouter.namespace(|| "next level"),
+                layouter.namespace(|| "merkle prove layer"),
                 &digest,
-                self.path_elements[i],
-                self.path_indices[i],
+                *path_element,
+                *path_index,
             )?;
         }
         chip.expose_public(layouter.namespace(|| "public root"), &digest, 1)?;
@@ -61,27 +57,38 @@ impl<F: FieldExt> Circuit<F> for MerkleTreeV2Circuit<F> {
     }
 }
 
+// Instance used by the experiments registry, a tree of depth 5
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let circuit = MerkleTreeV2Circuit {
+        leaf: Value::known(Fp::from(99)),
+        path_elements: [1u64, 5, 6, 9, 9].map(|x| Value::known(Fp::from(x))),
+        path_indices: [0u64, 1, 0, 1, 0].map(|x| Value::known(Fp::from(x))),
+    };
+
+    // the dummy hash of the chip is the sum of the two nodes
+    visitor.visit("merkle-tree-2", &circuit, &[vec![Fp::from(99), Fp::from(129)]]);
+}
+
 #[cfg(test)]
 mod tests {
     use super::MerkleTreeV2Circuit;
-    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+    use crate::proving::{ipa, kzg};
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+    };
 
     #[test]
     fn test_merkle_tree_2() {
         let leaf = 99u64;
-        let elements = vec![1u64, 5u64, 6u64, 9u64, 9u64];
-        let indices = vec![0u64, 0u64, 0u64, 0u64, 0u64];
+        let elements = [1u64, 5u64, 6u64, 9u64, 9u64];
+        let indices = [0u64, 0u64, 0u64, 0u64, 0u64];
         let digest: u64 = leaf + elements.iter().sum::<u64>();
 
         let leaf_fp = Value::known(Fp::from(leaf));
-        let elements_fp: Vec<Value<Fp>> = elements
-            .iter()
-            .map(|x| Value::known(Fp::from(x.to_owned())))
-            .collect();
-        let indices_fp: Vec<Value<Fp>> = indices
-            .iter()
-            .map(|x| Value::known(Fp::from(x.to_owned())))
-            .collect();
+        let elements_fp = elements.map(|x| Value::known(Fp::from(x)));
+        let indices_fp = indices.map(|x| Value::known(Fp::from(x)));
 
         let circuit = MerkleTreeV2Circuit {
             leaf: leaf_fp,
@@ -93,43 +100,43 @@ mod tests {
         let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
         prover.assert_satisfied();
     }
-}
 
-#[cfg(feature = "dev-graph")]
-#[test]
-fn print_merkle_tree_2() {
-    use halo2_proofs::halo2curves::pasta::Fp;
-    use plotters::prelude::*;
-
-    let root =
-        BitMapBackend::new("prints/merkle-tree-2-layout.png", (1024, 3096)).into_drawing_area();
-    root.fill(&WHITE).unwrap();
-    let root = root
-        .titled("Merkle Tree 2 Layout", ("sans-serif", 60))
-        .unwrap();
-
-    let leaf = 99u64;
-    let elements = vec![1u64, 5u64, 6u64, 9u64, 9u64];
-    let indices = vec![0u64, 0u64, 0u64, 0u64, 0u64];
-    let digest: u64 = leaf + elements.iter().sum::<u64>();
-
-    let leaf_fp = Value::known(Fp::from(leaf));
-    let elements_fp: Vec<Value<Fp>> = elements
-        .iter()
-        .map(|x| Value::known(Fp::from(x.to_owned())))
-        .collect();
-    let indices_fp: Vec<Value<Fp>> = indices
-        .iter()
-        .map(|x| Value::known(Fp::from(x.to_owned())))
-        .collect();
+    #[test]
+    fn test_merkle_tree_2_depth_0() {
+        // a tree of depth 0 is a single leaf, which is also the root
+        let circuit = MerkleTreeV2Circuit::<Fp, 0> {
+            leaf: Value::known(Fp::from(99)),
+            path_elements: [],
+            path_indices: [],
+        };
+        let prover = MockProver::run(4, &circuit, vec![vec![Fp::from(99), Fp::from(99)]]).unwrap();
+        prover.assert_satisfied();
+    }
 
-    let circuit = MerkleTreeV2Circuit {
-        leaf: leaf_fp,
-        path_elements: elements_fp,
-        path_indices: indices_fp,
-    };
+    #[test]
+    fn test_merkle_tree_2_real_prover() {
+        let circuit = MerkleTreeV2Circuit {
+            leaf: Value::known(Fp::from(99)),
+            path_elements: [1u64, 5, 6, 9, 9].map(|x| Value::known(Fp::from(x))),
+            path_indices: [0u64, 1, 0, 1, 0].map(|x| Value::known(Fp::from(x))),
+        };
+
+        // the dummy hash of the chip is the sum of the two nodes
+        let public_inputs = vec![vec![Fp::from(99), Fp::from(129)]];
+        let wrong_public_inputs = vec![vec![Fp::from(99), Fp::from(130)]];
+        ipa::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
+
+    #[test]
+    fn test_merkle_tree_2_kzg() {
+        let circuit = MerkleTreeV2Circuit {
+            leaf: Value::known(Fr::from(99)),
+            path_elements: [1u64, 5, 6, 9, 9].map(|x| Value::known(Fr::from(x))),
+            path_indices: [0u64, 1, 0, 1, 0].map(|x| Value::known(Fr::from(x))),
+        };
+        let public_inputs = vec![vec![Fr::from(99), Fr::from(129)]];
 
-    halo2_proofs::dev::CircuitLayout::default()
-        .render(4, &circuit, &root)
-        .unwrap();
+        let wrong_public_inputs = vec![vec![Fr::from(99), Fr::from(130)]];
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
 }
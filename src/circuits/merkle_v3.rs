@@ -1,23 +1,70 @@
+use super::super::chips::merkle_index::{MerkleTreeIndexChip, MerkleTreeIndexConfig};
 use super::super::chips::merkle_v3::{MerkleTreeV3Chip, MerkleTreeV3Config};
-use halo2_proofs::{circuit::*, plonk::*, halo2curves::pasta::Fp};
+use crate::experiments::ExperimentVisitor;
+use crate::merkle_tree::{MerkleProof, MerkleTree};
+use halo2_gadgets::poseidon::primitives::{P128Pow5T3, Spec};
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
+use std::marker::PhantomData;
 
-#[derive(Default)]
-struct MerkleTreeV3Circuit {
-    pub leaf: Value<Fp>,
-    pub path_elements: Vec<Value<Fp>>,
-    pub path_indices: Vec<Value<Fp>>,
+// The depth of the tree is a compile-time parameter, so that the shape of the circuit doesn't depend on the witnesses
+pub struct MerkleTreeV3Circuit<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> {
+    pub leaf: Value<F>,
+    pub path_elements: [Value<F>; DEPTH],
+    pub path_indices: [Value<F>; DEPTH],
+    _spec: PhantomData<S>,
 }
 
-impl Circuit<Fp> for MerkleTreeV3Circuit {
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> MerkleTreeV3Circuit<F, S, DEPTH> {
+    // The leaf is the root of a tree of depth 0, which the chip can't prove, so DEPTH = 0 doesn't compile
+    const AT_LEAST_ONE_LEVEL: () = assert!(DEPTH > 0, "the tree needs at least one level");
 
-    type Config = MerkleTreeV3Config;
+    pub fn new(
+        leaf: Value<F>,
+        path_elements: [Value<F>; DEPTH],
+        path_indices: [Value<F>; DEPTH],
+    ) -> Self {
+        let () = Self::AT_LEAST_ONE_LEVEL;
+        Self {
+            leaf,
+            path_elements,
+            path_indices,
+            _spec: PhantomData,
+        }
+    }
+
+    // Builds the circuit out of a proof generated by merkle_tree::MerkleTree, the tree must have depth DEPTH
+    pub fn from_proof(proof: &MerkleProof<F>) -> Self {
+        assert_eq!(
+            proof.path_elements.len(),
+            DEPTH,
+            "the proof has {} levels, the circuit expects {}",
+            proof.path_elements.len(),
+            DEPTH
+        );
+        Self::new(
+            Value::known(proof.leaf),
+            std::array::from_fn(|i| Value::known(proof.path_elements[i])),
+            std::array::from_fn(|i| Value::known(proof.path_indices[i])),
+        )
+    }
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> Circuit<F>
+    for MerkleTreeV3Circuit<F, S, DEPTH>
+{
+
+    type Config = MerkleTreeV3Config<F>;
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
-        Self::default()
+        Self::new(
+            Value::unknown(),
+            [Value::unknown(); DEPTH],
+            [Value::unknown(); DEPTH],
+        )
     }
 
-    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
 
         // config for the merkle tree chip
         let col_a = meta.advice_column();
@@ -26,7 +73,7 @@ impl Circuit<Fp> for MerkleTreeV3Circuit {
         let instance = meta.instance_column();
 
 
-        MerkleTreeV3Chip::configure(
+        MerkleTreeV3Chip::<F, S>::configure(
             meta,
             [col_a, col_b, col_c],
             instance,
@@ -36,10 +83,10 @@ impl Circuit<Fp> for MerkleTreeV3Circuit {
     fn synthesize(
         &self,
         config: Self::Config,
-        mut layouter: impl Layouter<Fp>,
+        mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        let chip = MerkleTreeV3Chip::construct(config);
-        let leaf_cell = chip.assing_leaf(layouter.namespace(|| "assign leaf"), self.leaf)?;
+        let chip = MerkleTreeV3Chip::<F, S>::construct(config);
+        let leaf_cell = chip.assign_leaf(layouter.namespace(|| "assign leaf"), self.leaf)?;
         chip.expose_public(layouter.namespace(|| "public leaf"), &leaf_cell, 0)?;
 
         // apply it for level 0 of the merkle tree
@@ -66,102 +113,416 @@ impl Circuit<Fp> for MerkleTreeV3Circuit {
     }
 }
 
+// Same as MerkleTreeV3Circuit, but the position of the leaf is given by a single leaf index instead of DEPTH
+// independent path indices. The index is decomposed into DEPTH bits, which are used as the swap bits of the levels,
+// so the proof is bound to the position of the leaf. If PUBLIC_INDEX is true the index is exposed as a public input
+// (row 2), after the leaf (row 0) and the root (row 1)
+pub struct MerkleTreeV3IndexCircuit<
+    F: FieldExt,
+    S: Spec<F, 3, 2>,
+    const DEPTH: usize,
+    const PUBLIC_INDEX: bool,
+> {
+    pub leaf: Value<F>,
+    pub leaf_index: Value<F>,
+    pub path_elements: [Value<F>; DEPTH],
+    _spec: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize, const PUBLIC_INDEX: bool>
+    MerkleTreeV3IndexCircuit<F, S, DEPTH, PUBLIC_INDEX>
+{
+    // Same as MerkleTreeV3Circuit, a tree of depth 0 has no level to prove
+    const AT_LEAST_ONE_LEVEL: () = assert!(DEPTH > 0, "the tree needs at least one level");
+
+    pub fn new(leaf: Value<F>, leaf_index: Value<F>, path_elements: [Value<F>; DEPTH]) -> Self {
+        let () = Self::AT_LEAST_ONE_LEVEL;
+        Self {
+            leaf,
+            leaf_index,
+            path_elements,
+            _spec: PhantomData,
+        }
+    }
+
+    // Builds the circuit out of a proof generated by merkle_tree::MerkleTree, the tree must have depth DEPTH
+    pub fn from_proof(proof: &MerkleProof<F>) -> Self {
+        assert_eq!(
+            proof.path_elements.len(),
+            DEPTH,
+            "the proof has {} levels, the circuit expects {}",
+            proof.path_elements.len(),
+            DEPTH
+        );
+        Self::new(
+            Value::known(proof.leaf),
+            Value::known(proof.leaf_index()),
+            std::array::from_fn(|i| Value::known(proof.path_elements[i])),
+        )
+    }
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize, const PUBLIC_INDEX: bool> Circuit<F>
+    for MerkleTreeV3IndexCircuit<F, S, DEPTH, PUBLIC_INDEX>
+{
+    type Config = MerkleTreeIndexConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new(Value::unknown(), Value::unknown(), [Value::unknown(); DEPTH])
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+
+        MerkleTreeIndexChip::<F, S, DEPTH>::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = MerkleTreeIndexChip::<F, S, DEPTH>::construct(config);
+        let leaf_cell = chip.assign_leaf(layouter.namespace(|| "assign leaf"), self.leaf)?;
+        chip.expose_public(layouter.namespace(|| "public leaf"), &leaf_cell, 0)?;
+
+        let (index_cell, index_bits) =
+            chip.assign_index(layouter.namespace(|| "assign leaf index"), self.leaf_index)?;
+        if PUBLIC_INDEX {
+            chip.expose_public(layouter.namespace(|| "public leaf index"), &index_cell, 2)?;
+        }
+
+        let root_cell = chip.merkle_prove(
+            layouter.namespace(|| "merkle prove"),
+            &leaf_cell,
+            &index_bits,
+            &self.path_elements,
+        )?;
+        chip.expose_public(layouter.namespace(|| "public root"), &root_cell, 1)?;
+        Ok(())
+    }
+}
+
+// Instance used by the experiments registry, a tree of depth 5
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let leaves = (0..32u64).map(|x| Fp::from(x * 3 + 99)).collect::<Vec<Fp>>();
+    let tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5);
+    let proof = tree.proof(13);
+    let circuit = MerkleTreeV3Circuit::<Fp, P128Pow5T3, 5>::from_proof(&proof);
+    visitor.visit("merkle-tree-3", &circuit, &[vec![proof.leaf, tree.root()]]);
+
+    let circuit = MerkleTreeV3IndexCircuit::<Fp, P128Pow5T3, 5, true>::from_proof(&proof);
+    visitor.visit(
+        "merkle-tree-3-index",
+        &circuit,
+        &[vec![proof.leaf, tree.root(), Fp::from(13)]],
+    );
+}
+
 #[cfg(test)]
 mod tests {
-    use super::MerkleTreeV3Circuit;
-    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
-    use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, P128Pow5T3};
-
-    fn compute_merkle_root(leaf: &u64, elements: &Vec<u64>, indices: &Vec<u64>) -> Fp {
-        let k = elements.len();
-        let mut digest = Fp::from(leaf.clone());
-        let mut message: [Fp; 2];
-        for i in 0..k {
-            if indices[i] == 0 {
-                message = [digest, Fp::from(elements[i])];
-            } else {
-                message = [Fp::from(elements[i]), digest];
+    use super::{MerkleTreeV3Circuit, MerkleTreeV3IndexCircuit};
+    use crate::chips::hash_instructions::TwoToOneHashInstructions;
+    use crate::chips::merkle_tree::{MerkleTreeChip, MerkleTreeConfig};
+    use crate::chips::poseidon::{
+        PoseidonChip, PoseidonConfig, P128_POW5_T3_HASH_CONSTANTS, P128_POW5_T3_HASH_ROWS,
+    };
+    use crate::chips::poseidon_spec::P128Pow5T3Bn256;
+    use crate::cost::circuit_cost;
+    use crate::merkle_tree::{MerkleProof, MerkleTree};
+    use crate::proving::{ipa, kzg};
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::{
+        circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::{
+            bn256::Fr,
+            pasta::{EqAffine, Fp},
+        },
+        plonk::{
+            keygen_pk, keygen_vk, Advice, Circuit, Column, ConstraintSystem, Error, Instance,
+        },
+        poly::{commitment::ParamsProver, ipa::commitment::ParamsIPA},
+    };
+
+    #[test]
+    fn test_merkle_tree_3() {
+        let leaves = (0..32u64).map(|x| Fp::from(x * 3 + 99)).collect::<Vec<Fp>>();
+        let tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5);
+
+        for index in [0, 13, 31] {
+            let proof = tree.proof(index);
+            let circuit = MerkleTreeV3Circuit::<Fp, P128Pow5T3, 5>::from_proof(&proof);
+
+            let correct_public_input = vec![proof.leaf, tree.root()];
+            let valid_prover = MockProver::run(10, &circuit, vec![correct_public_input]).unwrap();
+            valid_prover.assert_satisfied();
+
+            let wrong_public_input = vec![proof.leaf, Fp::from(0)];
+            let invalid_prover = MockProver::run(10, &circuit, vec![wrong_public_input]).unwrap();
+            assert!(invalid_prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_merkle_tree_3_rows() {
+        let leaves = (0..32u64).map(|x| Fp::from(x * 3 + 99)).collect::<Vec<Fp>>();
+        let tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5);
+        let proof = tree.proof(13);
+        let circuit = MerkleTreeV3Circuit::<Fp, P128Pow5T3, 5>::from_proof(&proof);
+        let cost = circuit_cost("merkle-tree-3", &circuit, &[vec![proof.leaf, proof.root]]).unwrap();
+
+        // the hashes are laid out one after the other in the Poseidon state columns, one per level, and their
+        // initial states assign their constants after the last hash. The swap regions fit next to the hashes
+        assert_eq!(
+            cost.rows,
+            5 * (P128_POW5_T3_HASH_ROWS + P128_POW5_T3_HASH_CONSTANTS)
+        );
+    }
+
+    // Hash chip reproducing the layout used before the left and right cells were fed straight into
+    // the permutation: they were first copied into the hash_inputs columns of the PoseidonChip in a
+    // region of their own
+    struct CopyThenHashChip(PoseidonChip<Fp, P128Pow5T3, 3, 2, 2>);
+
+    impl TwoToOneHashInstructions<Fp> for CopyThenHashChip {
+        type Config = PoseidonConfig<Fp, 3, 2>;
+
+        fn configure(
+            meta: &mut ConstraintSystem<Fp>,
+            advice: [Column<Advice>; 3],
+            instance: Column<Instance>,
+        ) -> Self::Config {
+            <PoseidonChip<Fp, P128Pow5T3, 3, 2, 2> as TwoToOneHashInstructions<Fp>>::configure(
+                meta, advice, instance,
+            )
+        }
+
+        fn construct(config: Self::Config) -> Self {
+            Self(PoseidonChip::construct(config))
+        }
+
+        fn hash_two(
+            &self,
+            mut layouter: impl Layouter<Fp>,
+            left: AssignedCell<Fp, Fp>,
+            right: AssignedCell<Fp, Fp>,
+        ) -> Result<AssignedCell<Fp, Fp>, Error> {
+            let inputs = self.0.load_private_inputs(
+                layouter.namespace(|| "copy inputs"),
+                [left.value().copied(), right.value().copied()],
+            )?;
+            layouter.assign_region(
+                || "constrain copies",
+                |mut region| {
+                    region.constrain_equal(left.cell(), inputs[0].cell())?;
+                    region.constrain_equal(right.cell(), inputs[1].cell())
+                },
+            )?;
+            self.0.hash(layouter.namespace(|| "hash"), &inputs)
+        }
+    }
+
+    // MerkleTreeV3Circuit with the former layout of the hashes
+    struct CopyThenHashMerkleCircuit<const DEPTH: usize> {
+        leaf: Value<Fp>,
+        path_elements: [Value<Fp>; DEPTH],
+        path_indices: [Value<Fp>; DEPTH],
+    }
+
+    impl<const DEPTH: usize> Circuit<Fp> for CopyThenHashMerkleCircuit<DEPTH> {
+        type Config = MerkleTreeConfig<PoseidonConfig<Fp, 3, 2>>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                leaf: Value::unknown(),
+                path_elements: [Value::unknown(); DEPTH],
+                path_indices: [Value::unknown(); DEPTH],
             }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let col_a = meta.advice_column();
+            let col_b = meta.advice_column();
+            let col_c = meta.advice_column();
+            let instance = meta.instance_column();
+
+            MerkleTreeChip::<Fp, CopyThenHashChip>::configure(
+                meta,
+                [col_a, col_b, col_c],
+                instance,
+            )
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let chip = MerkleTreeChip::<Fp, CopyThenHashChip>::construct(config);
+            let leaf_cell = chip.assign_leaf(layouter.namespace(|| "assign leaf"), self.leaf)?;
+            chip.expose_public(layouter.namespace(|| "public leaf"), &leaf_cell, 0)?;
 
-            digest = poseidon::Hash::<_, P128Pow5T3, ConstantLength<2>, 3, 2>::init()
-                .hash(message);
+            let mut digest = leaf_cell;
+            for i in 0..DEPTH {
+                digest = chip.merkle_prove_layer(
+                    layouter.namespace(|| "merkle prove layer"),
+                    &digest,
+                    self.path_elements[i],
+                    self.path_indices[i],
+                )?;
+            }
+            chip.expose_public(layouter.namespace(|| "public root"), &digest, 1)
         }
-        return digest;
     }
 
     #[test]
-    fn test_merkle_tree_3() {
-        let leaf = 99u64;
-        let elements = vec![1u64, 5u64, 6u64, 9u64, 9u64];
-        let indices = vec![0u64, 0u64, 0u64, 0u64, 0u64];
-
-        // print leaf
-        println!("leaf: {}", leaf);
-
-        let root = compute_merkle_root(&leaf, &elements, &indices);
-
-        let leaf_fp = Value::known(Fp::from(leaf));
-        let elements_fp: Vec<Value<Fp>> = elements
-            .iter()
-            .map(|x| Value::known(Fp::from(x.to_owned())))
-            .collect();
-        let indices_fp: Vec<Value<Fp>> = indices
-            .iter()
-            .map(|x| Value::known(Fp::from(x.to_owned())))
-            .collect();
-
-        let circuit = MerkleTreeV3Circuit {
-            leaf: leaf_fp,
-            path_elements: elements_fp,
-            path_indices: indices_fp,
+    fn test_merkle_tree_3_rows_depth_20() {
+        // a tree of depth 20 has 2^20 leaves, only the path of one of them is needed to measure
+        // the circuits
+        let mut proof = MerkleProof {
+            leaf: Fp::from(99),
+            path_elements: (0..20u64).map(|i| Fp::from(i * 7 + 1)).collect(),
+            path_indices: (0..20u64).map(|i| Fp::from(i % 3 % 2)).collect(),
+            root: Fp::zero(),
         };
+        proof.root = proof.compute_root::<P128Pow5T3>();
+        let public_inputs = [vec![proof.leaf, proof.root]];
 
-        let correct_public_input = vec![Fp::from(leaf), root];
-        let valid_prover = MockProver::run(10, &circuit, vec![correct_public_input]).unwrap();
-        valid_prover.assert_satisfied();
+        let circuit = MerkleTreeV3Circuit::<Fp, P128Pow5T3, 20>::from_proof(&proof);
+        let rows = circuit_cost("merkle-tree-3", &circuit, &public_inputs)
+            .unwrap()
+            .rows;
+
+        let baseline_circuit = CopyThenHashMerkleCircuit::<20> {
+            leaf: Value::known(proof.leaf),
+            path_elements: std::array::from_fn(|i| Value::known(proof.path_elements[i])),
+            path_indices: std::array::from_fn(|i| Value::known(proof.path_indices[i])),
+        };
+        let baseline_rows = circuit_cost(
+            "merkle-tree-3-copy-then-hash",
+            &baseline_circuit,
+            &public_inputs,
+        )
+        .unwrap()
+        .rows;
 
-        let wrong_public_input = vec![Fp::from(leaf), Fp::from(0)];
+        // feeding the cells straight into the permutation saves the row of the copy region at
+        // every level
+        assert_eq!(
+            rows,
+            20 * (P128_POW5_T3_HASH_ROWS + P128_POW5_T3_HASH_CONSTANTS)
+        );
+        assert_eq!(baseline_rows, rows + 20);
+    }
+
+    #[test]
+    fn test_merkle_tree_3_index() {
+        let leaves = (0..32u64).map(|x| Fp::from(x * 3 + 99)).collect::<Vec<Fp>>();
+        let tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5);
+
+        for index in [0u64, 13, 31] {
+            let proof = tree.proof(index as usize);
+
+            // private index
+            let circuit = MerkleTreeV3IndexCircuit::<Fp, P128Pow5T3, 5, false>::from_proof(&proof);
+            let public_input = vec![proof.leaf, tree.root()];
+            let valid_prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+            valid_prover.assert_satisfied();
+
+            // public index
+            let circuit = MerkleTreeV3IndexCircuit::<Fp, P128Pow5T3, 5, true>::from_proof(&proof);
+            let public_input = vec![proof.leaf, tree.root(), Fp::from(index)];
+            let valid_prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+            valid_prover.assert_satisfied();
+
+            // the proof is bound to the position of the leaf
+            let wrong_public_input = vec![proof.leaf, tree.root(), Fp::from(index ^ 1)];
+            let invalid_prover = MockProver::run(10, &circuit, vec![wrong_public_input]).unwrap();
+            assert!(invalid_prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_merkle_tree_3_index_wrong_index() {
+        let leaves = (0..32u64).map(|x| Fp::from(x * 3 + 99)).collect::<Vec<Fp>>();
+        let tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5);
+        let proof = tree.proof(13);
+        let public_input = vec![proof.leaf, tree.root(), Fp::from(13)];
+
+        // the bits of another index lead to another root
+        let mut circuit = MerkleTreeV3IndexCircuit::<Fp, P128Pow5T3, 5, false>::from_proof(&proof);
+        circuit.leaf_index = Value::known(Fp::from(12));
+        let invalid_prover =
+            MockProver::run(10, &circuit, vec![public_input[..2].to_vec()]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+
+        // an index with the same lower bits that doesn't fit in DEPTH bits is rejected by the decomposition
+        let mut circuit = MerkleTreeV3IndexCircuit::<Fp, P128Pow5T3, 5, true>::from_proof(&proof);
+        circuit.leaf_index = Value::known(Fp::from(32 + 13));
+        let wrong_public_input = vec![proof.leaf, tree.root(), Fp::from(32 + 13)];
         let invalid_prover = MockProver::run(10, &circuit, vec![wrong_public_input]).unwrap();
         assert!(invalid_prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_merkle_tree_3_keygen() {
+        let k = 10;
+        let params: ParamsIPA<EqAffine> = ParamsIPA::new(k);
+
+        let leaves = (0..32u64).map(|x| Fp::from(x * 3 + 99)).collect::<Vec<Fp>>();
+        let tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5);
+        let circuit = MerkleTreeV3Circuit::<Fp, P128Pow5T3, 5>::from_proof(&tree.proof(7));
 
+        // key generation synthesizes the circuit without witnesses, which has the same shape as the circuit with witnesses
+        let empty_circuit = circuit.without_witnesses();
+        let vk = keygen_vk(&params, &empty_circuit).expect("keygen_vk should not fail");
+        let pk = keygen_pk(&params, vk, &empty_circuit).expect("keygen_pk should not fail");
+
+        let vk_with_witnesses = keygen_vk(&params, &circuit).expect("keygen_vk should not fail");
+        assert_eq!(
+            format!("{:?}", pk.get_vk().pinned()),
+            format!("{:?}", vk_with_witnesses.pinned())
+        );
+
+        // a different depth results in a different circuit
+        let other_tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves[..16], 4);
+        let other_circuit =
+            MerkleTreeV3Circuit::<Fp, P128Pow5T3, 4>::from_proof(&other_tree.proof(7))
+                .without_witnesses();
+        let other_vk = keygen_vk(&params, &other_circuit).expect("keygen_vk should not fail");
+        assert_ne!(
+            format!("{:?}", pk.get_vk().pinned()),
+            format!("{:?}", other_vk.pinned())
+        );
     }
-}
 
-#[cfg(feature = "dev-graph")]
-#[test]
-fn print_merkle_tree_3() {
-    use halo2_proofs::halo2curves::pasta::Fp;
-    use plotters::prelude::*;
-
-    let root =
-        BitMapBackend::new("prints/merkle-tree-3-layout.png", (1024, 3096)).into_drawing_area();
-    root.fill(&WHITE).unwrap();
-    let root = root
-        .titled("Merkle Tree 3 Layout", ("sans-serif", 60))
-        .unwrap();
-
-    let leaf = 99u64;
-    let elements = vec![1u64, 5u64, 6u64, 9u64, 9u64];
-    let indices = vec![0u64, 0u64, 0u64, 0u64, 0u64];
-    let digest: u64 = leaf + elements.iter().sum::<u64>();
-
-    let leaf_fp = Value::known(Fp::from(leaf));
-    let elements_fp: Vec<Value<Fp>> = elements
-        .iter()
-        .map(|x| Value::known(Fp::from(x.to_owned())))
-        .collect();
-    let indices_fp: Vec<Value<Fp>> = indices
-        .iter()
-        .map(|x| Value::known(Fp::from(x.to_owned())))
-        .collect();
-
-    let circuit = MerkleTreeV3Circuit {
-        leaf: leaf_fp,
-        path_elements: elements_fp,
-        path_indices: indices_fp,
-    };
+    #[test]
+    fn test_merkle_tree_3_real_prover() {
+        let leaves = (0..32u64).map(|x| Fp::from(x * 3 + 99)).collect::<Vec<Fp>>();
+        let tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5);
+        let proof = tree.proof(13);
+        let circuit = MerkleTreeV3Circuit::<Fp, P128Pow5T3, 5>::from_proof(&proof);
+        let public_inputs = vec![vec![proof.leaf, tree.root()]];
+
+        let wrong_public_inputs = vec![vec![proof.leaf, Fp::from(0)]];
+        ipa::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
 
-    halo2_proofs::dev::CircuitLayout::default()
-        .render(8, &circuit, &root)
-        .unwrap();
+    #[test]
+    fn test_merkle_tree_3_kzg() {
+        let leaves = (0..32u64).map(|x| Fr::from(x * 3 + 99)).collect::<Vec<Fr>>();
+        let tree = MerkleTree::<Fr, P128Pow5T3Bn256>::new(&leaves, 5);
+        let proof = tree.proof(13);
+        let circuit = MerkleTreeV3Circuit::<Fr, P128Pow5T3Bn256, 5>::from_proof(&proof);
+        let public_inputs = vec![vec![proof.leaf, tree.root()]];
+
+        let wrong_public_inputs = vec![vec![proof.leaf, Fr::from(0)]];
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
 }
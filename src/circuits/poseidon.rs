@@ -1,34 +1,54 @@
-use super::super::chips::poseidon::{PoseidonChip, PoseidonConfig};
+use super::super::chips::poseidon::{sponge_hash, PoseidonChip, PoseidonConfig};
+use crate::experiments::ExperimentVisitor;
 use halo2_gadgets::poseidon::primitives::*;
-use halo2_proofs::{circuit::*, plonk::*, halo2curves::pasta::Fp};
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
 use std::marker::PhantomData;
 
-struct PoseidonCircuit<
-    S: Spec<Fp, WIDTH, RATE>,
+pub struct PoseidonCircuit<
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
     const WIDTH: usize,
     const RATE: usize,
     const L: usize,
 > {
-    hash_input: [Value<Fp>; L],
-    digest: Value<Fp>,
+    hash_input: [Value<F>; L],
+    digest: Value<F>,
     _spec: PhantomData<S>,
 }
 
 impl<
-        S: Spec<Fp, WIDTH, RATE>,
+        F: FieldExt,
+        S: Spec<F, WIDTH, RATE>,
         const WIDTH: usize,
         const RATE: usize,
         const L: usize,
-    > Circuit<Fp> for PoseidonCircuit<S, WIDTH, RATE, L>
+    > PoseidonCircuit<F, S, WIDTH, RATE, L>
 {
-    type Config = PoseidonConfig<WIDTH, RATE, L>;
+    pub fn new(hash_input: [Value<F>; L], digest: Value<F>) -> Self {
+        Self {
+            hash_input,
+            digest,
+            _spec: PhantomData,
+        }
+    }
+}
+
+impl<
+        F: FieldExt,
+        S: Spec<F, WIDTH, RATE>,
+        const WIDTH: usize,
+        const RATE: usize,
+        const L: usize,
+    > Circuit<F> for PoseidonCircuit<F, S, WIDTH, RATE, L>
+{
+    type Config = PoseidonConfig<F, WIDTH, RATE>;
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
         Self {
             hash_input: (0..L)
                 .map(|_i| Value::unknown())
-                .collect::<Vec<Value<Fp>>>()
+                .collect::<Vec<Value<F>>>()
                 .try_into()
                 .unwrap(),
             digest: Value::unknown(),
@@ -36,11 +56,11 @@ impl<
         }
     }
 
-    fn configure(meta: &mut ConstraintSystem<Fp>) -> PoseidonConfig<WIDTH, RATE, L> {
+    fn configure(meta: &mut ConstraintSystem<F>) -> PoseidonConfig<F, WIDTH, RATE> {
         let instance = meta.instance_column();
         let hash_inputs = (0..WIDTH).map(|_| meta.advice_column()).collect::<Vec<_>>();
 
-        PoseidonChip::<S, WIDTH, RATE, L>::configure(
+        PoseidonChip::<F, S, WIDTH, RATE, L>::configure(
             meta,
             hash_inputs,
             instance,
@@ -49,10 +69,10 @@ impl<
 
     fn synthesize(
         &self,
-        config: PoseidonConfig<WIDTH, RATE, L>,
-        mut layouter: impl Layouter<Fp>,
+        config: PoseidonConfig<F, WIDTH, RATE>,
+        mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        let poseidon_chip = PoseidonChip::<S, WIDTH, RATE, L>::construct(config);
+        let poseidon_chip = PoseidonChip::<F, S, WIDTH, RATE, L>::construct(config);
         let assigned_input_cells = poseidon_chip
             .load_private_inputs(layouter.namespace(|| "load private inputs"), self.hash_input)?;
         let digest = poseidon_chip.hash(layouter.namespace(|| "poseidon chip"), &assigned_input_cells)?;
@@ -61,12 +81,242 @@ impl<
     }
 }
 
+// Same as PoseidonCircuit, but the digest is the one of circomlib poseidon, which hashes exactly RATE inputs
+pub struct PoseidonCircomlibCircuit<
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
+    const WIDTH: usize,
+    const RATE: usize,
+> {
+    hash_input: [Value<F>; RATE],
+    digest: Value<F>,
+    _spec: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, WIDTH, RATE>, const WIDTH: usize, const RATE: usize>
+    PoseidonCircomlibCircuit<F, S, WIDTH, RATE>
+{
+    pub fn new(hash_input: [Value<F>; RATE], digest: Value<F>) -> Self {
+        Self {
+            hash_input,
+            digest,
+            _spec: PhantomData,
+        }
+    }
+}
+
+impl<F: FieldExt, S: Spec<F, WIDTH, RATE>, const WIDTH: usize, const RATE: usize> Circuit<F>
+    for PoseidonCircomlibCircuit<F, S, WIDTH, RATE>
+{
+    type Config = PoseidonConfig<F, WIDTH, RATE>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new([Value::unknown(); RATE], Value::unknown())
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> PoseidonConfig<F, WIDTH, RATE> {
+        let instance = meta.instance_column();
+        let hash_inputs = (0..WIDTH).map(|_| meta.advice_column()).collect::<Vec<_>>();
+
+        PoseidonChip::<F, S, WIDTH, RATE, RATE>::configure(meta, hash_inputs, instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: PoseidonConfig<F, WIDTH, RATE>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let poseidon_chip = PoseidonChip::<F, S, WIDTH, RATE, RATE>::construct(config);
+        let assigned_input_cells = poseidon_chip
+            .load_private_inputs(layouter.namespace(|| "load private inputs"), self.hash_input)?;
+        let digest = poseidon_chip
+            .hash_circomlib(layouter.namespace(|| "poseidon circomlib"), &assigned_input_cells)?;
+        poseidon_chip.expose_public(layouter.namespace(|| "expose result"), &digest, 0)?;
+        Ok(())
+    }
+}
+
+// Same as PoseidonCircuit, but the number of inputs is only known when the circuit is instantiated
+struct PoseidonSpongeCircuit<
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
+    const WIDTH: usize,
+    const RATE: usize,
+> {
+    hash_input: Vec<Value<F>>,
+    digest: Value<F>,
+    _spec: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, WIDTH, RATE>, const WIDTH: usize, const RATE: usize> Circuit<F>
+    for PoseidonSpongeCircuit<F, S, WIDTH, RATE>
+{
+    type Config = PoseidonConfig<F, WIDTH, RATE>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            hash_input: vec![Value::unknown(); self.hash_input.len()],
+            digest: Value::unknown(),
+            _spec: PhantomData,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> PoseidonConfig<F, WIDTH, RATE> {
+        let instance = meta.instance_column();
+        let hash_inputs = (0..WIDTH).map(|_| meta.advice_column()).collect::<Vec<_>>();
+
+        PoseidonChip::<F, S, WIDTH, RATE, 0>::configure(meta, hash_inputs, instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: PoseidonConfig<F, WIDTH, RATE>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let poseidon_chip = PoseidonChip::<F, S, WIDTH, RATE, 0>::construct(config);
+        let assigned_input_cells = poseidon_chip.load_private_sponge_inputs(
+            layouter.namespace(|| "load private inputs"),
+            &self.hash_input,
+        )?;
+        let digest = poseidon_chip
+            .hash_sponge(layouter.namespace(|| "poseidon sponge"), &assigned_input_cells)?;
+        poseidon_chip.expose_public(layouter.namespace(|| "expose result"), &digest, 0)?;
+        Ok(())
+    }
+}
+
+// Hashes many tuples of L inputs, either with a single batched call or with one hash call per tuple
+struct PoseidonBatchCircuit<
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
+    const WIDTH: usize,
+    const RATE: usize,
+    const L: usize,
+> {
+    hash_inputs: Vec<[Value<F>; L]>,
+    batched: bool,
+    _spec: PhantomData<S>,
+}
+
+impl<
+        F: FieldExt,
+        S: Spec<F, WIDTH, RATE>,
+        const WIDTH: usize,
+        const RATE: usize,
+        const L: usize,
+    > Circuit<F> for PoseidonBatchCircuit<F, S, WIDTH, RATE, L>
+{
+    type Config = PoseidonConfig<F, WIDTH, RATE>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            hash_inputs: vec![[Value::unknown(); L]; self.hash_inputs.len()],
+            batched: self.batched,
+            _spec: PhantomData,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> PoseidonConfig<F, WIDTH, RATE> {
+        let instance = meta.instance_column();
+        let hash_inputs = (0..WIDTH).map(|_| meta.advice_column()).collect::<Vec<_>>();
+
+        PoseidonChip::<F, S, WIDTH, RATE, L>::configure(meta, hash_inputs, instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: PoseidonConfig<F, WIDTH, RATE>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let poseidon_chip = PoseidonChip::<F, S, WIDTH, RATE, L>::construct(config);
+
+        let mut assigned_input_cells = Vec::with_capacity(self.hash_inputs.len());
+        for hash_input in self.hash_inputs.iter() {
+            assigned_input_cells.push(poseidon_chip.load_private_inputs(
+                layouter.namespace(|| "load private inputs"),
+                *hash_input,
+            )?);
+        }
+
+        let digests = if self.batched {
+            poseidon_chip.hash_batch(layouter.namespace(|| "poseidon batch"), &assigned_input_cells)?
+        } else {
+            let mut digests = Vec::with_capacity(assigned_input_cells.len());
+            for input_cells in assigned_input_cells.iter() {
+                digests.push(
+                    poseidon_chip.hash(layouter.namespace(|| "poseidon chip"), input_cells)?,
+                );
+            }
+            digests
+        };
+
+        for (i, digest) in digests.iter().enumerate() {
+            poseidon_chip.expose_public(layouter.namespace(|| "expose result"), digest, i)?;
+        }
+        Ok(())
+    }
+}
+
+// Instances used by the experiments registry: a hash of 3 inputs, the sponge over 7 inputs and a batch of 24 hashes
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let hash_input = [Fp::from(99), Fp::from(99), Fp::from(99)];
+    let digest = Hash::<_, P128Pow5T3, ConstantLength<3>, 3, 2>::init().hash(hash_input);
+    let circuit = PoseidonCircuit::<Fp, P128Pow5T3, 3, 2, 3>::new(
+        hash_input.map(Value::known),
+        Value::known(digest),
+    );
+    visitor.visit("poseidon", &circuit, &[vec![digest]]);
+
+    let hash_input = (1..=7u64).map(Fp::from).collect::<Vec<Fp>>();
+    let digest = sponge_hash::<_, P128Pow5T3, 3, 2>(&hash_input);
+    let circuit = PoseidonSpongeCircuit::<Fp, P128Pow5T3, 3, 2> {
+        hash_input: hash_input.iter().map(|x| Value::known(*x)).collect(),
+        digest: Value::known(digest),
+        _spec: PhantomData,
+    };
+    visitor.visit("poseidon-sponge", &circuit, &[vec![digest]]);
+
+    let hash_inputs = (0..24u64)
+        .map(|i| [Fp::from(i), Fp::from(i + 1)])
+        .collect::<Vec<[Fp; 2]>>();
+    let digests = hash_inputs
+        .iter()
+        .map(|x| Hash::<_, P128Pow5T3, ConstantLength<2>, 3, 2>::init().hash(*x))
+        .collect::<Vec<Fp>>();
+    let circuit = PoseidonBatchCircuit::<Fp, P128Pow5T3, 3, 2, 2> {
+        hash_inputs: hash_inputs.iter().map(|x| x.map(Value::known)).collect(),
+        batched: true,
+        _spec: PhantomData,
+    };
+    visitor.visit("poseidon-batch", &circuit, &[digests]);
+}
+
 #[cfg(test)]
 mod tests {
     use std::marker::PhantomData;
-    use super::PoseidonCircuit;
-    use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, P128Pow5T3};
-    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+    use super::{
+        PoseidonBatchCircuit, PoseidonCircomlibCircuit, PoseidonCircuit, PoseidonSpongeCircuit,
+    };
+    use crate::chips::poseidon::{
+        circomlib_hash, sponge_hash, P128_POW5_T3_HASH_CONSTANTS, P128_POW5_T3_HASH_ROWS,
+        P128_POW5_T3_PERMUTATION_ROWS,
+    };
+    use crate::chips::poseidon_spec::P128Pow5T3Bn256;
+    use crate::cost::circuit_cost;
+    use crate::proving::{ipa, kzg};
+    use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, P128Pow5T3, Spec};
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{
+            group::ff::{Field, PrimeField},
+            bn256::Fr,
+            pasta::{Fp, Fq},
+        },
+    };
     #[test]
     fn test_poseidon() {
         let input = 99u64;
@@ -81,7 +331,7 @@ mod tests {
         // print output
         println!("output: {:?}", digest);
 
-        let circuit = PoseidonCircuit::<P128Pow5T3, 3, 2, 3> {
+        let circuit = PoseidonCircuit::<Fp, P128Pow5T3, 3, 2, 3> {
             hash_input: hash_input.map(Value::known),
             digest: Value::known(digest),
             _spec: PhantomData,
@@ -91,31 +341,245 @@ mod tests {
         prover.assert_satisfied();
     }
 
-    #[cfg(feature = "dev-graph")]
     #[test]
-    fn print_poseidon() {
-        use halo2_proofs::halo2curves::pasta::Fp;
-        use plotters::prelude::*;
+    fn test_poseidon_fq() {
+        // same circuit instantiated over the scalar field of vesta
+        let input = 99u64;
+        let hash_input = [Fq::from(input), Fq::from(input), Fq::from(input)];
 
-        let root =
-            BitMapBackend::new("prints/poseidon-layout.png", (1024, 3096)).into_drawing_area();
-        root.fill(&WHITE).unwrap();
-        let root = root.titled("Posiedon Layout", ("sans-serif", 60)).unwrap();
+        let digest =
+            poseidon::Hash::<_, P128Pow5T3, ConstantLength<3>, 3, 2>::init().hash(hash_input);
 
-        let input = 99u64;
-        let hash_input = [Fp::from(input), Fp::from(input), Fp::from(input)];
+        let circuit = PoseidonCircuit::<Fq, P128Pow5T3, 3, 2, 3> {
+            hash_input: hash_input.map(Value::known),
+            digest: Value::known(digest),
+            _spec: PhantomData,
+        };
+        let public_input = vec![digest];
+        let prover = MockProver::run(7, &circuit, vec![public_input]).unwrap();
+        prover.assert_satisfied();
+    }
+
+    #[test]
+    fn test_poseidon_bn256() {
+        use crate::chips::poseidon_spec::P128Pow5T5Bn256;
 
+        // width 3, rate 2
+        let hash_input = [Fr::from(1), Fr::from(2)];
+        let digest =
+            poseidon::Hash::<_, P128Pow5T3Bn256, ConstantLength<2>, 3, 2>::init().hash(hash_input);
+
+        let circuit = PoseidonCircuit::<Fr, P128Pow5T3Bn256, 3, 2, 2> {
+            hash_input: hash_input.map(Value::known),
+            digest: Value::known(digest),
+            _spec: PhantomData,
+        };
+        let prover = MockProver::run(7, &circuit, vec![vec![digest]]).unwrap();
+        prover.assert_satisfied();
+
+        // width 5, rate 4
+        let hash_input = [Fr::from(1), Fr::from(2), Fr::from(3), Fr::from(4)];
+        let digest =
+            poseidon::Hash::<_, P128Pow5T5Bn256, ConstantLength<4>, 5, 4>::init().hash(hash_input);
+
+        let circuit = PoseidonCircuit::<Fr, P128Pow5T5Bn256, 5, 4, 4> {
+            hash_input: hash_input.map(Value::known),
+            digest: Value::known(digest),
+            _spec: PhantomData,
+        };
+        let prover = MockProver::run(7, &circuit, vec![vec![digest]]).unwrap();
+        prover.assert_satisfied();
+    }
+
+    #[test]
+    fn test_poseidon_circomlib() {
+        use crate::chips::poseidon_spec::P128Pow5T5Bn256;
+
+        // circomlib poseidon([1, 2]), computed with R_P = 57 partial rounds
+        let hash_input = [Fr::from(1), Fr::from(2)];
+        let digest = Fr::from_str_vartime(
+            "7853200120776062878684798364095072458815029376092732009249414926327459813530",
+        )
+        .unwrap();
+        assert_eq!(circomlib_hash::<_, P128Pow5T3Bn256, 3, 2>(&hash_input), digest);
+
+        let circuit = PoseidonCircomlibCircuit::<Fr, P128Pow5T3Bn256, 3, 2>::new(
+            hash_input.map(Value::known),
+            Value::known(digest),
+        );
+        let prover = MockProver::run(7, &circuit, vec![vec![digest]]).unwrap();
+        prover.assert_satisfied();
+
+        // the ConstantLength digest of the same inputs is rejected
+        let constant_length =
+            poseidon::Hash::<_, P128Pow5T3Bn256, ConstantLength<2>, 3, 2>::init().hash(hash_input);
+        let prover = MockProver::run(7, &circuit, vec![vec![constant_length]]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // circomlib poseidon([1, 2, 3, 4]), computed with R_P = 60 partial rounds
+        let hash_input = [Fr::from(1), Fr::from(2), Fr::from(3), Fr::from(4)];
+        let digest = Fr::from_str_vartime(
+            "18821383157269793795438455681495246036402687001665670618754263018637548127333",
+        )
+        .unwrap();
+
+        let circuit = PoseidonCircomlibCircuit::<Fr, P128Pow5T5Bn256, 5, 4>::new(
+            hash_input.map(Value::known),
+            Value::known(digest),
+        );
+        let prover = MockProver::run(7, &circuit, vec![vec![digest]]).unwrap();
+        prover.assert_satisfied();
+
+        let prover = MockProver::run(7, &circuit, vec![vec![digest + Fr::one()]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_poseidon_circomlib_kzg() {
+        let hash_input = [Fr::from(1), Fr::from(2)];
+        let digest = Fr::from_str_vartime(
+            "7853200120776062878684798364095072458815029376092732009249414926327459813530",
+        )
+        .unwrap();
+        let circuit = PoseidonCircomlibCircuit::<Fr, P128Pow5T3Bn256, 3, 2>::new(
+            hash_input.map(Value::known),
+            Value::known(digest),
+        );
+        let public_inputs = vec![vec![digest]];
+
+        let wrong_public_inputs = vec![vec![digest + Fr::one()]];
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
+
+    #[test]
+    fn test_poseidon_batch() {
+        let k = 10;
+
+        let hash_inputs = (0..24u64)
+            .map(|i| [Fp::from(i), Fp::from(i + 1)])
+            .collect::<Vec<[Fp; 2]>>();
+        let digests = hash_inputs
+            .iter()
+            .map(|x| poseidon::Hash::<_, P128Pow5T3, ConstantLength<2>, 3, 2>::init().hash(*x))
+            .collect::<Vec<Fp>>();
+
+        let batched_circuit = PoseidonBatchCircuit::<Fp, P128Pow5T3, 3, 2, 2> {
+            hash_inputs: hash_inputs.iter().map(|x| x.map(Value::known)).collect(),
+            batched: true,
+            _spec: PhantomData,
+        };
+        let prover = MockProver::run(k, &batched_circuit, vec![digests.clone()]).unwrap();
+        prover.assert_satisfied();
+
+        let wrong_digests = digests.iter().rev().cloned().collect::<Vec<Fp>>();
+        let prover = MockProver::run(k, &batched_circuit, vec![wrong_digests]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // Each single call lays out its own initial state (and assigns its own constants) on top of the permutation.
+        // Every tuple is loaded in 1 row, then a batched tuple only takes the rows of its permutation, plus 1 row and
+        // 2 constants (padding and capacity) shared by the batch, which no longer fits in 2^10 rows when hashed one by one
+        let single_circuit = PoseidonBatchCircuit::<Fp, P128Pow5T3, 3, 2, 2> {
+            hash_inputs: hash_inputs.iter().map(|x| x.map(Value::known)).collect(),
+            batched: false,
+            _spec: PhantomData,
+        };
+        let rows = |circuit: &PoseidonBatchCircuit<Fp, P128Pow5T3, 3, 2, 2>| {
+            circuit_cost("poseidon-batch", circuit, &[digests.clone()])
+                .unwrap()
+                .rows
+        };
+        const LOAD_ROWS: usize = 1;
+        const BATCH_SHARED_ROWS: usize = 1;
+        const BATCH_SHARED_CONSTANTS: usize = 2;
+        assert_eq!(
+            P128_POW5_T3_PERMUTATION_ROWS,
+            <P128Pow5T3 as Spec<Fp, 3, 2>>::full_rounds()
+                + <P128Pow5T3 as Spec<Fp, 3, 2>>::partial_rounds() / 2
+                + 1
+        );
+        assert_eq!(
+            rows(&batched_circuit),
+            24 * (LOAD_ROWS + P128_POW5_T3_PERMUTATION_ROWS)
+                + BATCH_SHARED_ROWS
+                + BATCH_SHARED_CONSTANTS
+        );
+        assert_eq!(
+            rows(&single_circuit),
+            24 * (LOAD_ROWS + P128_POW5_T3_HASH_ROWS) + 24 * P128_POW5_T3_HASH_CONSTANTS
+        );
+        assert!(MockProver::run(k, &single_circuit, vec![digests.clone()]).is_err());
+
+        let prover = MockProver::run(k + 1, &single_circuit, vec![digests]).unwrap();
+        prover.assert_satisfied();
+    }
+
+    #[test]
+    fn test_poseidon_sponge() {
+        // 0 inputs: a single block of padding, 3 inputs: the padding completes the second block,
+        // 4 inputs: the padding takes a whole extra block, 7 inputs: 4 permutations
+        for len in [0u64, 1, 3, 4, 7] {
+            let hash_input = (0..len).map(|i| Fp::from(i + 1)).collect::<Vec<Fp>>();
+            let digest = sponge_hash::<_, P128Pow5T3, 3, 2>(&hash_input);
+
+            let circuit = PoseidonSpongeCircuit::<Fp, P128Pow5T3, 3, 2> {
+                hash_input: hash_input.iter().map(|x| Value::known(*x)).collect(),
+                digest: Value::known(digest),
+                _spec: PhantomData,
+            };
+
+            let prover = MockProver::run(8, &circuit, vec![vec![digest]]).unwrap();
+            prover.assert_satisfied();
+
+            let prover = MockProver::run(8, &circuit, vec![vec![digest + Fp::one()]]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_poseidon_sponge_domain_separation() {
+        let message = [Fp::from(1), Fp::from(2)];
+
+        // the variable length and constant length hashes of the same message differ
+        let constant_length =
+            poseidon::Hash::<_, P128Pow5T3, ConstantLength<2>, 3, 2>::init().hash(message);
+        assert_ne!(sponge_hash::<_, P128Pow5T3, 3, 2>(&message), constant_length);
+
+        // appending zeros changes the digest thanks to the padding
+        assert_ne!(
+            sponge_hash::<_, P128Pow5T3, 3, 2>(&message),
+            sponge_hash::<_, P128Pow5T3, 3, 2>(&[message[0], message[1], Fp::zero()])
+        );
+    }
+
+    #[test]
+    fn test_poseidon_real_prover() {
+        let hash_input = [Fp::from(99), Fp::from(100), Fp::from(101)];
         let digest =
             poseidon::Hash::<_, P128Pow5T3, ConstantLength<3>, 3, 2>::init().hash(hash_input);
+        let circuit = PoseidonCircuit::<Fp, P128Pow5T3, 3, 2, 3> {
+            hash_input: hash_input.map(Value::known),
+            digest: Value::known(digest),
+            _spec: PhantomData,
+        };
+
+        let public_inputs = vec![vec![digest]];
+        let wrong_public_inputs = vec![vec![digest + Fp::one()]];
+        ipa::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+    }
 
-        let circuit = PoseidonCircuit::<P128Pow5T3, 3, 2, 3> {
-            hash_input: hash_input.map(|x| Value::known(x)),
+    #[test]
+    fn test_poseidon_kzg() {
+        let hash_input = [Fr::from(99), Fr::from(100), Fr::from(101)];
+        let digest =
+            poseidon::Hash::<_, P128Pow5T3Bn256, ConstantLength<3>, 3, 2>::init().hash(hash_input);
+        let circuit = PoseidonCircuit::<Fr, P128Pow5T3Bn256, 3, 2, 3> {
+            hash_input: hash_input.map(Value::known),
             digest: Value::known(digest),
             _spec: PhantomData,
         };
+        let public_inputs = vec![vec![digest]];
 
-        halo2_proofs::dev::CircuitLayout::default()
-            .render(7, &circuit, &root)
-            .unwrap();
+        let wrong_public_inputs = vec![vec![digest + Fr::one()]];
+        kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
     }
 }
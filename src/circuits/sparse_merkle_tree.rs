@@ -0,0 +1,201 @@
+use super::super::chips::sparse_merkle_tree::{SparseMerkleTreeChip, SparseMerkleTreeConfig};
+use crate::experiments::ExperimentVisitor;
+use crate::merkle_tree::{SparseMerkleProof, SparseMerkleTree};
+use halo2_gadgets::poseidon::primitives::{P128Pow5T3, Spec};
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, halo2curves::pasta::Fp, plonk::*};
+use std::marker::PhantomData;
+
+// Proves that the key is in the sparse Merkle tree (value is Some) or that it is not (value is None).
+// The public inputs are the key (row 0) and the root (row 1), the value of a key in the tree stays private.
+// Whether the circuit proves membership or non-membership is part of its shape, like the depth
+pub struct SparseMerkleTreeCircuit<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> {
+    pub key: Value<F>,
+    pub value: Option<Value<F>>,
+    pub path_elements: [Value<F>; DEPTH],
+    _spec: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> SparseMerkleTreeCircuit<F, S, DEPTH> {
+    // Builds the circuit out of a proof generated by merkle_tree::SparseMerkleTree, the tree must have depth DEPTH
+    pub fn from_proof(proof: &SparseMerkleProof<F>) -> Self {
+        assert_eq!(
+            proof.path_elements.len(),
+            DEPTH,
+            "the proof has {} levels, the circuit expects {}",
+            proof.path_elements.len(),
+            DEPTH
+        );
+        Self {
+            key: Value::known(proof.key),
+            value: proof.value.map(Value::known),
+            path_elements: std::array::from_fn(|i| Value::known(proof.path_elements[i])),
+            _spec: PhantomData,
+        }
+    }
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>, const DEPTH: usize> Circuit<F>
+    for SparseMerkleTreeCircuit<F, S, DEPTH>
+{
+    type Config = SparseMerkleTreeConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            key: Value::unknown(),
+            value: self.value.map(|_| Value::unknown()),
+            path_elements: [Value::unknown(); DEPTH],
+            _spec: PhantomData,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+
+        SparseMerkleTreeChip::<F, S, DEPTH>::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = SparseMerkleTreeChip::<F, S, DEPTH>::construct(config);
+
+        let (key_cell, key_bits) =
+            chip.assign_key(layouter.namespace(|| "assign key"), self.key)?;
+        chip.expose_public(layouter.namespace(|| "public key"), &key_cell, 0)?;
+
+        // the leaf at the position of the key is H(key, value) if the key is in the tree and zero otherwise
+        let leaf_cell = match self.value {
+            Some(value) => {
+                chip.assign_leaf(layouter.namespace(|| "assign leaf"), &key_cell, value)?
+            }
+            None => chip.assign_empty_leaf(layouter.namespace(|| "assign empty leaf"))?,
+        };
+
+        let root_cell = chip.compute_root(
+            layouter.namespace(|| "compute root"),
+            &leaf_cell,
+            &key_bits,
+            &self.path_elements,
+        )?;
+        chip.expose_public(layouter.namespace(|| "public root"), &root_cell, 1)?;
+        Ok(())
+    }
+}
+
+// Tree of depth 8 containing 4 keys, used by the experiments registry and by the tests
+fn tree<F: FieldExt, S: Spec<F, 3, 2>>() -> SparseMerkleTree<F, S> {
+    let mut tree = SparseMerkleTree::<F, S>::new(8);
+    for key in [3u64, 42, 200, 255] {
+        tree.insert(F::from(key), F::from(key * 1000));
+    }
+    tree
+}
+
+// Instances used by the experiments registry, the membership of a key of the tree and the non-membership of another
+pub(crate) fn experiment<V: ExperimentVisitor>(visitor: &mut V) {
+    let tree = tree::<Fp, P128Pow5T3>();
+
+    let proof = tree.proof(Fp::from(42));
+    let circuit = SparseMerkleTreeCircuit::<Fp, P128Pow5T3, 8>::from_proof(&proof);
+    visitor.visit(
+        "sparse-merkle-tree-membership",
+        &circuit,
+        &[vec![proof.key, tree.root()]],
+    );
+
+    let proof = tree.proof(Fp::from(43));
+    let circuit = SparseMerkleTreeCircuit::<Fp, P128Pow5T3, 8>::from_proof(&proof);
+    visitor.visit(
+        "sparse-merkle-tree-non-membership",
+        &circuit,
+        &[vec![proof.key, tree.root()]],
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tree, SparseMerkleTreeCircuit};
+    use crate::chips::poseidon_spec::P128Pow5T3Bn256;
+    use crate::proving::kzg;
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+    };
+
+    #[test]
+    fn test_sparse_merkle_tree() {
+        let tree = tree::<Fp, P128Pow5T3>();
+
+        // keys in the tree and keys that are not
+        for key in [3u64, 42, 255, 0, 43, 254] {
+            let proof = tree.proof(Fp::from(key));
+            let circuit = SparseMerkleTreeCircuit::<Fp, P128Pow5T3, 8>::from_proof(&proof);
+
+            let correct_public_input = vec![proof.key, tree.root()];
+            let valid_prover = MockProver::run(11, &circuit, vec![correct_public_input]).unwrap();
+            valid_prover.assert_satisfied();
+
+            let wrong_public_input = vec![proof.key, Fp::from(0)];
+            let invalid_prover = MockProver::run(11, &circuit, vec![wrong_public_input]).unwrap();
+            assert!(invalid_prover.verify().is_err());
+
+            // the proof is bound to the key: the same path doesn't work for a different key
+            let other_key_public_input = vec![proof.key + Fp::from(1), tree.root()];
+            let invalid_prover =
+                MockProver::run(11, &circuit, vec![other_key_public_input]).unwrap();
+            assert!(invalid_prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_sparse_merkle_tree_wrong_proofs() {
+        let tree = tree::<Fp, P128Pow5T3>();
+
+        // a key in the tree cannot be proven to be absent
+        let mut proof = tree.proof(Fp::from(42));
+        proof.value = None;
+        let circuit = SparseMerkleTreeCircuit::<Fp, P128Pow5T3, 8>::from_proof(&proof);
+        let invalid_prover =
+            MockProver::run(11, &circuit, vec![vec![proof.key, tree.root()]]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+
+        // a key that is not in the tree cannot be proven to be present
+        let mut proof = tree.proof(Fp::from(43));
+        proof.value = Some(Fp::from(43000));
+        let circuit = SparseMerkleTreeCircuit::<Fp, P128Pow5T3, 8>::from_proof(&proof);
+        let invalid_prover =
+            MockProver::run(11, &circuit, vec![vec![proof.key, tree.root()]]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+
+        // a key larger than 2^DEPTH cannot use the path of the key with the same lower bits
+        let proof = tree.proof(Fp::from(43));
+        let mut circuit = SparseMerkleTreeCircuit::<Fp, P128Pow5T3, 8>::from_proof(&proof);
+        let large_key = Fp::from(256 + 43);
+        circuit.key = Value::known(large_key);
+        let invalid_prover =
+            MockProver::run(11, &circuit, vec![vec![large_key, tree.root()]]).unwrap();
+        assert!(invalid_prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_sparse_merkle_tree_kzg() {
+        let tree = tree::<Fr, P128Pow5T3Bn256>();
+
+        for key in [42u64, 43] {
+            let proof = tree.proof(Fr::from(key));
+            let circuit = SparseMerkleTreeCircuit::<Fr, P128Pow5T3Bn256, 8>::from_proof(&proof);
+            let public_inputs = vec![vec![proof.key, tree.root()]];
+
+            let wrong_public_inputs = vec![vec![proof.key, Fr::from(0)]];
+            kzg::assert_roundtrip(&circuit, &public_inputs, &wrong_public_inputs);
+        }
+    }
+}
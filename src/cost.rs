@@ -0,0 +1,322 @@
+/*
+Cost report of a circuit, used to compare the designs of the experiments. The shape of the circuit (columns, gates,
+degree) is read from its constraint system, the rows are counted by laying out the circuit, while the size of the
+proof and the time taken by the prover are measured by creating an actual proof with the IPA backend.
+*/
+
+use crate::{
+    experiments::ExperimentVisitor,
+    proving::{ipa, min_k, MinKError},
+};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::Value,
+    halo2curves::pasta::Fp,
+    plonk::{
+        Advice, Any, Assigned, Assignment, Challenge, Circuit, Column, ConstraintSystem, Error,
+        Fixed, FloorPlanner, Instance, Selector,
+    },
+};
+use serde::Serialize;
+use std::{fmt, time::Instant};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitCost {
+    pub name: String,
+    // smallest k such that the circuit fits in 2^k rows
+    pub k: u32,
+    pub advice_columns: usize,
+    // selectors are not included, they are turned into fixed columns during key generation
+    pub fixed_columns: usize,
+    pub instance_columns: usize,
+    pub selectors: usize,
+    pub gates: usize,
+    // number of polynomial constraints, a gate can contain many of them
+    pub constraints: usize,
+    pub lookups: usize,
+    pub max_degree: usize,
+    // columns with equality enabled
+    pub permutation_columns: usize,
+    // rows used by the regions and the lookup tables, the blinding rows are not included
+    pub rows: usize,
+    pub public_inputs: usize,
+    // the proof size and the timings are measured with the IPA backend over the pasta curves (proving::ipa), a KZG
+    // proof over BN254 has another size and other timings
+    pub proof_size: usize,
+    pub keygen_time_ms: u128,
+    pub prover_time_ms: u128,
+    pub verifier_time_ms: u128,
+}
+
+#[derive(Debug)]
+pub enum CostError {
+    MinK(MinKError),
+    Proving(Error),
+}
+
+impl fmt::Display for CostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MinK(e) => write!(f, "{}", e),
+            Self::Proving(e) => write!(f, "proving error: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for CostError {}
+
+impl From<MinKError> for CostError {
+    fn from(e: MinKError) -> Self {
+        Self::MinK(e)
+    }
+}
+
+impl From<Error> for CostError {
+    fn from(e: Error) -> Self {
+        Self::Proving(e)
+    }
+}
+
+// Measures the cost of a circuit. The public inputs must be valid, since a proof is created and verified
+pub fn circuit_cost<C: Circuit<Fp>>(
+    name: &str,
+    circuit: &C,
+    public_inputs: &[Vec<Fp>],
+) -> Result<CircuitCost, CostError> {
+    let mut cs = ConstraintSystem::default();
+    let config = C::configure(&mut cs);
+
+    let mut row_counter = RowCounter { rows: 0 };
+    C::FloorPlanner::synthesize(&mut row_counter, circuit, config, cs.constants().clone())?;
+
+    let k = min_k(circuit, public_inputs)?;
+    let params = ipa::setup(k);
+
+    let start = Instant::now();
+    let pk = ipa::keys(&params, circuit)?;
+    let keygen_time_ms = start.elapsed().as_millis();
+
+    let start = Instant::now();
+    let proof = ipa::full_prover(&params, &pk, circuit, public_inputs)?;
+    let prover_time_ms = start.elapsed().as_millis();
+
+    let start = Instant::now();
+    ipa::full_verifier(&params, pk.get_vk(), &proof, public_inputs)?;
+    let verifier_time_ms = start.elapsed().as_millis();
+
+    Ok(CircuitCost {
+        name: name.to_string(),
+        k,
+        advice_columns: cs.num_advice_columns(),
+        fixed_columns: cs.num_fixed_columns(),
+        instance_columns: cs.num_instance_columns(),
+        selectors: cs.num_selectors(),
+        gates: cs.gates().len(),
+        constraints: cs.gates().iter().map(|gate| gate.polynomials().len()).sum(),
+        lookups: cs.lookups().len(),
+        max_degree: cs.degree(),
+        permutation_columns: cs.permutation().get_columns().len(),
+        rows: row_counter.rows,
+        public_inputs: public_inputs.iter().map(|column| column.len()).sum(),
+        proof_size: proof.len(),
+        keygen_time_ms,
+        prover_time_ms,
+        verifier_time_ms,
+    })
+}
+
+// Measures the cost of the experiments, all of them if names is empty
+pub struct CostVisitor {
+    names: Vec<String>,
+    pub costs: Vec<CircuitCost>,
+    pub errors: Vec<(String, CostError)>,
+}
+
+impl CostVisitor {
+    pub fn new(names: Vec<String>) -> Self {
+        Self {
+            names,
+            costs: vec![],
+            errors: vec![],
+        }
+    }
+}
+
+impl ExperimentVisitor for CostVisitor {
+    fn visit<C: Circuit<Fp>>(&mut self, name: &str, circuit: &C, public_inputs: &[Vec<Fp>]) {
+        if !self.names.is_empty() && !self.names.iter().any(|n| n == name) {
+            return;
+        }
+        match circuit_cost(name, circuit, public_inputs) {
+            Ok(cost) => self.costs.push(cost),
+            Err(e) => self.errors.push((name.to_string(), e)),
+        }
+    }
+}
+
+// Lays out the circuit without storing anything, only the last row used is kept
+struct RowCounter {
+    rows: usize,
+}
+
+impl RowCounter {
+    fn use_row(&mut self, row: usize) {
+        self.rows = self.rows.max(row + 1);
+    }
+}
+
+impl<F: FieldExt> Assignment<F> for RowCounter {
+    fn enter_region<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn exit_region(&mut self) {}
+
+    fn enable_selector<A, AR>(&mut self, _: A, _: &Selector, row: usize) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.use_row(row);
+        Ok(())
+    }
+
+    fn annotate_column<A, AR>(&mut self, _: A, _: Column<Any>)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+    }
+
+    fn query_instance(&self, _: Column<Instance>, _: usize) -> Result<Value<F>, Error> {
+        Ok(Value::unknown())
+    }
+
+    fn assign_advice<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        _: Column<Advice>,
+        row: usize,
+        _: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.use_row(row);
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        _: Column<Fixed>,
+        row: usize,
+        _: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.use_row(row);
+        Ok(())
+    }
+
+    // the cells of the instance columns are counted as well, since they are copied from the regions
+    fn copy(
+        &mut self,
+        _: Column<Any>,
+        left_row: usize,
+        _: Column<Any>,
+        right_row: usize,
+    ) -> Result<(), Error> {
+        self.use_row(left_row);
+        self.use_row(right_row);
+        Ok(())
+    }
+
+    // the rows filled after the end of the lookup tables are not used by the circuit
+    fn fill_from_row(
+        &mut self,
+        _: Column<Fixed>,
+        _: usize,
+        _: Value<Assigned<F>>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn get_challenge(&self, _: Challenge) -> Value<F> {
+        Value::unknown()
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _: Option<String>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{circuit_cost, CostVisitor};
+    use crate::circuits::poseidon::PoseidonCircuit;
+    use crate::experiments::{experiment_names, visit_experiments};
+    use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, P128Pow5T3};
+    use halo2_proofs::{circuit::Value, halo2curves::pasta::Fp};
+
+    #[test]
+    fn test_circuit_cost() {
+        let hash_input = [Fp::from(1), Fp::from(2)];
+        let digest =
+            poseidon::Hash::<_, P128Pow5T3, ConstantLength<2>, 3, 2>::init().hash(hash_input);
+        let circuit = PoseidonCircuit::<Fp, P128Pow5T3, 3, 2, 2>::new(
+            hash_input.map(Value::known),
+            Value::known(digest),
+        );
+
+        let cost = circuit_cost("poseidon", &circuit, &[vec![digest]]).unwrap();
+        // the inputs and the state of the permutation, plus the partial sbox column
+        assert_eq!(cost.advice_columns, 4);
+        // the round constants
+        assert_eq!(cost.fixed_columns, 6);
+        assert_eq!(cost.instance_columns, 1);
+        assert_eq!(cost.public_inputs, 1);
+        assert_eq!(cost.lookups, 0);
+        assert!(cost.rows > 0 && cost.rows < 1 << cost.k);
+        assert!(cost.proof_size > 0);
+
+        // a wrong public input makes the proof fail
+        assert!(circuit_cost("poseidon", &circuit, &[vec![digest + Fp::from(1)]]).is_err());
+    }
+
+    #[test]
+    fn test_cost_visitor() {
+        let mut visitor =
+            CostVisitor::new(vec!["hash-1".to_string(), "merkle-sum-tree".to_string()]);
+        visit_experiments(&mut visitor);
+        assert!(visitor.errors.is_empty());
+        assert_eq!(
+            visitor
+                .costs
+                .iter()
+                .map(|cost| cost.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["hash-1", "merkle-sum-tree"]
+        );
+
+        // the range check table of the merkle sum tree takes 256 rows
+        assert_eq!(visitor.costs[1].lookups, 1);
+        assert!(visitor.costs[1].rows >= 256);
+        assert!(experiment_names().contains(&"merkle-sum-tree".to_string()));
+    }
+}
@@ -0,0 +1,96 @@
+/*
+Registry of the experiment circuits, instantiated over Fp with representative witnesses taken from their tests.
+Tools that run on every circuit (cost reports, layouts) implement ExperimentVisitor and are called once per circuit
+by visit_experiments, so that adding an experiment only requires registering it here.
+*/
+
+use crate::circuits::{
+    hash_v1, hash_v2, inclusion_check, inclusion_check_v2, merkle_public_positions,
+    merkle_quaternary, merkle_sum_tree, merkle_sum_tree_multi_asset, merkle_update, merkle_v2,
+    merkle_v3, poseidon, sparse_merkle_tree,
+};
+use halo2_proofs::{halo2curves::pasta::Fp, plonk::Circuit};
+
+pub trait ExperimentVisitor {
+    // public_inputs contains the values of each instance column, in the same format as the instances passed to the MockProver
+    fn visit<C: Circuit<Fp>>(&mut self, name: &str, circuit: &C, public_inputs: &[Vec<Fp>]);
+}
+
+// Calls the visitor on every experiment, in the order of the README
+pub fn visit_experiments<V: ExperimentVisitor>(visitor: &mut V) {
+    inclusion_check::experiment(visitor);
+    inclusion_check_v2::experiment(visitor);
+    hash_v1::experiment(visitor);
+    hash_v2::experiment(visitor);
+    merkle_v2::experiment(visitor);
+    poseidon::experiment(visitor);
+    merkle_v3::experiment(visitor);
+    merkle_sum_tree::experiment(visitor);
+    merkle_sum_tree_multi_asset::experiment(visitor);
+    sparse_merkle_tree::experiment(visitor);
+    merkle_update::experiment(visitor);
+    merkle_public_positions::experiment(visitor);
+    merkle_quaternary::experiment(visitor);
+}
+
+// Names of the experiments, in the order in which they are visited
+pub fn experiment_names() -> Vec<String> {
+    struct Names(Vec<String>);
+
+    impl ExperimentVisitor for Names {
+        fn visit<C: Circuit<Fp>>(&mut self, name: &str, _: &C, _: &[Vec<Fp>]) {
+            self.0.push(name.to_string());
+        }
+    }
+
+    let mut names = Names(vec![]);
+    visit_experiments(&mut names);
+    names.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{experiment_names, visit_experiments, ExperimentVisitor};
+    use crate::proving::min_k;
+    use halo2_proofs::{dev::MockProver, halo2curves::pasta::Fp, plonk::Circuit};
+
+    struct SatisfiedVisitor;
+
+    impl ExperimentVisitor for SatisfiedVisitor {
+        fn visit<C: Circuit<Fp>>(&mut self, name: &str, circuit: &C, public_inputs: &[Vec<Fp>]) {
+            let k = min_k(circuit, public_inputs).unwrap();
+            let prover = MockProver::run(k, circuit, public_inputs.to_vec()).unwrap();
+            assert_eq!(prover.verify(), Ok(()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_experiments() {
+        // the witnesses of the registry satisfy the circuits
+        visit_experiments(&mut SatisfiedVisitor);
+
+        // every experiment is registered once, in the order of the README
+        assert_eq!(
+            experiment_names(),
+            [
+                "inclusion-check-1",
+                "inclusion-check-2",
+                "hash-1",
+                "hash-2",
+                "merkle-tree-2",
+                "poseidon",
+                "poseidon-sponge",
+                "poseidon-batch",
+                "merkle-tree-3",
+                "merkle-tree-3-index",
+                "merkle-sum-tree",
+                "merkle-sum-tree-multi-asset",
+                "sparse-merkle-tree-membership",
+                "sparse-merkle-tree-non-membership",
+                "merkle-update",
+                "merkle-tree-public-positions",
+                "merkle-tree-quaternary",
+            ]
+        );
+    }
+}
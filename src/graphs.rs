@@ -0,0 +1,133 @@
+/*
+Visualisations of the experiment circuits, written to a directory (prints/ by default) for every circuit of the
+experiments registry:
+- with the dev-graph feature, the layout of the circuit is rendered as a PNG
+- with the dot-graph feature, the graph of the gates and regions is exported as DOT, using the graph tools of
+  halo2_proofs (built on tabbycat)
+*/
+
+use crate::experiments::{visit_experiments, ExperimentVisitor};
+use halo2_proofs::{halo2curves::pasta::Fp, plonk::Circuit};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "dev-graph")]
+const LAYOUT_SIZE: (u32, u32) = (1024, 3096);
+
+// Renders the layout of every experiment as <name>-layout.png, returns the paths of the files
+// The circuits are rendered with the k given by min_k. The regions are labelled and the cells constrained by the
+// permutation are marked, on top of the columns used by the selectors
+#[cfg(feature = "dev-graph")]
+pub fn render_layouts(out_dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    use crate::proving::min_k;
+    use halo2_proofs::dev::CircuitLayout;
+    use plotters::prelude::*;
+
+    struct LayoutVisitor<'a> {
+        out_dir: &'a Path,
+        result: Result<Vec<PathBuf>, Box<dyn std::error::Error>>,
+    }
+
+    impl LayoutVisitor<'_> {
+        fn render<C: Circuit<Fp>>(
+            &self,
+            name: &str,
+            circuit: &C,
+            public_inputs: &[Vec<Fp>],
+        ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+            let k = min_k(circuit, public_inputs)?;
+            let path = self.out_dir.join(format!("{}-layout.png", name));
+
+            // the backend borrows the path until it is dropped
+            {
+                let root = BitMapBackend::new(&path, LAYOUT_SIZE).into_drawing_area();
+                root.fill(&WHITE)?;
+                let root = root.titled(&format!("{} layout", name), ("sans-serif", 60))?;
+                CircuitLayout::default()
+                    .show_labels(true)
+                    .mark_equality_cells(true)
+                    .render(k, circuit, &root)?;
+                root.present()?;
+            }
+            Ok(path)
+        }
+    }
+
+    impl ExperimentVisitor for LayoutVisitor<'_> {
+        fn visit<C: Circuit<Fp>>(&mut self, name: &str, circuit: &C, public_inputs: &[Vec<Fp>]) {
+            if self.result.is_ok() {
+                match self.render(name, circuit, public_inputs) {
+                    Ok(path) => self.result.as_mut().unwrap().push(path),
+                    Err(e) => self.result = Err(e),
+                }
+            }
+        }
+    }
+
+    fs::create_dir_all(out_dir)?;
+    let mut visitor = LayoutVisitor {
+        out_dir,
+        result: Ok(vec![]),
+    };
+    visit_experiments(&mut visitor);
+    visitor.result
+}
+
+// Writes the graph of the gates and regions of every experiment as <name>-graph.dot, returns the paths of the files
+#[cfg(feature = "dot-graph")]
+pub fn write_dot_graphs(out_dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    struct DotGraphVisitor<'a> {
+        out_dir: &'a Path,
+        result: std::io::Result<Vec<PathBuf>>,
+    }
+
+    impl ExperimentVisitor for DotGraphVisitor<'_> {
+        fn visit<C: Circuit<Fp>>(&mut self, name: &str, circuit: &C, _: &[Vec<Fp>]) {
+            if let Ok(paths) = &mut self.result {
+                let path = self.out_dir.join(format!("{}-graph.dot", name));
+                let dot_graph = halo2_proofs::dev::circuit_dot_graph(circuit);
+                match fs::write(&path, dot_graph) {
+                    Ok(()) => paths.push(path),
+                    Err(e) => self.result = Err(e),
+                }
+            }
+        }
+    }
+
+    fs::create_dir_all(out_dir)?;
+    let mut visitor = DotGraphVisitor {
+        out_dir,
+        result: Ok(vec![]),
+    };
+    visit_experiments(&mut visitor);
+    visitor.result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::experiments::experiment_names;
+
+    // Renders the layouts in the directory given by the PRINTS_DIR environment variable, prints/ by default
+    #[cfg(feature = "dev-graph")]
+    #[test]
+    fn print_layouts() {
+        let out_dir = std::env::var("PRINTS_DIR").unwrap_or_else(|_| "prints".to_string());
+        let paths = super::render_layouts(std::path::Path::new(&out_dir)).unwrap();
+        assert_eq!(paths.len(), experiment_names().len());
+        assert!(paths.iter().all(|path| path.exists()));
+    }
+
+    #[cfg(feature = "dot-graph")]
+    #[test]
+    fn test_write_dot_graphs() {
+        let out_dir = std::env::temp_dir().join("halo2-experiments-dot-graphs");
+        let paths = super::write_dot_graphs(&out_dir).unwrap();
+        assert_eq!(paths.len(), experiment_names().len());
+        for path in paths {
+            let dot_graph = std::fs::read_to_string(path).unwrap();
+            assert!(dot_graph.starts_with("digraph"));
+        }
+    }
+}
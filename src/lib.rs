@@ -1,2 +1,9 @@
 pub mod chips;
 pub mod circuits;
+pub mod cost;
+pub mod experiments;
+#[cfg(any(feature = "dev-graph", feature = "dot-graph"))]
+pub mod graphs;
+pub mod merkle_tree;
+pub mod proving;
+pub mod serialization;
@@ -0,0 +1,33 @@
+/*
+Off-circuit Merkle tree using the same 2-to-1 Poseidon hash as the MerkleTreeV3Chip, ConstantLength<2> with width 3 and rate 2.
+It is used to compute roots and to generate the witnesses (path_elements and path_indices) consumed by the Merkle circuits.
+
+The Merkle sum tree mirrors the MerkleSumTreeChip: every node holds a hash and a sum.
+The multi asset Merkle sum tree mirrors the MerkleSumTreeMultiAssetChip: every node holds a hash and N_ASSETS sums.
+The sparse Merkle tree mirrors the SparseMerkleTreeChip: the position of a leaf is given by the bits of its key, so that
+the tree can prove that a key is absent as well as present.
+The quaternary Merkle tree mirrors the QuaternaryMerkleTreeChip: every node has four children, hashed with ConstantLength<4>
+with width 5 and rate 4.
+
+Each tree has a module of its own, whose items are re-exported here. The update proof of MerkleTree::update and the
+proofs of the batch of the MerkleTreePublicPositionsCircuit come from the binary MerkleTree.
+*/
+
+mod binary;
+mod multi_asset;
+mod quaternary;
+mod sparse;
+mod sum;
+mod update;
+
+pub use binary::{hash_nodes, MerkleProof, MerkleTree};
+pub use multi_asset::{
+    hash_multi_asset_leaf, hash_multi_asset_nodes, MerkleSumMultiAssetProof,
+    MerkleSumMultiAssetTree,
+};
+pub use quaternary::{
+    hash_quaternary_nodes, quaternary_children, QuaternaryMerkleProof, QuaternaryMerkleTree,
+};
+pub use sparse::{empty_hashes, hash_sparse_leaf, SparseMerkleProof, SparseMerkleTree};
+pub use sum::{hash_sum_leaf, hash_sum_nodes, MerkleSumProof, MerkleSumTree};
+pub use update::MerkleUpdateProof;
@@ -0,0 +1,193 @@
+use super::MerkleUpdateProof;
+use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, Spec};
+use halo2_proofs::arithmetic::FieldExt;
+use std::marker::PhantomData;
+
+// Hash of a left and a right node, as constrained by MerkleTreeV3Chip::merkle_prove_layer
+pub fn hash_nodes<F: FieldExt, S: Spec<F, 3, 2>>(left: F, right: F) -> F {
+    poseidon::Hash::<_, S, ConstantLength<2>, 3, 2>::init().hash([left, right])
+}
+
+// Authentication path of a leaf in the format consumed by the Merkle circuits.
+// path_elements[i] is the sibling of the node at level i (level 0 being the leaf)
+// path_indices[i] is 0 if the node at level i is a left child, 1 if it is a right child
+#[derive(Debug, Clone)]
+pub struct MerkleProof<F: FieldExt> {
+    pub leaf: F,
+    pub path_elements: Vec<F>,
+    pub path_indices: Vec<F>,
+    pub root: F,
+}
+
+impl<F: FieldExt> MerkleProof<F> {
+    // Recomputes the root from the leaf and the path, the same way the circuit does
+    pub fn compute_root<S: Spec<F, 3, 2>>(&self) -> F {
+        self.path_elements
+            .iter()
+            .zip(self.path_indices.iter())
+            .fold(self.leaf, |node, (element, index)| {
+                if *index == F::zero() {
+                    hash_nodes::<F, S>(node, *element)
+                } else {
+                    hash_nodes::<F, S>(*element, node)
+                }
+            })
+    }
+
+    pub fn verify<S: Spec<F, 3, 2>>(&self) -> bool {
+        self.compute_root::<S>() == self.root
+    }
+
+    // Position of the leaf, the path indices being its bits, least significant bit first
+    pub fn leaf_index(&self) -> F {
+        self.path_indices
+            .iter()
+            .rev()
+            .fold(F::zero(), |index, bit| index + index + bit)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MerkleTree<F: FieldExt, S: Spec<F, 3, 2>> {
+    // layers[0] contains the leaves, layers[depth] contains the root
+    layers: Vec<Vec<F>>,
+    _spec: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>> MerkleTree<F, S> {
+    // Builds a tree with 2^depth leaves, the leaves that are not provided are set to zero
+    pub fn new(leaves: &[F], depth: usize) -> Self {
+        assert!(
+            leaves.len() <= 1 << depth,
+            "{} leaves do not fit in a tree of depth {}",
+            leaves.len(),
+            depth
+        );
+
+        let mut layer = leaves.to_vec();
+        layer.resize(1 << depth, F::zero());
+
+        let mut layers = vec![layer];
+        while layers.len() <= depth {
+            let next_layer = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| hash_nodes::<F, S>(pair[0], pair[1]))
+                .collect();
+            layers.push(next_layer);
+        }
+
+        Self {
+            layers,
+            _spec: PhantomData,
+        }
+    }
+
+    // Builds the smallest tree (of depth at least 1) that contains all the leaves
+    pub fn from_leaves(leaves: &[F]) -> Self {
+        let mut depth = 1;
+        while 1 << depth < leaves.len() {
+            depth += 1;
+        }
+        Self::new(leaves, depth)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    pub fn root(&self) -> F {
+        self.layers[self.depth()][0]
+    }
+
+    pub fn leaves(&self) -> &[F] {
+        &self.layers[0]
+    }
+
+    // Generates the authentication path of the leaf at the given index
+    pub fn proof(&self, index: usize) -> MerkleProof<F> {
+        assert!(
+            index < self.leaves().len(),
+            "leaf index {} out of range",
+            index
+        );
+
+        let mut path_elements = Vec::with_capacity(self.depth());
+        let mut path_indices = Vec::with_capacity(self.depth());
+        for level in 0..self.depth() {
+            let node_index = index >> level;
+            path_elements.push(self.layers[level][node_index ^ 1]);
+            path_indices.push(F::from((node_index & 1) as u64));
+        }
+
+        MerkleProof {
+            leaf: self.layers[0][index],
+            path_elements,
+            path_indices,
+            root: self.root(),
+        }
+    }
+
+    // Replaces the leaf at the given index and returns the proof of the update
+    pub fn update(&mut self, index: usize, leaf: F) -> MerkleUpdateProof<F> {
+        let old_proof = self.proof(index);
+
+        self.layers[0][index] = leaf;
+        for level in 1..=self.depth() {
+            let node_index = index >> level;
+            self.layers[level][node_index] = hash_nodes::<F, S>(
+                self.layers[level - 1][node_index << 1],
+                self.layers[level - 1][(node_index << 1) | 1],
+            );
+        }
+
+        MerkleUpdateProof {
+            old_leaf: old_proof.leaf,
+            new_leaf: leaf,
+            path_elements: old_proof.path_elements,
+            path_indices: old_proof.path_indices,
+            old_root: old_proof.root,
+            new_root: self.root(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_nodes, MerkleTree};
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::halo2curves::{group::ff::Field, pasta::Fp};
+
+    #[test]
+    fn test_merkle_tree_root() {
+        let leaves = [Fp::from(1), Fp::from(2), Fp::from(3)];
+        let tree = MerkleTree::<Fp, P128Pow5T3>::from_leaves(&leaves);
+
+        // the missing fourth leaf is zero
+        let expected_root = hash_nodes::<Fp, P128Pow5T3>(
+            hash_nodes::<Fp, P128Pow5T3>(leaves[0], leaves[1]),
+            hash_nodes::<Fp, P128Pow5T3>(leaves[2], Fp::zero()),
+        );
+        assert_eq!(tree.depth(), 2);
+        assert_eq!(tree.root(), expected_root);
+    }
+
+    #[test]
+    fn test_merkle_tree_proofs() {
+        let leaves = (0..20u64).map(|i| Fp::from(i * 7 + 3)).collect::<Vec<Fp>>();
+        let tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5);
+
+        for index in 0..32 {
+            let proof = tree.proof(index);
+            assert_eq!(proof.path_elements.len(), 5);
+            assert_eq!(proof.leaf_index(), Fp::from(index as u64));
+            assert!(proof.verify::<P128Pow5T3>());
+        }
+
+        // a proof for a different leaf does not match the root
+        let mut proof = tree.proof(3);
+        proof.leaf = Fp::from(1000);
+        assert!(!proof.verify::<P128Pow5T3>());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::chips::poseidon::sponge_hash;
+use halo2_gadgets::poseidon::primitives::Spec;
+use halo2_proofs::arithmetic::FieldExt;
+use std::marker::PhantomData;
+
+// Hash of a leaf of the multi asset Merkle sum tree, as constrained by MerkleSumTreeMultiAssetChip::assign_leaf
+pub fn hash_multi_asset_leaf<
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
+    const WIDTH: usize,
+    const RATE: usize,
+    const N_ASSETS: usize,
+>(
+    username: F,
+    balances: &[F; N_ASSETS],
+) -> F {
+    let message = std::iter::once(username)
+        .chain(balances.iter().cloned())
+        .collect::<Vec<F>>();
+    sponge_hash::<F, S, WIDTH, RATE>(&message)
+}
+
+// Hash of a parent node of the multi asset Merkle sum tree, as constrained by MerkleSumTreeMultiAssetChip::merkle_prove_layer
+pub fn hash_multi_asset_nodes<
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
+    const WIDTH: usize,
+    const RATE: usize,
+    const N_ASSETS: usize,
+>(
+    left: &(F, [F; N_ASSETS]),
+    right: &(F, [F; N_ASSETS]),
+) -> F {
+    let message = std::iter::once(left.0)
+        .chain(left.1.iter().cloned())
+        .chain(std::iter::once(right.0))
+        .chain(right.1.iter().cloned())
+        .collect::<Vec<F>>();
+    sponge_hash::<F, S, WIDTH, RATE>(&message)
+}
+
+fn add_sums<F: FieldExt, const N_ASSETS: usize>(
+    left: &[F; N_ASSETS],
+    right: &[F; N_ASSETS],
+) -> [F; N_ASSETS] {
+    let mut sums = [F::zero(); N_ASSETS];
+    for (sum, (l, r)) in sums.iter_mut().zip(left.iter().zip(right.iter())) {
+        *sum = *l + *r;
+    }
+    sums
+}
+
+// Authentication path of a user in the multi asset Merkle sum tree.
+// path_element_hashes[i] and path_element_sums[i] are the hash and the sums of the sibling of the node at level i
+#[derive(Debug, Clone)]
+pub struct MerkleSumMultiAssetProof<F: FieldExt, const N_ASSETS: usize> {
+    pub username: F,
+    pub balances: [F; N_ASSETS],
+    pub leaf_hash: F,
+    pub path_element_hashes: Vec<F>,
+    pub path_element_sums: Vec<[F; N_ASSETS]>,
+    pub path_indices: Vec<F>,
+    pub root_hash: F,
+    pub root_sums: [F; N_ASSETS],
+}
+
+impl<F: FieldExt, const N_ASSETS: usize> MerkleSumMultiAssetProof<F, N_ASSETS> {
+    // Recomputes the hash and the sums of the root from the leaf and the path
+    pub fn compute_root<S: Spec<F, WIDTH, RATE>, const WIDTH: usize, const RATE: usize>(
+        &self,
+    ) -> (F, [F; N_ASSETS]) {
+        let leaf = (
+            hash_multi_asset_leaf::<F, S, WIDTH, RATE, N_ASSETS>(self.username, &self.balances),
+            self.balances,
+        );
+        self.path_element_hashes
+            .iter()
+            .zip(self.path_element_sums.iter())
+            .zip(self.path_indices.iter())
+            .fold(leaf, |node, ((element_hash, element_sums), index)| {
+                let element = (*element_hash, *element_sums);
+                let (left, right) = if *index == F::zero() {
+                    (node, element)
+                } else {
+                    (element, node)
+                };
+                (
+                    hash_multi_asset_nodes::<F, S, WIDTH, RATE, N_ASSETS>(&left, &right),
+                    add_sums(&left.1, &right.1),
+                )
+            })
+    }
+
+    pub fn verify<S: Spec<F, WIDTH, RATE>, const WIDTH: usize, const RATE: usize>(&self) -> bool {
+        self.compute_root::<S, WIDTH, RATE>() == (self.root_hash, self.root_sums)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MerkleSumMultiAssetTree<
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
+    const WIDTH: usize,
+    const RATE: usize,
+    const N_ASSETS: usize,
+> {
+    // (username, balances) of every user, including the empty ones used as padding
+    entries: Vec<(F, [F; N_ASSETS])>,
+    // layers[0] contains the leaves, layers[depth] contains the root. Every node is a (hash, sums) pair
+    layers: Vec<Vec<(F, [F; N_ASSETS])>>,
+    _spec: PhantomData<S>,
+}
+
+impl<
+        F: FieldExt,
+        S: Spec<F, WIDTH, RATE>,
+        const WIDTH: usize,
+        const RATE: usize,
+        const N_ASSETS: usize,
+    > MerkleSumMultiAssetTree<F, S, WIDTH, RATE, N_ASSETS>
+{
+    // Builds a tree with 2^depth leaves out of (username, balances) entries, the entries that are not provided
+    // are set to a zero username with zero balances
+    pub fn new(entries: &[(F, [F; N_ASSETS])], depth: usize) -> Self {
+        assert!(
+            entries.len() <= 1 << depth,
+            "{} entries do not fit in a tree of depth {}",
+            entries.len(),
+            depth
+        );
+
+        let mut entries = entries.to_vec();
+        entries.resize(1 << depth, (F::zero(), [F::zero(); N_ASSETS]));
+
+        let leaves = entries
+            .iter()
+            .map(|(username, balances)| {
+                (
+                    hash_multi_asset_leaf::<F, S, WIDTH, RATE, N_ASSETS>(*username, balances),
+                    *balances,
+                )
+            })
+            .collect();
+
+        let mut layers = vec![leaves];
+        while layers.len() <= depth {
+            let next_layer = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| {
+                    (
+                        hash_multi_asset_nodes::<F, S, WIDTH, RATE, N_ASSETS>(&pair[0], &pair[1]),
+                        add_sums(&pair[0].1, &pair[1].1),
+                    )
+                })
+                .collect();
+            layers.push(next_layer);
+        }
+
+        Self {
+            entries,
+            layers,
+            _spec: PhantomData,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    // Returns the hash and the sums of the root
+    pub fn root(&self) -> (F, [F; N_ASSETS]) {
+        self.layers[self.depth()][0]
+    }
+
+    // Generates the authentication path of the user at the given index
+    pub fn proof(&self, index: usize) -> MerkleSumMultiAssetProof<F, N_ASSETS> {
+        assert!(
+            index < self.entries.len(),
+            "entry index {} out of range",
+            index
+        );
+
+        let mut path_element_hashes = Vec::with_capacity(self.depth());
+        let mut path_element_sums = Vec::with_capacity(self.depth());
+        let mut path_indices = Vec::with_capacity(self.depth());
+        for level in 0..self.depth() {
+            let node_index = index >> level;
+            let (element_hash, element_sums) = self.layers[level][node_index ^ 1];
+            path_element_hashes.push(element_hash);
+            path_element_sums.push(element_sums);
+            path_indices.push(F::from((node_index & 1) as u64));
+        }
+
+        let (username, balances) = self.entries[index];
+        let (root_hash, root_sums) = self.root();
+        MerkleSumMultiAssetProof {
+            username,
+            balances,
+            leaf_hash: self.layers[0][index].0,
+            path_element_hashes,
+            path_element_sums,
+            path_indices,
+            root_hash,
+            root_sums,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MerkleSumMultiAssetTree;
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::halo2curves::pasta::Fp;
+
+    #[test]
+    fn test_merkle_sum_multi_asset_tree() {
+        let entries = (0..6u64)
+            .map(|i| {
+                (
+                    Fp::from(i + 1000),
+                    [Fp::from(i), Fp::from(i * 10), Fp::from(i * 100)],
+                )
+            })
+            .collect::<Vec<(Fp, [Fp; 3])>>();
+        let tree = MerkleSumMultiAssetTree::<Fp, P128Pow5T3, 3, 2, 3>::new(&entries, 3);
+
+        // the root sums are the totals of the balances of each asset
+        assert_eq!(tree.root().1, [Fp::from(15), Fp::from(150), Fp::from(1500)]);
+
+        for index in 0..8 {
+            assert!(tree.proof(index).verify::<P128Pow5T3, 3, 2>());
+        }
+
+        // changing the balance of a single asset changes the root
+        let mut proof = tree.proof(4);
+        proof.balances[2] = Fp::from(1);
+        assert!(!proof.verify::<P128Pow5T3, 3, 2>());
+    }
+}
@@ -0,0 +1,172 @@
+use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, Spec};
+use halo2_proofs::arithmetic::FieldExt;
+use std::marker::PhantomData;
+
+// Hash of the four children of a node, as constrained by QuaternaryMerkleTreeChip::merkle_prove_layer
+pub fn hash_quaternary_nodes<F: FieldExt, S: Spec<F, 5, 4>>(children: [F; 4]) -> F {
+    poseidon::Hash::<_, S, ConstantLength<4>, 5, 4>::init().hash(children)
+}
+
+// Children of a node of the quaternary tree, the node being at the given position (0 to 3) and the siblings filling
+// the other positions from left to right
+pub fn quaternary_children<F: FieldExt>(node: F, siblings: [F; 3], position: usize) -> [F; 4] {
+    assert!(position < 4, "position {} out of range", position);
+    let mut siblings = siblings.into_iter();
+    std::array::from_fn(|i| {
+        if i == position {
+            node
+        } else {
+            siblings.next().unwrap()
+        }
+    })
+}
+
+// Authentication path of a leaf in the quaternary Merkle tree.
+// path_elements[i] are the three siblings of the node at level i, from left to right
+// path_indices[i] is the position (0 to 3) of the node at level i among the children of its parent
+#[derive(Debug, Clone)]
+pub struct QuaternaryMerkleProof<F: FieldExt> {
+    pub leaf: F,
+    pub path_elements: Vec<[F; 3]>,
+    pub path_indices: Vec<F>,
+    pub root: F,
+}
+
+impl<F: FieldExt> QuaternaryMerkleProof<F> {
+    // Recomputes the root from the leaf and the path, the same way the circuit does
+    pub fn compute_root<S: Spec<F, 5, 4>>(&self) -> F {
+        self.path_elements
+            .iter()
+            .zip(self.path_indices.iter())
+            .fold(self.leaf, |node, (siblings, index)| {
+                let position = index.get_lower_128() as usize;
+                hash_quaternary_nodes::<F, S>(quaternary_children(node, *siblings, position))
+            })
+    }
+
+    pub fn verify<S: Spec<F, 5, 4>>(&self) -> bool {
+        self.compute_root::<S>() == self.root
+    }
+
+    // Position of the leaf, the path indices being its digits in base 4, least significant digit first
+    pub fn leaf_index(&self) -> F {
+        self.path_indices
+            .iter()
+            .rev()
+            .fold(F::zero(), |index, digit| index * F::from(4) + digit)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QuaternaryMerkleTree<F: FieldExt, S: Spec<F, 5, 4>> {
+    // layers[0] contains the leaves, layers[depth] contains the root
+    layers: Vec<Vec<F>>,
+    _spec: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 5, 4>> QuaternaryMerkleTree<F, S> {
+    // Builds a tree with 4^depth leaves, the leaves that are not provided are set to zero
+    pub fn new(leaves: &[F], depth: usize) -> Self {
+        assert!(
+            leaves.len() <= 1 << (2 * depth),
+            "{} leaves do not fit in a quaternary tree of depth {}",
+            leaves.len(),
+            depth
+        );
+
+        let mut layer = leaves.to_vec();
+        layer.resize(1 << (2 * depth), F::zero());
+
+        let mut layers = vec![layer];
+        while layers.len() <= depth {
+            let next_layer = layers
+                .last()
+                .unwrap()
+                .chunks(4)
+                .map(|children| hash_quaternary_nodes::<F, S>(children.try_into().unwrap()))
+                .collect();
+            layers.push(next_layer);
+        }
+
+        Self {
+            layers,
+            _spec: PhantomData,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    pub fn root(&self) -> F {
+        self.layers[self.depth()][0]
+    }
+
+    pub fn leaves(&self) -> &[F] {
+        &self.layers[0]
+    }
+
+    // Generates the authentication path of the leaf at the given index
+    pub fn proof(&self, index: usize) -> QuaternaryMerkleProof<F> {
+        assert!(
+            index < self.leaves().len(),
+            "leaf index {} out of range",
+            index
+        );
+
+        let mut path_elements = Vec::with_capacity(self.depth());
+        let mut path_indices = Vec::with_capacity(self.depth());
+        for level in 0..self.depth() {
+            let node_index = index >> (2 * level);
+            let first_child = node_index & !3;
+            let mut siblings = (first_child..first_child + 4)
+                .filter(|child| *child != node_index)
+                .map(|child| self.layers[level][child]);
+            path_elements.push(std::array::from_fn(|_| siblings.next().unwrap()));
+            path_indices.push(F::from((node_index & 3) as u64));
+        }
+
+        QuaternaryMerkleProof {
+            leaf: self.layers[0][index],
+            path_elements,
+            path_indices,
+            root: self.root(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_quaternary_nodes, QuaternaryMerkleTree};
+    use crate::chips::poseidon_spec::P128Pow5T5Pasta;
+    use halo2_proofs::halo2curves::pasta::Fp;
+
+    #[test]
+    fn test_quaternary_merkle_tree() {
+        let leaves = (0..50u64).map(|i| Fp::from(i * 7 + 3)).collect::<Vec<Fp>>();
+        let tree = QuaternaryMerkleTree::<Fp, P128Pow5T5Pasta>::new(&leaves, 3);
+
+        // the leaf 6 is the third child of the second node of level 1, whose first sibling is the hash of the
+        // first four leaves
+        let proof = tree.proof(6);
+        assert_eq!(proof.path_indices[0], Fp::from(2));
+        assert_eq!(proof.path_elements[0], [leaves[4], leaves[5], leaves[7]]);
+        let first_subtree = [leaves[0], leaves[1], leaves[2], leaves[3]];
+        assert_eq!(
+            proof.path_elements[1][0],
+            hash_quaternary_nodes::<Fp, P128Pow5T5Pasta>(first_subtree)
+        );
+
+        for index in 0..64 {
+            let proof = tree.proof(index);
+            assert_eq!(proof.path_elements.len(), 3);
+            assert_eq!(proof.leaf_index(), Fp::from(index as u64));
+            assert!(proof.verify::<P128Pow5T5Pasta>());
+        }
+
+        // the leaf is at a single position among its siblings
+        let mut proof = tree.proof(6);
+        proof.path_indices[0] = Fp::from(1);
+        assert!(!proof.verify::<P128Pow5T5Pasta>());
+    }
+}
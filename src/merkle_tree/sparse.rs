@@ -0,0 +1,265 @@
+use super::{hash_nodes, MerkleProof};
+use halo2_gadgets::poseidon::primitives::Spec;
+use halo2_proofs::arithmetic::FieldExt;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+// Roots of the empty subtrees of a sparse Merkle tree, empty_hashes[i] being the root of an empty subtree of depth i.
+// An empty leaf is zero
+pub fn empty_hashes<F: FieldExt, S: Spec<F, 3, 2>>(depth: usize) -> Vec<F> {
+    let mut hashes = vec![F::zero()];
+    for level in 0..depth {
+        hashes.push(hash_nodes::<F, S>(hashes[level], hashes[level]));
+    }
+    hashes
+}
+
+// Hash of a leaf of the sparse Merkle tree, as constrained by SparseMerkleTreeChip::assign_leaf
+pub fn hash_sparse_leaf<F: FieldExt, S: Spec<F, 3, 2>>(key: F, value: F) -> F {
+    hash_nodes::<F, S>(key, value)
+}
+
+// Authentication path of a key in the sparse Merkle tree.
+// value is None if the key is not in the tree, in which case the path leads to an empty leaf
+#[derive(Debug, Clone)]
+pub struct SparseMerkleProof<F: FieldExt> {
+    pub key: F,
+    pub value: Option<F>,
+    pub path_elements: Vec<F>,
+    pub root: F,
+}
+
+// The n lowest bits of the canonical integer of the key, least significant bit first
+fn key_bits<F: FieldExt>(key: F, n: usize) -> Vec<bool> {
+    key.to_repr()
+        .as_ref()
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+        .take(n)
+        .collect()
+}
+
+impl<F: FieldExt> SparseMerkleProof<F> {
+    // The bits of the key, least significant bit first, are the indices of the path
+    pub fn path_indices(&self) -> Vec<F> {
+        key_bits(self.key, self.path_elements.len())
+            .into_iter()
+            .map(|bit| F::from(bit as u64))
+            .collect()
+    }
+
+    // Recomputes the root from the leaf of the key and the path, the same way the circuit does
+    pub fn compute_root<S: Spec<F, 3, 2>>(&self) -> F {
+        let leaf = match self.value {
+            Some(value) => hash_sparse_leaf::<F, S>(self.key, value),
+            None => F::zero(),
+        };
+        MerkleProof {
+            leaf,
+            path_elements: self.path_elements.clone(),
+            path_indices: self.path_indices(),
+            root: self.root,
+        }
+        .compute_root::<S>()
+    }
+
+    pub fn verify<S: Spec<F, 3, 2>>(&self) -> bool {
+        self.compute_root::<S>() == self.root
+    }
+}
+
+// The position of a node at level l is given by the bits l to depth - 1 of the keys below it, least significant bit
+// first, so that the tree isn't limited by the size of an integer type: the leaf of a key is at the position given by
+// all its bits and the root is at the empty position
+#[derive(Debug, Clone)]
+pub struct SparseMerkleTree<F: FieldExt, S: Spec<F, 3, 2>> {
+    depth: usize,
+    // value of every key inserted in the tree, indexed by the position of its leaf
+    values: HashMap<Vec<bool>, F>,
+    // the nodes that are not the root of an empty subtree, indexed by (level, position in the level)
+    nodes: HashMap<(usize, Vec<bool>), F>,
+    empty_hashes: Vec<F>,
+    _spec: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>> SparseMerkleTree<F, S> {
+    // Builds an empty tree with 2^depth leaves, the keys of the tree are the integers smaller than 2^depth.
+    // The depth is smaller than the number of bits of the field, like the DEPTH bits of the SparseMerkleTreeChip
+    pub fn new(depth: usize) -> Self {
+        let max_depth = F::NUM_BITS as usize - 1;
+        assert!(
+            (1..=max_depth).contains(&depth),
+            "the depth of a sparse Merkle tree must be between 1 and {}, got {}",
+            max_depth,
+            depth
+        );
+
+        Self {
+            depth,
+            values: HashMap::new(),
+            nodes: HashMap::new(),
+            empty_hashes: empty_hashes::<F, S>(depth),
+            _spec: PhantomData,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn root(&self) -> F {
+        self.node(self.depth, &[])
+    }
+
+    // Position of the leaf of the key, the key must be smaller than 2^depth
+    fn position(&self, key: F) -> Vec<bool> {
+        let bits = key_bits(key, F::NUM_BITS as usize);
+        assert!(
+            bits[self.depth..].iter().all(|bit| !bit),
+            "the key {:?} does not fit in {} bits",
+            key,
+            self.depth
+        );
+        bits[..self.depth].to_vec()
+    }
+
+    fn node(&self, level: usize, position: &[bool]) -> F {
+        self.nodes
+            .get(&(level, position.to_vec()))
+            .cloned()
+            .unwrap_or(self.empty_hashes[level])
+    }
+
+    pub fn get(&self, key: F) -> Option<F> {
+        self.values.get(&self.position(key)).cloned()
+    }
+
+    // Inserts the key in the tree, or replaces its value if it is already present
+    pub fn insert(&mut self, key: F, value: F) {
+        let position = self.position(key);
+        self.values.insert(position.clone(), value);
+        self.nodes
+            .insert((0, position.clone()), hash_sparse_leaf::<F, S>(key, value));
+
+        for level in 1..=self.depth {
+            // the children of the node are at the positions with one more bit, 0 for the left and 1 for the right
+            let parent = &position[level..];
+            let left = self.node(level - 1, &[&[false][..], parent].concat());
+            let right = self.node(level - 1, &[&[true][..], parent].concat());
+            self.nodes
+                .insert((level, parent.to_vec()), hash_nodes::<F, S>(left, right));
+        }
+    }
+
+    // Generates a proof of membership if the key is in the tree and a proof of non-membership otherwise
+    pub fn proof(&self, key: F) -> SparseMerkleProof<F> {
+        let position = self.position(key);
+        // the sibling of a node is at the same position with the lowest bit flipped
+        let path_elements = (0..self.depth)
+            .map(|level| {
+                let mut sibling = position[level..].to_vec();
+                sibling[0] = !sibling[0];
+                self.node(level, &sibling)
+            })
+            .collect();
+
+        SparseMerkleProof {
+            key,
+            value: self.values.get(&position).cloned(),
+            path_elements,
+            root: self.root(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{empty_hashes, SparseMerkleTree};
+    use crate::merkle_tree::{hash_nodes, MerkleTree};
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::halo2curves::{
+        group::ff::{Field, PrimeField},
+        pasta::Fp,
+    };
+
+    #[test]
+    fn test_sparse_merkle_tree() {
+        let mut tree = SparseMerkleTree::<Fp, P128Pow5T3>::new(8);
+        assert_eq!(tree.root(), empty_hashes::<Fp, P128Pow5T3>(8)[8]);
+
+        // an empty tree proves the absence of any key
+        assert!(tree.proof(Fp::from(42)).verify::<P128Pow5T3>());
+
+        let keys = [3u64, 42, 200, 255];
+        for key in keys {
+            tree.insert(Fp::from(key), Fp::from(key * 1000));
+        }
+
+        // the root is the one of the dense tree with the same leaves
+        let mut leaves = vec![Fp::zero(); 256];
+        for key in keys {
+            leaves[key as usize] =
+                hash_nodes::<Fp, P128Pow5T3>(Fp::from(key), Fp::from(key * 1000));
+        }
+        assert_eq!(
+            tree.root(),
+            MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 8).root()
+        );
+
+        for key in keys {
+            let proof = tree.proof(Fp::from(key));
+            assert_eq!(proof.value, Some(Fp::from(key * 1000)));
+            assert!(proof.verify::<P128Pow5T3>());
+        }
+
+        for key in [0u64, 2, 43, 254] {
+            let proof = tree.proof(Fp::from(key));
+            assert_eq!(proof.value, None);
+            assert!(proof.verify::<P128Pow5T3>());
+        }
+
+        // a key in the tree cannot be proven to be absent
+        let mut proof = tree.proof(Fp::from(42));
+        proof.value = None;
+        assert!(!proof.verify::<P128Pow5T3>());
+
+        // replacing the value of a key changes the root
+        let root = tree.root();
+        tree.insert(Fp::from(42), Fp::from(1));
+        assert_eq!(tree.get(Fp::from(42)), Some(Fp::from(1)));
+        assert_ne!(tree.root(), root);
+    }
+
+    #[test]
+    fn test_sparse_merkle_tree_max_depth() {
+        // the keys use all the bits of a tree of depth NUM_BITS - 1, 5 and 2^200 + 5 have the same lower 128 bits
+        let depth = Fp::NUM_BITS as usize - 1;
+        let mut tree = SparseMerkleTree::<Fp, P128Pow5T3>::new(depth);
+        let keys = [
+            Fp::from(5),
+            Fp::from(2).pow_vartime(&[200]) + Fp::from(5),
+            Fp::from(2).pow_vartime(&[depth as u64 - 1]),
+        ];
+        for (i, key) in keys.iter().enumerate() {
+            tree.insert(*key, Fp::from(i as u64 + 1));
+        }
+
+        for (i, key) in keys.iter().enumerate() {
+            let proof = tree.proof(*key);
+            assert_eq!(proof.path_elements.len(), depth);
+            assert_eq!(proof.value, Some(Fp::from(i as u64 + 1)));
+            assert!(proof.verify::<P128Pow5T3>());
+        }
+
+        let proof = tree.proof(Fp::from(2).pow_vartime(&[128]) + Fp::from(5));
+        assert_eq!(proof.value, None);
+        assert!(proof.verify::<P128Pow5T3>());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sparse_merkle_tree_key_too_large() {
+        let mut tree = SparseMerkleTree::<Fp, P128Pow5T3>::new(8);
+        tree.insert(Fp::from(256), Fp::from(1));
+    }
+}
@@ -0,0 +1,178 @@
+use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, Spec};
+use halo2_proofs::arithmetic::FieldExt;
+use std::marker::PhantomData;
+
+// Hash of a leaf of the Merkle sum tree, as constrained by MerkleSumTreeChip::assign_leaf
+pub fn hash_sum_leaf<F: FieldExt, S: Spec<F, 3, 2>>(username: F, balance: F) -> F {
+    poseidon::Hash::<_, S, ConstantLength<2>, 3, 2>::init().hash([username, balance])
+}
+
+// Hash of a parent node of the Merkle sum tree, as constrained by MerkleSumTreeChip::merkle_prove_layer
+pub fn hash_sum_nodes<F: FieldExt, S: Spec<F, 3, 2>>(left: (F, F), right: (F, F)) -> F {
+    poseidon::Hash::<_, S, ConstantLength<4>, 3, 2>::init().hash([left.0, left.1, right.0, right.1])
+}
+
+// Authentication path of a user in the Merkle sum tree.
+// path_element_hashes[i] and path_element_sums[i] are the hash and the sum of the sibling of the node at level i
+#[derive(Debug, Clone)]
+pub struct MerkleSumProof<F: FieldExt> {
+    pub username: F,
+    pub balance: F,
+    pub leaf_hash: F,
+    pub path_element_hashes: Vec<F>,
+    pub path_element_sums: Vec<F>,
+    pub path_indices: Vec<F>,
+    pub root_hash: F,
+    pub root_sum: F,
+}
+
+impl<F: FieldExt> MerkleSumProof<F> {
+    // Recomputes the hash and the sum of the root from the leaf and the path
+    pub fn compute_root<S: Spec<F, 3, 2>>(&self) -> (F, F) {
+        let leaf = (
+            hash_sum_leaf::<F, S>(self.username, self.balance),
+            self.balance,
+        );
+        self.path_element_hashes
+            .iter()
+            .zip(self.path_element_sums.iter())
+            .zip(self.path_indices.iter())
+            .fold(leaf, |node, ((element_hash, element_sum), index)| {
+                let element = (*element_hash, *element_sum);
+                let (left, right) = if *index == F::zero() {
+                    (node, element)
+                } else {
+                    (element, node)
+                };
+                (hash_sum_nodes::<F, S>(left, right), left.1 + right.1)
+            })
+    }
+
+    pub fn verify<S: Spec<F, 3, 2>>(&self) -> bool {
+        self.compute_root::<S>() == (self.root_hash, self.root_sum)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MerkleSumTree<F: FieldExt, S: Spec<F, 3, 2>> {
+    // (username, balance) of every user, including the empty ones used as padding
+    entries: Vec<(F, F)>,
+    // layers[0] contains the leaves, layers[depth] contains the root. Every node is a (hash, sum) pair
+    layers: Vec<Vec<(F, F)>>,
+    _spec: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: Spec<F, 3, 2>> MerkleSumTree<F, S> {
+    // Builds a tree with 2^depth leaves out of (username, balance) entries, the entries that are not provided
+    // are set to a zero username with a zero balance
+    pub fn new(entries: &[(F, F)], depth: usize) -> Self {
+        assert!(
+            entries.len() <= 1 << depth,
+            "{} entries do not fit in a tree of depth {}",
+            entries.len(),
+            depth
+        );
+
+        let mut entries = entries.to_vec();
+        entries.resize(1 << depth, (F::zero(), F::zero()));
+
+        let leaves = entries
+            .iter()
+            .map(|(username, balance)| (hash_sum_leaf::<F, S>(*username, *balance), *balance))
+            .collect();
+
+        let mut layers = vec![leaves];
+        while layers.len() <= depth {
+            let next_layer = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| {
+                    (
+                        hash_sum_nodes::<F, S>(pair[0], pair[1]),
+                        pair[0].1 + pair[1].1,
+                    )
+                })
+                .collect();
+            layers.push(next_layer);
+        }
+
+        Self {
+            entries,
+            layers,
+            _spec: PhantomData,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    // Returns the hash and the sum of the root
+    pub fn root(&self) -> (F, F) {
+        self.layers[self.depth()][0]
+    }
+
+    // Generates the authentication path of the user at the given index
+    pub fn proof(&self, index: usize) -> MerkleSumProof<F> {
+        assert!(
+            index < self.entries.len(),
+            "entry index {} out of range",
+            index
+        );
+
+        let mut path_element_hashes = Vec::with_capacity(self.depth());
+        let mut path_element_sums = Vec::with_capacity(self.depth());
+        let mut path_indices = Vec::with_capacity(self.depth());
+        for level in 0..self.depth() {
+            let node_index = index >> level;
+            let (element_hash, element_sum) = self.layers[level][node_index ^ 1];
+            path_element_hashes.push(element_hash);
+            path_element_sums.push(element_sum);
+            path_indices.push(F::from((node_index & 1) as u64));
+        }
+
+        let (username, balance) = self.entries[index];
+        let (root_hash, root_sum) = self.root();
+        MerkleSumProof {
+            username,
+            balance,
+            leaf_hash: self.layers[0][index].0,
+            path_element_hashes,
+            path_element_sums,
+            path_indices,
+            root_hash,
+            root_sum,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MerkleSumTree;
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::halo2curves::{group::ff::Field, pasta::Fp};
+
+    #[test]
+    fn test_merkle_sum_tree() {
+        let entries = (0..10u64)
+            .map(|i| (Fp::from(i + 1000), Fp::from(i * 100)))
+            .collect::<Vec<(Fp, Fp)>>();
+        let tree = MerkleSumTree::<Fp, P128Pow5T3>::new(&entries, 4);
+
+        // the root sum is the total of the balances
+        let total = entries
+            .iter()
+            .fold(Fp::zero(), |acc, (_, balance)| acc + balance);
+        assert_eq!(tree.root().1, total);
+
+        for index in 0..16 {
+            assert!(tree.proof(index).verify::<P128Pow5T3>());
+        }
+
+        // lowering the balance of a user changes the root
+        let mut proof = tree.proof(2);
+        proof.balance = Fp::from(1);
+        assert!(!proof.verify::<P128Pow5T3>());
+    }
+}
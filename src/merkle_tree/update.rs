@@ -0,0 +1,67 @@
+use super::MerkleProof;
+use halo2_gadgets::poseidon::primitives::Spec;
+use halo2_proofs::arithmetic::FieldExt;
+
+// Replacement of the leaf old_leaf with new_leaf at the same position, turning old_root into new_root.
+// The authentication path of the leaf is the same before and after the update
+#[derive(Debug, Clone)]
+pub struct MerkleUpdateProof<F: FieldExt> {
+    pub old_leaf: F,
+    pub new_leaf: F,
+    pub path_elements: Vec<F>,
+    pub path_indices: Vec<F>,
+    pub old_root: F,
+    pub new_root: F,
+}
+
+impl<F: FieldExt> MerkleUpdateProof<F> {
+    // Checks that the path leads from the old leaf to the old root and from the new leaf to the new root
+    pub fn verify<S: Spec<F, 3, 2>>(&self) -> bool {
+        let old_proof = MerkleProof {
+            leaf: self.old_leaf,
+            path_elements: self.path_elements.clone(),
+            path_indices: self.path_indices.clone(),
+            root: self.old_root,
+        };
+        let new_proof = MerkleProof {
+            leaf: self.new_leaf,
+            root: self.new_root,
+            ..old_proof.clone()
+        };
+        old_proof.verify::<S>() && new_proof.verify::<S>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::merkle_tree::MerkleTree;
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::halo2curves::pasta::Fp;
+
+    #[test]
+    fn test_merkle_tree_update() {
+        let leaves = (0..20u64).map(|i| Fp::from(i * 7 + 3)).collect::<Vec<Fp>>();
+        let mut tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5);
+
+        let old_root = tree.root();
+        let update = tree.update(6, Fp::from(1000));
+        assert_eq!(update.old_leaf, leaves[6]);
+        assert_eq!(update.old_root, old_root);
+        assert_eq!(update.new_root, tree.root());
+        assert!(update.verify::<P128Pow5T3>());
+
+        // the tree is the same as the one built with the new leaf
+        let mut new_leaves = leaves.clone();
+        new_leaves[6] = Fp::from(1000);
+        assert_eq!(
+            tree.root(),
+            MerkleTree::<Fp, P128Pow5T3>::new(&new_leaves, 5).root()
+        );
+        assert!(tree.proof(6).verify::<P128Pow5T3>());
+
+        // an update can't claim a different new leaf
+        let mut wrong_update = update;
+        wrong_update.new_leaf = Fp::from(1001);
+        assert!(!wrong_update.verify::<P128Pow5T3>());
+    }
+}
@@ -0,0 +1,12 @@
+/*
+Real proving and verification of the experiment circuits, as opposed to the MockProver used in the tests of the circuits.
+The MockProver only checks that the constraints are satisfied by the witnesses, while these functions generate the keys,
+create a proof with a transcript and verify it.
+The size of the parameters needed by a circuit is computed by min_k.
+*/
+
+pub mod ipa;
+pub mod kzg;
+pub mod min_k;
+
+pub use min_k::{min_k, MinKError};
@@ -0,0 +1,100 @@
+// Proving backend based on the IPA commitment scheme over the pasta curves. No trusted setup is needed,
+// the parameters are generated from k only. The circuits are instantiated over Fp, the scalar field of Eq (Vesta).
+
+use halo2_proofs::{
+    halo2curves::pasta::{EqAffine, Fp},
+    plonk::{
+        create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, Error, ProvingKey, VerifyingKey,
+    },
+    poly::{
+        commitment::ParamsProver,
+        ipa::{
+            commitment::{IPACommitmentScheme, ParamsIPA},
+            multiopen::{ProverIPA, VerifierIPA},
+            strategy::SingleStrategy,
+        },
+    },
+    transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+    },
+};
+use rand_core::OsRng;
+
+// Generates the parameters for circuits of up to 2^k rows
+pub fn setup(k: u32) -> ParamsIPA<EqAffine> {
+    ParamsIPA::<EqAffine>::new(k)
+}
+
+// Generates the proving key (which contains the verifying key) of a circuit
+// The keys only depend on the shape of the circuit, therefore they are generated from the circuit without witnesses
+pub fn keys<C: Circuit<Fp>>(
+    params: &ParamsIPA<EqAffine>,
+    circuit: &C,
+) -> Result<ProvingKey<EqAffine>, Error> {
+    let empty_circuit = circuit.without_witnesses();
+    let vk = keygen_vk(params, &empty_circuit)?;
+    keygen_pk(params, vk, &empty_circuit)
+}
+
+// Creates a proof for the circuit. public_inputs contains the values of each instance column of the circuit,
+// in the same format as the instances passed to the MockProver
+pub fn full_prover<C: Circuit<Fp>>(
+    params: &ParamsIPA<EqAffine>,
+    pk: &ProvingKey<EqAffine>,
+    circuit: &C,
+    public_inputs: &[Vec<Fp>],
+) -> Result<Vec<u8>, Error> {
+    let instances = public_inputs
+        .iter()
+        .map(|column| column.as_slice())
+        .collect::<Vec<&[Fp]>>();
+
+    let mut transcript = Blake2bWrite::<_, EqAffine, Challenge255<_>>::init(vec![]);
+    create_proof::<IPACommitmentScheme<EqAffine>, ProverIPA<EqAffine>, _, _, _, _>(
+        params,
+        pk,
+        std::slice::from_ref(circuit),
+        &[&instances],
+        OsRng,
+        &mut transcript,
+    )?;
+    Ok(transcript.finalize())
+}
+
+// Verifies a proof against the public inputs
+pub fn full_verifier(
+    params: &ParamsIPA<EqAffine>,
+    vk: &VerifyingKey<EqAffine>,
+    proof: &[u8],
+    public_inputs: &[Vec<Fp>],
+) -> Result<(), Error> {
+    let instances = public_inputs
+        .iter()
+        .map(|column| column.as_slice())
+        .collect::<Vec<&[Fp]>>();
+
+    let strategy = SingleStrategy::new(params);
+    let mut transcript = Blake2bRead::<_, EqAffine, Challenge255<_>>::init(proof);
+    verify_proof::<IPACommitmentScheme<EqAffine>, VerifierIPA<EqAffine>, _, _, _>(
+        params,
+        vk,
+        strategy,
+        &[&instances],
+        &mut transcript,
+    )
+}
+
+// Proves the circuit with parameters of the smallest size that fits it, then checks that the proof is accepted with
+// the public inputs and rejected with the wrong ones. Shared by the IPA tests of the circuits
+#[cfg(test)]
+pub(crate) fn assert_roundtrip<C: Circuit<Fp>>(
+    circuit: &C,
+    public_inputs: &[Vec<Fp>],
+    wrong_public_inputs: &[Vec<Fp>],
+) {
+    let params = setup(super::min_k(circuit, public_inputs).unwrap());
+    let pk = keys(&params, circuit).unwrap();
+    let proof = full_prover(&params, &pk, circuit, public_inputs).unwrap();
+    assert!(full_verifier(&params, pk.get_vk(), &proof, public_inputs).is_ok());
+    assert!(full_verifier(&params, pk.get_vk(), &proof, wrong_public_inputs).is_err());
+}
@@ -0,0 +1,143 @@
+// Proving backend based on the KZG commitment scheme over BN254, with the SHPLONK multiopen argument.
+// The transcript is Blake2b like in proving::ipa, so the proofs are verified with full_verifier: an EVM verifier would
+// need a Keccak transcript and a generated verifier contract, which this module doesn't provide.
+// The circuits are instantiated over Fr, the scalar field of BN254.
+
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{
+        create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, Error, ProvingKey, VerifyingKey,
+    },
+    poly::{
+        commitment::{Params, ParamsProver},
+        kzg::{
+            commitment::{KZGCommitmentScheme, ParamsKZG},
+            multiopen::{ProverSHPLONK, VerifierSHPLONK},
+            strategy::SingleStrategy,
+        },
+    },
+    transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+    },
+};
+use rand::{rngs::StdRng, SeedableRng};
+use rand_core::OsRng;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+// Generates the parameters for circuits of up to 2^k rows from a locally generated trusted setup.
+// Whoever knows the seed knows the toxic waste and can forge proofs, so these parameters are only meant for
+// tests and experiments, a production deployment should use the parameters of a trusted setup ceremony.
+pub fn setup(k: u32, seed: u64) -> ParamsKZG<Bn256> {
+    ParamsKZG::<Bn256>::setup(k, StdRng::seed_from_u64(seed))
+}
+
+// Loads the parameters of a trusted setup ceremony, written in the format of write_params, and downsizes them to
+// circuits of up to 2^k rows. The parameters for 2^k rows are a prefix of the ones of any larger size, so a single file
+// serves every circuit that fits in it. Returns an error if the file holds parameters for fewer rows
+pub fn load_params(path: &Path, k: u32) -> io::Result<ParamsKZG<Bn256>> {
+    let mut params = ParamsKZG::<Bn256>::read(&mut BufReader::new(File::open(path)?))?;
+    if params.k() < k {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "the parameters support up to 2^{} rows, the circuit needs 2^{}",
+                params.k(),
+                k
+            ),
+        ));
+    }
+    params.downsize(k);
+    Ok(params)
+}
+
+pub fn write_params(params: &ParamsKZG<Bn256>, path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    params.write(&mut writer)?;
+    writer.flush()
+}
+
+// Generates the proving key (which contains the verifying key) of a circuit
+// The keys only depend on the shape of the circuit, therefore they are generated from the circuit without witnesses
+pub fn keys<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    circuit: &C,
+) -> Result<ProvingKey<G1Affine>, Error> {
+    let empty_circuit = circuit.without_witnesses();
+    let vk = keygen_vk(params, &empty_circuit)?;
+    keygen_pk(params, vk, &empty_circuit)
+}
+
+// Generates the verifying key of a circuit, this is all the verifier needs besides the parameters
+pub fn verifying_key<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    circuit: &C,
+) -> Result<VerifyingKey<G1Affine>, Error> {
+    keygen_vk(params, &circuit.without_witnesses())
+}
+
+// Creates a proof for the circuit. public_inputs contains the values of each instance column of the circuit,
+// in the same format as the instances passed to the MockProver
+pub fn full_prover<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: &C,
+    public_inputs: &[Vec<Fr>],
+) -> Result<Vec<u8>, Error> {
+    let instances = public_inputs
+        .iter()
+        .map(|column| column.as_slice())
+        .collect::<Vec<&[Fr]>>();
+
+    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+    create_proof::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _, _>(
+        params,
+        pk,
+        std::slice::from_ref(circuit),
+        &[&instances],
+        OsRng,
+        &mut transcript,
+    )?;
+    Ok(transcript.finalize())
+}
+
+// Verifies a proof against the public inputs
+pub fn full_verifier(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    proof: &[u8],
+    public_inputs: &[Vec<Fr>],
+) -> Result<(), Error> {
+    let instances = public_inputs
+        .iter()
+        .map(|column| column.as_slice())
+        .collect::<Vec<&[Fr]>>();
+
+    let strategy = SingleStrategy::new(params);
+    let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof);
+    verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+        params.verifier_params(),
+        vk,
+        strategy,
+        &[&instances],
+        &mut transcript,
+    )
+}
+
+// Proves the circuit with parameters of the smallest size that fits it, then checks that the proof is accepted with
+// the public inputs and rejected with the wrong ones. Shared by the KZG tests of the circuits
+#[cfg(test)]
+pub(crate) fn assert_roundtrip<C: Circuit<Fr>>(
+    circuit: &C,
+    public_inputs: &[Vec<Fr>],
+    wrong_public_inputs: &[Vec<Fr>],
+) {
+    let params = setup(super::min_k(circuit, public_inputs).unwrap(), 42);
+    let pk = keys(&params, circuit).unwrap();
+    let proof = full_prover(&params, &pk, circuit, public_inputs).unwrap();
+    assert!(full_verifier(&params, pk.get_vk(), &proof, public_inputs).is_ok());
+    assert!(full_verifier(&params, pk.get_vk(), &proof, wrong_public_inputs).is_err());
+}
@@ -0,0 +1,139 @@
+// Computes the smallest k such that a circuit fits in 2^k rows. The rows used by the regions, the lookup tables,
+// the instance columns and the blinding rows of the proving system all have to fit in the usable rows,
+// which are found by running the MockProver with increasing values of k.
+
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    dev::MockProver,
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+use std::fmt;
+
+// Upper bound of the search used by min_k
+pub const MAX_K: u32 = 20;
+
+#[derive(Debug)]
+pub enum MinKError {
+    // the regions, the lookup tables or the instance values of the circuit don't fit in 2^max_k rows
+    CircuitTooLarge { max_k: u32 },
+    // the number of instance columns passed doesn't match the circuit
+    InvalidInstances { expected: usize, actual: usize },
+    // the synthesis of the circuit failed for a reason unrelated to the number of rows
+    Synthesis(Error),
+}
+
+impl fmt::Display for MinKError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CircuitTooLarge { max_k } => {
+                write!(f, "the circuit needs more than 2^{} rows", max_k)
+            }
+            Self::InvalidInstances { expected, actual } => write!(
+                f,
+                "the circuit has {} instance columns, {} were given",
+                expected, actual
+            ),
+            Self::Synthesis(e) => write!(f, "synthesis error: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for MinKError {}
+
+// Returns the smallest k such that the circuit and its public inputs fit in 2^k rows, up to MAX_K
+// The witnesses can be unknown, the constraints are not checked
+pub fn min_k<F: FieldExt, C: Circuit<F>>(
+    circuit: &C,
+    public_inputs: &[Vec<F>],
+) -> Result<u32, MinKError> {
+    min_k_up_to(circuit, public_inputs, MAX_K)
+}
+
+pub fn min_k_up_to<F: FieldExt, C: Circuit<F>>(
+    circuit: &C,
+    public_inputs: &[Vec<F>],
+    max_k: u32,
+) -> Result<u32, MinKError> {
+    let mut cs = ConstraintSystem::default();
+    C::configure(&mut cs);
+    if cs.num_instance_columns() != public_inputs.len() {
+        return Err(MinKError::InvalidInstances {
+            expected: cs.num_instance_columns(),
+            actual: public_inputs.len(),
+        });
+    }
+
+    // the blinding rows and the rows reserved by the proving system are needed even by an empty circuit
+    let mut k = 1;
+    while (1 << k) < cs.minimum_rows() {
+        k += 1;
+    }
+
+    while k <= max_k {
+        match MockProver::run(k, circuit, public_inputs.to_vec()) {
+            Ok(_) => return Ok(k),
+            Err(Error::NotEnoughRowsAvailable { .. }) | Err(Error::InstanceTooLarge) => k += 1,
+            Err(e) => return Err(MinKError::Synthesis(e)),
+        }
+    }
+    Err(MinKError::CircuitTooLarge { max_k })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{min_k, min_k_up_to, MinKError};
+    use crate::circuits::merkle_v3::MerkleTreeV3Circuit;
+    use crate::merkle_tree::MerkleTree;
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp, plonk::Error};
+
+    #[test]
+    fn test_min_k() {
+        let leaves = (0..32u64)
+            .map(|x| Fp::from(x * 3 + 99))
+            .collect::<Vec<Fp>>();
+        let tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5);
+        let proof = tree.proof(13);
+        let circuit = MerkleTreeV3Circuit::<Fp, P128Pow5T3, 5>::from_proof(&proof);
+        let public_inputs = vec![vec![proof.leaf, tree.root()]];
+
+        let k = min_k(&circuit, &public_inputs).unwrap();
+        MockProver::run(k, &circuit, public_inputs.clone())
+            .unwrap()
+            .assert_satisfied();
+        assert!(matches!(
+            MockProver::run(k - 1, &circuit, public_inputs.clone()),
+            Err(Error::NotEnoughRowsAvailable { .. })
+        ));
+
+        // the witnesses are not needed
+        let empty_circuit = MerkleTreeV3Circuit::<Fp, P128Pow5T3, 5>::new(
+            Value::unknown(),
+            [Value::unknown(); 5],
+            [Value::unknown(); 5],
+        );
+        assert_eq!(min_k(&empty_circuit, &public_inputs).unwrap(), k);
+    }
+
+    #[test]
+    fn test_min_k_errors() {
+        let leaves = (0..32u64)
+            .map(|x| Fp::from(x * 3 + 99))
+            .collect::<Vec<Fp>>();
+        let tree = MerkleTree::<Fp, P128Pow5T3>::new(&leaves, 5);
+        let proof = tree.proof(13);
+        let circuit = MerkleTreeV3Circuit::<Fp, P128Pow5T3, 5>::from_proof(&proof);
+
+        assert!(matches!(
+            min_k_up_to(&circuit, &[vec![proof.leaf, tree.root()]], 5),
+            Err(MinKError::CircuitTooLarge { max_k: 5 })
+        ));
+        assert!(matches!(
+            min_k(&circuit, &[]),
+            Err(MinKError::InvalidInstances {
+                expected: 1,
+                actual: 0
+            })
+        ));
+    }
+}
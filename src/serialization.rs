@@ -0,0 +1,470 @@
+/*
+On-disk formats to hand the artifacts of a prover over to a separate verifier.
+
+A ProofBundle contains a proof, its instance columns, the identifier of the circuit and the parameters needed to
+rebuild the circuit on the verifier side (k, Poseidon spec, tree depth), together with the fingerprint of the
+verifying key the proof has been generated for. A bundle can be encoded as JSON (byte strings are hex encoded)
+or as a compact binary encoding (bincode). Every bundle carries a format version, bundles of other versions are rejected.
+The bundle comes from an untrusted prover and its k sizes the parameters of the verifier, so bundles whose k is larger
+than MAX_BUNDLE_K are rejected when they are decoded.
+
+The verifying keys are exported and imported with the RawBytes format of halo2, which is only available on BN254.
+*/
+
+use blake2b_simd::Params as Blake2bParams;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    halo2curves::{
+        bn256::{Fr, G1Affine},
+        group::ff::PrimeField,
+    },
+    plonk::{Circuit, VerifyingKey},
+    SerdeFormat,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+// Version of the format of the ProofBundle, to be increased whenever the format changes
+pub const BUNDLE_VERSION: u32 = 1;
+
+// Largest k accepted in a bundle. 2^26 rows is far more than any circuit of the crate needs, and the parameters of
+// larger sizes take gigabytes of memory
+pub const MAX_BUNDLE_K: u32 = 26;
+
+#[derive(Debug)]
+pub enum SerializationError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Bincode(bincode::Error),
+    UnsupportedVersion(u32),
+    UnsupportedK(u32),
+    InvalidFieldElement,
+    FingerprintMismatch,
+}
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Json(e) => write!(f, "json error: {}", e),
+            Self::Bincode(e) => write!(f, "bincode error: {}", e),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported bundle version {}, expected {}",
+                version, BUNDLE_VERSION
+            ),
+            Self::UnsupportedK(k) => write!(
+                f,
+                "unsupported k = {}, expected at most {}",
+                k, MAX_BUNDLE_K
+            ),
+            Self::InvalidFieldElement => write!(f, "invalid encoding of a field element"),
+            Self::FingerprintMismatch => {
+                write!(f, "the bundle was not generated for this verifying key")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+impl From<std::io::Error> for SerializationError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SerializationError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<bincode::Error> for SerializationError {
+    fn from(e: bincode::Error) -> Self {
+        Self::Bincode(e)
+    }
+}
+
+// Byte string encoded as hex in human readable formats (JSON) and as raw bytes otherwise (bincode)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bytes(pub Vec<u8>);
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&format!("0x{}", hex::encode(&self.0)))
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            hex::decode(s.trim_start_matches("0x"))
+                .map(Bytes)
+                .map_err(serde::de::Error::custom)
+        } else {
+            Vec::<u8>::deserialize(deserializer).map(Bytes)
+        }
+    }
+}
+
+// Encodes a field element as the little-endian bytes of its canonical representation
+pub fn field_to_bytes<F: FieldExt>(value: &F) -> Bytes {
+    Bytes(value.to_repr().as_ref().to_vec())
+}
+
+// Parses a field element from a decimal string or from a big-endian hex string starting with 0x
+pub fn field_from_str<F: FieldExt>(s: &str) -> Result<F, SerializationError> {
+    match s.strip_prefix("0x") {
+        Some(hex) => {
+            let padded = if hex.len() % 2 == 1 {
+                format!("0{}", hex)
+            } else {
+                hex.to_string()
+            };
+            let mut bytes =
+                hex::decode(padded).map_err(|_| SerializationError::InvalidFieldElement)?;
+            bytes.reverse();
+            let mut repr = <F as PrimeField>::Repr::default();
+            if bytes.len() > repr.as_ref().len() {
+                return Err(SerializationError::InvalidFieldElement);
+            }
+            repr.as_mut()[..bytes.len()].copy_from_slice(&bytes);
+            Option::from(F::from_repr(repr)).ok_or(SerializationError::InvalidFieldElement)
+        }
+        // from_str_vartime reduces the integer modulo p, the decimal must be the canonical one of the field element
+        None => F::from_str_vartime(s)
+            .filter(|value| field_to_decimal(value) == s)
+            .ok_or(SerializationError::InvalidFieldElement),
+    }
+}
+
+// Decimal representation of the canonical integer of a field element, the encoding accepted by field_from_str
+pub fn field_to_decimal<F: FieldExt>(value: &F) -> String {
+    let mut bytes = value.to_repr().as_ref().to_vec();
+    bytes.reverse();
+    let mut digits = vec![];
+    // divide the big-endian integer by 10 until it is zero, the remainders are the digits
+    loop {
+        let mut remainder = 0u32;
+        for byte in bytes.iter_mut() {
+            let current = remainder * 256 + *byte as u32;
+            *byte = (current / 10) as u8;
+            remainder = current % 10;
+        }
+        digits.push(char::from(b'0' + remainder as u8));
+        if bytes.iter().all(|byte| *byte == 0) {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+pub fn field_from_bytes<F: FieldExt>(bytes: &Bytes) -> Result<F, SerializationError> {
+    let mut repr = <F as PrimeField>::Repr::default();
+    if repr.as_ref().len() != bytes.0.len() {
+        return Err(SerializationError::InvalidFieldElement);
+    }
+    repr.as_mut().copy_from_slice(&bytes.0);
+    Option::from(F::from_repr(repr)).ok_or(SerializationError::InvalidFieldElement)
+}
+
+// Parameters needed to rebuild the circuit, and therefore its keys, on the verifier side
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitParams {
+    pub k: u32,
+    pub poseidon_spec: Option<String>,
+    pub depth: Option<usize>,
+    pub num_inputs: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofBundle {
+    pub version: u32,
+    pub circuit: String,
+    pub params: CircuitParams,
+    // values of each instance column
+    pub instances: Vec<Vec<Bytes>>,
+    pub proof: Bytes,
+    pub vk_fingerprint: Bytes,
+}
+
+// The version is the first field of every format, it is read before decoding the rest of the bundle
+#[derive(Deserialize)]
+struct BundleVersion {
+    version: u32,
+}
+
+impl ProofBundle {
+    pub fn new<F: FieldExt>(
+        circuit: &str,
+        params: CircuitParams,
+        instances: &[Vec<F>],
+        proof: Vec<u8>,
+        vk_fingerprint: [u8; 32],
+    ) -> Self {
+        Self {
+            version: BUNDLE_VERSION,
+            circuit: circuit.to_string(),
+            params,
+            instances: instances
+                .iter()
+                .map(|column| column.iter().map(field_to_bytes).collect())
+                .collect(),
+            proof: Bytes(proof),
+            vk_fingerprint: Bytes(vk_fingerprint.to_vec()),
+        }
+    }
+
+    // Decodes the instance columns, in the format expected by the provers and verifiers
+    pub fn instances<F: FieldExt>(&self) -> Result<Vec<Vec<F>>, SerializationError> {
+        self.instances
+            .iter()
+            .map(|column| column.iter().map(field_from_bytes).collect())
+            .collect()
+    }
+
+    // Checks that the bundle has been generated for the verifying key
+    pub fn check_vk(&self, vk: &VerifyingKey<G1Affine>) -> Result<(), SerializationError> {
+        if self.vk_fingerprint.0 == vk_fingerprint(vk) {
+            Ok(())
+        } else {
+            Err(SerializationError::FingerprintMismatch)
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, SerializationError> {
+        self.check_current_version()?;
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SerializationError> {
+        let bundle: Self = match serde_json::from_str::<BundleVersion>(json)?.version {
+            BUNDLE_VERSION => serde_json::from_str(json)?,
+            version => return Err(SerializationError::UnsupportedVersion(version)),
+        };
+        bundle.check_k()?;
+        Ok(bundle)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializationError> {
+        self.check_current_version()?;
+        Ok(bincode::serialize(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        let bundle: Self = match bincode::deserialize::<BundleVersion>(bytes)?.version {
+            BUNDLE_VERSION => bincode::deserialize(bytes)?,
+            version => return Err(SerializationError::UnsupportedVersion(version)),
+        };
+        bundle.check_k()?;
+        Ok(bundle)
+    }
+
+    // The parameters of the verifier are generated or loaded for the k of the bundle, which must be bounded
+    fn check_k(&self) -> Result<(), SerializationError> {
+        if self.params.k <= MAX_BUNDLE_K {
+            Ok(())
+        } else {
+            Err(SerializationError::UnsupportedK(self.params.k))
+        }
+    }
+
+    // Only the current version is written
+    fn check_current_version(&self) -> Result<(), SerializationError> {
+        if self.version == BUNDLE_VERSION {
+            Ok(())
+        } else {
+            Err(SerializationError::UnsupportedVersion(self.version))
+        }
+    }
+}
+
+pub fn write_vk(vk: &VerifyingKey<G1Affine>) -> Vec<u8> {
+    let mut bytes = vec![];
+    vk.write(&mut bytes, SerdeFormat::RawBytes)
+        .expect("writing to a vector should not fail");
+    bytes
+}
+
+// The constraint system is not part of the encoding, it is rebuilt from the configuration of the circuit C
+pub fn read_vk<C: Circuit<Fr>>(
+    mut bytes: &[u8],
+) -> Result<VerifyingKey<G1Affine>, SerializationError> {
+    Ok(VerifyingKey::read::<_, C>(
+        &mut bytes,
+        SerdeFormat::RawBytes,
+    )?)
+}
+
+// Blake2b hash of the encoding of the verifying key and of its transcript representation. The encoding only contains
+// the commitments, the transcript representation is the hash of the pinned verifying key, which also covers the
+// constraint system: two circuits with the same commitments but different gates have different fingerprints
+pub fn vk_fingerprint(vk: &VerifyingKey<G1Affine>) -> [u8; 32] {
+    let hash = Blake2bParams::new()
+        .hash_length(32)
+        .personal(b"halo2-exp-vk-fp\0")
+        .to_state()
+        .update(&write_vk(vk))
+        .update(vk.transcript_repr().to_repr().as_ref())
+        .finalize();
+    hash.as_bytes().try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        field_from_str, field_to_decimal, read_vk, vk_fingerprint, write_vk, Bytes, CircuitParams,
+        ProofBundle, SerializationError, MAX_BUNDLE_K,
+    };
+    use crate::{
+        chips::poseidon_spec::P128Pow5T3Bn256, circuits::merkle_v3::MerkleTreeV3Circuit,
+        merkle_tree::MerkleTree, proving::kzg,
+    };
+    use halo2_proofs::halo2curves::bn256::Fr;
+
+    type TestCircuit = MerkleTreeV3Circuit<Fr, P128Pow5T3Bn256, 3>;
+
+    fn circuit_params() -> CircuitParams {
+        CircuitParams {
+            k: 9,
+            poseidon_spec: Some("P128Pow5T3Bn256".to_string()),
+            depth: Some(3),
+            num_inputs: None,
+        }
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let instances = vec![vec![Fr::from(1), -Fr::from(1)]];
+        let bundle = ProofBundle::new(
+            "merkle-v3",
+            circuit_params(),
+            &instances,
+            vec![1, 2, 3],
+            [7; 32],
+        );
+
+        let json = bundle.to_json().unwrap();
+        assert!(json.contains("\"proof\": \"0x010203\""));
+        let from_json = ProofBundle::from_json(&json).unwrap();
+        assert_eq!(from_json, bundle);
+        assert_eq!(from_json.instances::<Fr>().unwrap(), instances);
+
+        let from_bytes = ProofBundle::from_bytes(&bundle.to_bytes().unwrap()).unwrap();
+        assert_eq!(from_bytes, bundle);
+
+        // bundles of other versions are rejected
+        let other_version = json.replace("\"version\": 1", "\"version\": 2");
+        assert!(matches!(
+            ProofBundle::from_json(&other_version),
+            Err(SerializationError::UnsupportedVersion(2))
+        ));
+
+        // a k that would make the verifier allocate huge parameters is rejected
+        let mut large_k = bundle.clone();
+        large_k.params.k = MAX_BUNDLE_K + 1;
+        let json = serde_json::to_string(&large_k).unwrap();
+        assert!(matches!(
+            ProofBundle::from_json(&json),
+            Err(SerializationError::UnsupportedK(27))
+        ));
+        let bytes = bincode::serialize(&large_k).unwrap();
+        assert!(matches!(
+            ProofBundle::from_bytes(&bytes),
+            Err(SerializationError::UnsupportedK(27))
+        ));
+        large_k.params.k = MAX_BUNDLE_K;
+        assert!(ProofBundle::from_json(&large_k.to_json().unwrap()).is_ok());
+
+        // non canonical field elements are rejected
+        let mut invalid = bundle;
+        invalid.instances[0][0] = Bytes(vec![0xff; 32]);
+        assert!(invalid.instances::<Fr>().is_err());
+    }
+
+    #[test]
+    fn test_bundle_verification() {
+        // prover side
+        let params = kzg::setup(9, 42);
+        let leaves = (0..8u64).map(Fr::from).collect::<Vec<Fr>>();
+        let tree = MerkleTree::<Fr, P128Pow5T3Bn256>::new(&leaves, 3);
+        let proof = tree.proof(5);
+        let circuit = TestCircuit::from_proof(&proof);
+        let pk = kzg::keys(&params, &circuit).unwrap();
+        let public_inputs = vec![vec![proof.leaf, tree.root()]];
+        let zk_proof = kzg::full_prover(&params, &pk, &circuit, &public_inputs).unwrap();
+
+        let vk_bytes = write_vk(pk.get_vk());
+        let bundle = ProofBundle::new(
+            "merkle-v3",
+            circuit_params(),
+            &public_inputs,
+            zk_proof,
+            vk_fingerprint(pk.get_vk()),
+        );
+        let json = bundle.to_json().unwrap();
+
+        // verifier side
+        let vk = read_vk::<TestCircuit>(&vk_bytes).unwrap();
+        assert_eq!(vk_fingerprint(&vk), vk_fingerprint(pk.get_vk()));
+
+        let bundle = ProofBundle::from_json(&json).unwrap();
+        bundle.check_vk(&vk).unwrap();
+        let instances = bundle.instances::<Fr>().unwrap();
+        assert!(kzg::full_verifier(&params, &vk, &bundle.proof.0, &instances).is_ok());
+
+        // a bundle generated for another verifying key is detected
+        let other_circuit = MerkleTreeV3Circuit::<Fr, P128Pow5T3Bn256, 2>::from_proof(
+            &MerkleTree::<Fr, P128Pow5T3Bn256>::new(&leaves[..4], 2).proof(0),
+        );
+        let other_pk = kzg::keys(&params, &other_circuit).unwrap();
+        assert!(matches!(
+            bundle.check_vk(other_pk.get_vk()),
+            Err(SerializationError::FingerprintMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_field_from_str() {
+        assert_eq!(field_from_str::<Fr>("1234").unwrap(), Fr::from(1234));
+        assert_eq!(field_from_str::<Fr>("0x4d2").unwrap(), Fr::from(1234));
+        assert_eq!(field_from_str::<Fr>("0x04d2").unwrap(), Fr::from(1234));
+        assert!(field_from_str::<Fr>("12a").is_err());
+        assert!(field_from_str::<Fr>("0xzz").is_err());
+
+        // the modulus is not a canonical encoding
+        let modulus = "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001";
+        assert!(field_from_str::<Fr>(modulus).is_err());
+
+        // decimals are only accepted in their canonical form, the integer is not reduced modulo p
+        let modulus =
+            "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+        assert!(field_from_str::<Fr>(modulus).is_err());
+        let modulus_plus_one =
+            "21888242871839275222246405745257275088548364400416034343698204186575808495618";
+        assert!(field_from_str::<Fr>(modulus_plus_one).is_err());
+        let modulus_minus_one =
+            "21888242871839275222246405745257275088548364400416034343698204186575808495616";
+        assert_eq!(
+            field_from_str::<Fr>(modulus_minus_one).unwrap(),
+            -Fr::from(1)
+        );
+        assert_eq!(field_from_str::<Fr>("0").unwrap(), Fr::from(0));
+        assert!(field_from_str::<Fr>("01234").is_err());
+        assert!(field_from_str::<Fr>("").is_err());
+
+        // the decimals printed by the command line are accepted back
+        assert_eq!(field_to_decimal(&Fr::from(1234)), "1234");
+        let value = -Fr::from(1234);
+        assert_eq!(
+            field_from_str::<Fr>(&field_to_decimal(&value)).unwrap(),
+            value
+        );
+    }
+}
@@ -0,0 +1,211 @@
+// Runs the halo2-exp binary as the ops team would: prove a Merkle inclusion, then verify the bundle and check the
+// exit codes, 0 for a valid proof, 1 for an invalid one and 2 for any other error
+
+use halo2_experiments::{proving::kzg, serialization::ProofBundle};
+use std::{fs, path::Path, path::PathBuf, process::Command};
+
+fn halo2_exp(args: &[&str]) -> (i32, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_halo2-exp"))
+        .args(args)
+        .output()
+        .expect("the binary should run");
+    (
+        output.status.code().expect("the binary should exit"),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn test_cli_prove_verify() {
+    let dir: PathBuf = std::env::temp_dir().join(format!("halo2-exp-cli-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+    // parameters standing for the ones of a trusted setup ceremony, larger than needed by the circuit
+    kzg::write_params(&kzg::setup(10, 42), Path::new(&path("kzg.params"))).unwrap();
+    kzg::write_params(&kzg::setup(4, 42), Path::new(&path("small.params"))).unwrap();
+
+    fs::write(
+        path("witness.json"),
+        r#"{ "leaf": "99", "path_elements": ["1", "5", "6"], "path_indices": [0, 1, 0] }"#,
+    )
+    .unwrap();
+    let prove = |setup: &[&str], out: &str| {
+        let mut args = vec![
+            "prove",
+            "merkle-v3",
+            "--input",
+            &path("witness.json"),
+            "--out",
+            out,
+        ];
+        args.extend_from_slice(setup);
+        halo2_exp(&args)
+    };
+    let params = ["--params", &path("kzg.params")];
+    let (code, stdout) = prove(&params, &path("proof.json"));
+    assert_eq!(code, 0);
+
+    // the public inputs computed by the prover are printed in the encoding accepted by verify
+    let public_input = |name: &str| {
+        stdout
+            .lines()
+            .find_map(|line| line.strip_prefix(&format!("{}: ", name)))
+            .expect("prove should print the public inputs")
+            .to_string()
+    };
+    assert_eq!(public_input("leaf"), "99");
+    let root = public_input("root");
+
+    // valid proof
+    let verify = |proof: &str, leaf: &str, root: &str| {
+        halo2_exp(&[
+            "verify",
+            "--params",
+            &path("kzg.params"),
+            "--proof",
+            proof,
+            "--leaf",
+            leaf,
+            "--root",
+            root,
+        ])
+        .0
+    };
+    assert_eq!(verify(&path("proof.json"), "99", &root), 0);
+
+    // valid proof of other public inputs than the expected ones
+    assert_eq!(verify(&path("proof.json"), "98", &root), 1);
+
+    // tampered proof
+    let mut bundle =
+        ProofBundle::from_json(&fs::read_to_string(path("proof.json")).unwrap()).unwrap();
+    bundle.proof.0[40] ^= 1;
+    fs::write(path("tampered.json"), bundle.to_json().unwrap()).unwrap();
+    assert_eq!(verify(&path("tampered.json"), "99", &root), 1);
+
+    // malformed bundle, missing expected public input
+    fs::write(path("malformed.json"), "{ \"version\": 1 }").unwrap();
+    assert_eq!(verify(&path("malformed.json"), "99", &root), 2);
+    assert_eq!(
+        halo2_exp(&[
+            "verify",
+            "--params",
+            &path("kzg.params"),
+            "--proof",
+            &path("proof.json"),
+            "--leaf",
+            "99"
+        ])
+        .0,
+        2
+    );
+
+    // the parameters are required, either from a file large enough for the circuit or from an explicit insecure seed
+    assert_eq!(prove(&[], &path("proof.bin")).0, 2);
+    assert_eq!(
+        prove(&["--params", &path("small.params")], &path("proof.bin")).0,
+        2
+    );
+    assert_eq!(
+        prove(
+            &[
+                "--params",
+                &path("kzg.params"),
+                "--insecure-dev-setup",
+                "42"
+            ],
+            &path("proof.bin")
+        )
+        .0,
+        2
+    );
+    assert_eq!(
+        prove(&["--insecure-dev-setup", "42"], &path("insecure.bin")).0,
+        0
+    );
+    let verify_insecure = |proof: &str| {
+        halo2_exp(&[
+            "verify",
+            "--insecure-dev-setup",
+            "42",
+            "--proof",
+            proof,
+            "--leaf",
+            "99",
+            "--root",
+            &root,
+        ])
+        .0
+    };
+    assert_eq!(verify_insecure(&path("insecure.bin")), 0);
+
+    // a proof generated with other parameters has another verifying key
+    assert_eq!(verify_insecure(&path("proof.json")), 2);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cli_poseidon_prove_verify() {
+    let dir: PathBuf =
+        std::env::temp_dir().join(format!("halo2-exp-cli-poseidon-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+    kzg::write_params(&kzg::setup(10, 42), Path::new(&path("kzg.params"))).unwrap();
+
+    let prove = |inputs: &str, out: &str| {
+        fs::write(
+            path("witness.json"),
+            format!("{{ \"inputs\": {} }}", inputs),
+        )
+        .unwrap();
+        halo2_exp(&[
+            "prove",
+            "poseidon",
+            "--input",
+            &path("witness.json"),
+            "--out",
+            out,
+            "--params",
+            &path("kzg.params"),
+        ])
+    };
+    let (code, stdout) = prove(r#"["1", "2", "3"]"#, &path("proof.json"));
+    assert_eq!(code, 0);
+    let digest = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("digest: "))
+        .expect("prove should print the digest")
+        .to_string();
+
+    let verify = |digest: &[&str]| {
+        let mut args = vec![
+            "verify",
+            "--params",
+            &path("kzg.params"),
+            "--proof",
+            &path("proof.json"),
+        ];
+        args.extend_from_slice(digest);
+        halo2_exp(&args).0
+    };
+
+    // valid proof
+    assert_eq!(verify(&["--digest", &digest]), 0);
+
+    // valid proof of another digest than the expected one
+    assert_eq!(verify(&["--digest", "1"]), 1);
+
+    // the digest is required to verify a poseidon proof
+    assert_eq!(verify(&[]), 2);
+
+    // the circuit hashes 1 to 4 inputs
+    assert_eq!(
+        prove(r#"["1", "2", "3", "4", "5"]"#, &path("five.json")).0,
+        2
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}